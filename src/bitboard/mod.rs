@@ -0,0 +1,207 @@
+use crate::piece::{Sides, Types};
+
+// A set of squares, one bit per square using the same numbering as
+// `board::square` (bit 0 is A1, bit 63 is H8)
+pub const FILE_A: u64 = 0x0101_0101_0101_0101;
+pub const RANK_1: u64 = 0xff;
+
+pub fn bit(square: usize) -> u64 {
+    1 << square
+}
+
+pub fn file_of(square: usize) -> usize {
+    square % 8
+}
+
+pub fn rank_of(square: usize) -> usize {
+    square / 8
+}
+
+// King moves from one square to the other
+pub fn distance(from: usize, to: usize) -> usize {
+    let files = (file_of(from) as i32 - file_of(to) as i32).unsigned_abs();
+    let ranks = (rank_of(from) as i32 - rank_of(to) as i32).unsigned_abs();
+    files.max(ranks) as usize
+}
+
+pub fn file_mask(file: usize) -> u64 {
+    FILE_A << file
+}
+
+pub fn rank_mask(rank: usize) -> u64 {
+    RANK_1 << (8 * rank)
+}
+
+pub fn adjacent_files(file: usize) -> u64 {
+    let mut mask = 0;
+    if file > 0 {
+        mask |= file_mask(file - 1);
+    }
+    if file < 7 {
+        mask |= file_mask(file + 1);
+    }
+    mask
+}
+
+// Rank counted from the side's own back rank, so a White pawn on the 7th and
+// a Black pawn on the 2nd are both on relative rank 6
+pub fn relative_rank(side: Sides, square: usize) -> usize {
+    match side {
+        Sides::White => rank_of(square),
+        Sides::Black => 7 - rank_of(square),
+    }
+}
+
+// Every rank strictly in front of `rank` from `side`'s point of view
+pub fn forward_ranks(side: Sides, rank: usize) -> u64 {
+    match side {
+        Sides::White => {
+            if rank >= 7 {
+                0
+            } else {
+                !0 << (8 * (rank + 1))
+            }
+        }
+        Sides::Black => {
+            if rank == 0 {
+                0
+            } else {
+                !0 >> (8 * (8 - rank))
+            }
+        }
+    }
+}
+
+// Squares directly in front of the square, up to the end of the board
+pub fn forward_file(side: Sides, square: usize) -> u64 {
+    forward_ranks(side, rank_of(square)) & file_mask(file_of(square))
+}
+
+// The squares an enemy pawn would have to be on to stop a pawn on `square`
+// from being passed
+pub fn passed_pawn_span(side: Sides, square: usize) -> u64 {
+    let file = file_of(square);
+    forward_ranks(side, rank_of(square)) & (file_mask(file) | adjacent_files(file))
+}
+
+pub fn pawn_attacks(side: Sides, square: usize) -> u64 {
+    let from = bit(square);
+    let not_a = !FILE_A;
+    let not_h = !file_mask(7);
+    match side {
+        Sides::White => ((from & not_a) << 7) | ((from & not_h) << 9),
+        Sides::Black => ((from & not_a) >> 9) | ((from & not_h) >> 7),
+    }
+}
+
+pub fn pawn_attacks_all(side: Sides, pawns: u64) -> u64 {
+    squares(pawns).fold(0, |attacks, square| attacks | pawn_attacks(side, square))
+}
+
+pub struct Squares(u64);
+
+impl Iterator for Squares {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        if self.0 == 0 {
+            return None;
+        }
+        let square = self.0.trailing_zeros() as usize;
+        self.0 &= self.0 - 1;
+        Some(square)
+    }
+}
+
+pub fn squares(bitboard: u64) -> Squares {
+    Squares(bitboard)
+}
+
+const KNIGHT_STEPS: [(i32, i32); 8] = [
+    (1, 2),
+    (2, 1),
+    (2, -1),
+    (1, -2),
+    (-1, -2),
+    (-2, -1),
+    (-2, 1),
+    (-1, 2),
+];
+const KING_STEPS: [(i32, i32); 8] = [
+    (0, 1),
+    (1, 1),
+    (1, 0),
+    (1, -1),
+    (0, -1),
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+];
+const ROOK_DIRECTIONS: [(i32, i32); 4] = [(0, 1), (1, 0), (0, -1), (-1, 0)];
+const BISHOP_DIRECTIONS: [(i32, i32); 4] = [(1, 1), (1, -1), (-1, -1), (-1, 1)];
+
+// Steps are (files, ranks). Anything that would fall off the board is dropped.
+fn offset(square: usize, (files, ranks): (i32, i32)) -> Option<usize> {
+    let file = file_of(square) as i32 + files;
+    let rank = rank_of(square) as i32 + ranks;
+    if (0..8).contains(&file) && (0..8).contains(&rank) {
+        Some((rank * 8 + file) as usize)
+    } else {
+        None
+    }
+}
+
+fn step_attacks(square: usize, steps: &[(i32, i32)]) -> u64 {
+    steps
+        .iter()
+        .filter_map(|step| offset(square, *step))
+        .fold(0, |attacks, to| attacks | bit(to))
+}
+
+// Slides along each direction until it runs into something, including the
+// square of whatever it ran into
+fn ray_attacks(square: usize, occupied: u64, directions: &[(i32, i32)]) -> u64 {
+    let mut attacks = 0;
+    for direction in directions.iter() {
+        let mut from = square;
+        while let Some(to) = offset(from, *direction) {
+            attacks |= bit(to);
+            if occupied & bit(to) != 0 {
+                break;
+            }
+            from = to;
+        }
+    }
+    attacks
+}
+
+pub fn knight_attacks(square: usize) -> u64 {
+    step_attacks(square, &KNIGHT_STEPS)
+}
+
+pub fn king_attacks(square: usize) -> u64 {
+    step_attacks(square, &KING_STEPS)
+}
+
+pub fn rook_attacks(square: usize, occupied: u64) -> u64 {
+    ray_attacks(square, occupied, &ROOK_DIRECTIONS)
+}
+
+pub fn bishop_attacks(square: usize, occupied: u64) -> u64 {
+    ray_attacks(square, occupied, &BISHOP_DIRECTIONS)
+}
+
+pub fn queen_attacks(square: usize, occupied: u64) -> u64 {
+    rook_attacks(square, occupied) | bishop_attacks(square, occupied)
+}
+
+pub fn attacks(_type: Types, side: Sides, square: usize, occupied: u64) -> u64 {
+    match _type {
+        Types::Pawn => pawn_attacks(side, square),
+        Types::Rook => rook_attacks(square, occupied),
+        Types::Bishop => bishop_attacks(square, occupied),
+        Types::Knight => knight_attacks(square),
+        Types::Queen => queen_attacks(square, occupied),
+        Types::King => king_attacks(square),
+    }
+}
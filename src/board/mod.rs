@@ -15,3 +15,30 @@ pub fn cols() -> Vec<char> {
 pub fn rows() -> Vec<u32> {
     vec![1, 2, 3, 4, 5, 6, 7, 8]
 }
+
+// Squares are numbered 0..64 starting at A1, then B1, ... H1, A2 and so on up to H8
+pub fn square(location: (char, u32)) -> usize {
+    let (col, row) = location;
+    let col_index = cols().iter().position(|&c| c == col).unwrap();
+    (row as usize - 1) * 8 + col_index
+}
+
+pub fn location(square: usize) -> (char, u32) {
+    (cols()[square % 8], (square / 8) as u32 + 1)
+}
+
+// Lowercase algebraic name of a square, like "e4"
+pub fn square_name(square: usize) -> String {
+    let (col, row) = location(square);
+    format!("{}{}", col.to_ascii_lowercase(), row)
+}
+
+pub fn parse_square(name: &str) -> Option<usize> {
+    let mut chars = name.chars();
+    let col = chars.next()?.to_ascii_uppercase();
+    let row = chars.next()?.to_digit(10)?;
+    if chars.next().is_some() || !cols().contains(&col) || !rows().contains(&row) {
+        return None;
+    }
+    Some(square((col, row)))
+}
@@ -0,0 +1,112 @@
+use crate::book::pgn::{self, Game, Outcome};
+use crate::book::{encode_move, key, Book, Entry};
+use crate::piece::Sides;
+use std::collections::HashMap;
+
+// Makes a Polyglot book out of a collection of games. Every move played in
+// the first plies of a game is counted, along with how it scored for the
+// side that played it: 2 for a win and 1 for a draw, the way Polyglot itself
+// weighs them. Moves seen in too few games are left out.
+
+const DEFAULT_MIN_GAMES: u32 = 3;
+const DEFAULT_MAX_PLY: u32 = 30;
+
+#[derive(Debug, Default, Clone, Copy)]
+struct Stats {
+    games: u32,
+    score: u64,
+}
+
+pub struct BookBuilder {
+    // A move needs to have been played in at least this many games
+    pub min_games: u32,
+    // How deep into each game to go
+    pub max_ply: u32,
+    stats: HashMap<(u64, u16), Stats>,
+    games: usize,
+    skipped: usize,
+}
+
+impl Default for BookBuilder {
+    fn default() -> BookBuilder {
+        BookBuilder::new()
+    }
+}
+
+impl BookBuilder {
+    pub fn new() -> BookBuilder {
+        BookBuilder {
+            min_games: DEFAULT_MIN_GAMES,
+            max_ply: DEFAULT_MAX_PLY,
+            stats: HashMap::new(),
+            games: 0,
+            skipped: 0,
+        }
+    }
+
+    // How many games have gone in so far
+    pub fn games(&self) -> usize {
+        self.games
+    }
+
+    // How many games were left out for having a move that can't be played
+    pub fn skipped(&self) -> usize {
+        self.skipped
+    }
+
+    pub fn add_game(&mut self, game: &Game) -> Result<(), String> {
+        let (mut position, moves) = game.replay()?;
+        for mv in moves.into_iter().take(self.max_ply as usize) {
+            let score = match (game.outcome, position.side_to_move) {
+                (Outcome::WhiteWins, Sides::White) | (Outcome::BlackWins, Sides::Black) => 2,
+                (Outcome::Draw, _) => 1,
+                _ => 0,
+            };
+            let stats = self
+                .stats
+                .entry((key::key(&position), encode_move(&position, mv)))
+                .or_default();
+            stats.games += 1;
+            stats.score += score;
+            position.make_move(mv);
+        }
+        self.games += 1;
+        Ok(())
+    }
+
+    // Adds every game in the PGN text, skipping any with a move that can't
+    // be played. Says how many went in.
+    pub fn add_pgn(&mut self, text: &str) -> usize {
+        let mut added = 0;
+        for game in pgn::parse(text).iter() {
+            match self.add_game(game) {
+                Ok(()) => added += 1,
+                Err(_) => self.skipped += 1,
+            }
+        }
+        added
+    }
+
+    // Moves that never scored anything get no weight, so they're dropped as
+    // well. Weights are scaled down if need be to fit in 16 bits.
+    pub fn build(&self) -> Book {
+        let kept: Vec<(&(u64, u16), &Stats)> = self
+            .stats
+            .iter()
+            .filter(|(_, stats)| stats.games >= self.min_games && stats.score > 0)
+            .collect();
+        let heaviest = kept.iter().map(|(_, stats)| stats.score).max().unwrap_or(0);
+        let scale = heaviest.div_ceil(u16::MAX as u64).max(1);
+
+        let entries = kept
+            .into_iter()
+            .map(|((key, mv), stats)| Entry {
+                key: *key,
+                mv: *mv,
+                weight: (stats.score / scale).max(1) as u16,
+                learn: 0,
+            })
+            .collect();
+        Book::new(entries)
+    }
+}
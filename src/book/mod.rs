@@ -0,0 +1,186 @@
+pub mod builder;
+pub mod key;
+pub mod pgn;
+
+use crate::piece::Types;
+use crate::position::{Move, Position};
+use crate::search::skill::next_random;
+use std::fs;
+use std::path::Path;
+
+// Opening books in the Polyglot format: a file of 16 byte entries sorted by
+// position key, each a move someone thought worth playing there and how much
+// they liked it. Everything is big endian.
+
+const ENTRY_SIZE: usize = 16;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Entry {
+    pub key: u64,
+    // Polyglot's own move encoding, see encode_move
+    pub mv: u16,
+    pub weight: u16,
+    // Left alone, nothing we do learns
+    pub learn: u32,
+}
+
+// How to pick between the book moves for a position
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Choice {
+    // At random, in proportion to their weights
+    Weighted,
+    // Always the one with the highest weight
+    Best,
+}
+
+pub struct Book {
+    entries: Vec<Entry>,
+    pub choice: Choice,
+    // Stop using the book after this many plies of the game
+    pub max_depth: Option<u32>,
+}
+
+fn big_endian(bytes: &[u8]) -> u64 {
+    bytes
+        .iter()
+        .fold(0, |value, byte| value << 8 | *byte as u64)
+}
+
+// Polyglot moves are the from and to squares, 6 bits each, with the
+// promotion above them: knight 1, bishop 2, rook 3, queen 4. Castling is
+// written as the king taking its own rook.
+pub fn encode_move(position: &Position, mv: Move) -> u16 {
+    let king = position.piece_at(mv.from).map(|(_type, _)| _type) == Some(Types::King);
+    let to = match (king, mv.from, mv.to) {
+        (true, 4, 6) | (true, 60, 62) => mv.to + 1,
+        (true, 4, 2) | (true, 60, 58) => mv.to - 2,
+        _ => mv.to,
+    };
+    let promotion = match mv.promotion {
+        Some(Types::Knight) => 1,
+        Some(Types::Bishop) => 2,
+        Some(Types::Rook) => 3,
+        Some(Types::Queen) => 4,
+        _ => 0,
+    };
+    (promotion << 12 | mv.from << 6 | to) as u16
+}
+
+// The legal move `mv` stands for in this position, if there is one
+pub fn decode_move(position: &mut Position, mv: u16) -> Option<Move> {
+    let moves = position.legal_moves();
+    moves
+        .into_iter()
+        .find(|legal| encode_move(position, *legal) == mv)
+}
+
+impl Book {
+    pub fn new(entries: Vec<Entry>) -> Book {
+        let mut entries = entries;
+        entries.sort_by_key(|entry| (entry.key, std::cmp::Reverse(entry.weight)));
+        Book {
+            entries,
+            choice: Choice::Weighted,
+            max_depth: None,
+        }
+    }
+
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Book, String> {
+        let path = path.as_ref();
+        let bytes =
+            fs::read(path).map_err(|error| format!("can't read {}: {}", path.display(), error))?;
+        Book::from_bytes(&bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Book, String> {
+        if !bytes.len().is_multiple_of(ENTRY_SIZE) {
+            return Err(format!(
+                "a Polyglot book is a whole number of {} byte entries, this is {} bytes",
+                ENTRY_SIZE,
+                bytes.len()
+            ));
+        }
+        let entries = bytes
+            .chunks(ENTRY_SIZE)
+            .map(|chunk| Entry {
+                key: big_endian(&chunk[0..8]),
+                mv: big_endian(&chunk[8..10]) as u16,
+                weight: big_endian(&chunk[10..12]) as u16,
+                learn: big_endian(&chunk[12..16]) as u32,
+            })
+            .collect();
+        Ok(Book::new(entries))
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.entries.len() * ENTRY_SIZE);
+        for entry in self.entries.iter() {
+            bytes.extend_from_slice(&entry.key.to_be_bytes());
+            bytes.extend_from_slice(&entry.mv.to_be_bytes());
+            bytes.extend_from_slice(&entry.weight.to_be_bytes());
+            bytes.extend_from_slice(&entry.learn.to_be_bytes());
+        }
+        bytes
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let path = path.as_ref();
+        fs::write(path, self.to_bytes())
+            .map_err(|error| format!("can't write {}: {}", path.display(), error))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // The book's legal moves for this position with their weights, heaviest
+    // first
+    pub fn moves(&self, position: &mut Position) -> Vec<(Move, u16)> {
+        let key = key::key(position);
+        let start = self.entries.partition_point(|entry| entry.key < key);
+        self.entries[start..]
+            .iter()
+            .take_while(|entry| entry.key == key)
+            .filter_map(|entry| decode_move(position, entry.mv).map(|mv| (mv, entry.weight)))
+            .collect()
+    }
+
+    // How many plies into the game this position is, going by its move
+    // number
+    fn ply(position: &Position) -> u32 {
+        position.fullmove_number.saturating_sub(1) * 2 + position.side_to_move.index() as u32
+    }
+
+    // The move to play from the book, or None when we're out of it. Weighted
+    // choices draw on `random`, so the same state gives the same moves.
+    pub fn choose(&self, position: &mut Position, random: &mut u64) -> Option<Move> {
+        if self
+            .max_depth
+            .is_some_and(|depth| Book::ply(position) >= depth)
+        {
+            return None;
+        }
+        let moves = self.moves(position);
+        match self.choice {
+            Choice::Best => moves.first().map(|(mv, _)| *mv),
+            Choice::Weighted => {
+                let total: u64 = moves.iter().map(|(_, weight)| *weight as u64).sum();
+                if total == 0 {
+                    return moves.first().map(|(mv, _)| *mv);
+                }
+                let mut roll = next_random(random) % total;
+                for (mv, weight) in moves.iter() {
+                    if roll < *weight as u64 {
+                        return Some(*mv);
+                    }
+                    roll -= *weight as u64;
+                }
+                None
+            }
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use crate::position::{self, Move, Position};
+
+// Just enough PGN to get games out of a collection: the tags, the main line
+// in SAN and the result. Comments, variations and annotation glyphs are
+// skipped.
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Outcome {
+    WhiteWins,
+    BlackWins,
+    Draw,
+    // "*", or a game that stops without saying
+    Unknown,
+}
+
+impl Outcome {
+    fn from_token(token: &str) -> Option<Outcome> {
+        match token {
+            "1-0" => Some(Outcome::WhiteWins),
+            "0-1" => Some(Outcome::BlackWins),
+            "1/2-1/2" => Some(Outcome::Draw),
+            "*" => Some(Outcome::Unknown),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Game {
+    pub tags: Vec<(String, String)>,
+    // In SAN, as written
+    pub moves: Vec<String>,
+    pub outcome: Outcome,
+}
+
+impl Game {
+    fn new() -> Game {
+        Game {
+            tags: Vec::new(),
+            moves: Vec::new(),
+            outcome: Outcome::Unknown,
+        }
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    // Where the game starts, which is the normal starting position unless
+    // there's a FEN tag
+    pub fn start(&self) -> Result<Position, String> {
+        match self.tag("FEN") {
+            Some(fen) => position::from_fen(fen),
+            None => Ok(position::start()),
+        }
+    }
+
+    // The moves played, checked for legality along the way
+    pub fn replay(&self) -> Result<(Position, Vec<Move>), String> {
+        let mut position = self.start()?;
+        let start = position.clone();
+        let mut moves = Vec::with_capacity(self.moves.len());
+        for san in self.moves.iter() {
+            let mv = position.parse_san(san).ok_or(format!(
+                "illegal move {} in {}",
+                san,
+                position.to_fen()
+            ))?;
+            position.make_move(mv);
+            moves.push(mv);
+        }
+        Ok((start, moves))
+    }
+}
+
+// A token outside tags and comments is a move, maybe with its number stuck
+// to the front ("12.e4", "12...e5"), or a result, which ends the game
+fn finish(token: &mut String, game: &mut Game, games: &mut Vec<Game>) {
+    if token.is_empty() {
+        return;
+    }
+    if let Some(outcome) = Outcome::from_token(token) {
+        game.outcome = outcome;
+        games.push(std::mem::replace(game, Game::new()));
+    } else {
+        let mv = token.rsplit('.').next().unwrap_or_default();
+        if !mv.is_empty() {
+            game.moves.push(mv.to_string());
+        }
+    }
+    token.clear();
+}
+
+// Every game in `text`. A game ends at its result, or where the next one's
+// tags start.
+pub fn parse(text: &str) -> Vec<Game> {
+    let mut games = Vec::new();
+    let mut game = Game::new();
+    let mut chars = text.chars().peekable();
+    let mut token = String::new();
+
+    while let Some(letter) = chars.next() {
+        match letter {
+            '[' => {
+                finish(&mut token, &mut game, &mut games);
+                if !game.moves.is_empty() {
+                    games.push(std::mem::replace(&mut game, Game::new()));
+                }
+                let tag: String = chars.by_ref().take_while(|letter| *letter != ']').collect();
+                let (name, value) = tag
+                    .trim()
+                    .split_once(char::is_whitespace)
+                    .unwrap_or((tag.trim(), ""));
+                let value = value.trim().trim_matches('"').replace("\\\"", "\"");
+                game.tags.push((name.to_string(), value));
+            }
+            '{' => {
+                finish(&mut token, &mut game, &mut games);
+                chars.by_ref().find(|letter| *letter == '}');
+            }
+            ';' => {
+                finish(&mut token, &mut game, &mut games);
+                chars.by_ref().find(|letter| *letter == '\n');
+            }
+            '(' => {
+                finish(&mut token, &mut game, &mut games);
+                let mut depth = 1;
+                while depth > 0 {
+                    match chars.next() {
+                        Some('(') => depth += 1,
+                        Some(')') => depth -= 1,
+                        Some('{') => {
+                            chars.by_ref().find(|letter| *letter == '}');
+                        }
+                        Some(_) => {}
+                        None => break,
+                    }
+                }
+            }
+            '$' => {
+                finish(&mut token, &mut game, &mut games);
+                while chars.peek().is_some_and(|letter| letter.is_ascii_digit()) {
+                    chars.next();
+                }
+            }
+            // An escaped line, for whatever another program wanted to keep
+            '%' if token.is_empty() => {
+                chars.by_ref().find(|letter| *letter == '\n');
+            }
+            letter if letter.is_whitespace() => finish(&mut token, &mut game, &mut games),
+            letter => token.push(letter),
+        }
+    }
+    finish(&mut token, &mut game, &mut games);
+    if !game.moves.is_empty() {
+        games.push(game);
+    }
+    games
+}
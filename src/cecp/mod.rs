@@ -0,0 +1,423 @@
+use crate::position::{self, Move, Position};
+use crate::search::background::Background;
+use crate::search::{self, time, Limits, Score, SearchResult, Searcher};
+use std::sync::mpsc::Sender;
+use std::time::Duration;
+
+// The Chess Engine Communication Protocol that XBoard and WinBoard speak.
+// Unlike UCI the engine keeps the game itself: it's told the moves as they're
+// played, thinks whenever it's its turn unless it's in force mode, and plays
+// its own moves with "move". Searches run in the background like UCI's, so
+// "?" and the rest still get through while it thinks.
+
+const NAME: &str = "rust-chess";
+
+// Mates go out as this plus the number of moves, the way XBoard shows them
+const MATE_SCORE: i32 = 100000;
+
+pub struct Cecp {
+    output: Sender<String>,
+    position: Position,
+    // Away with the search while there is one
+    searcher: Option<Searcher>,
+    search: Option<Background>,
+    // Only keeps track of the moves, without thinking
+    force: bool,
+    // Send thinking output
+    post: bool,
+    depth: Option<u32>,
+    // Time for each move, set by "st"
+    move_time: Option<Duration>,
+    // Moves in each time control, from "level", or 0 for all the rest
+    moves_per_session: u32,
+    increment: Duration,
+    // Time left on our clock, from "time"
+    clock: Option<Duration>,
+    // Playing the fischerandom variant, which is Chess960
+    chess960: bool,
+    crazyhouse: bool,
+}
+
+fn score(score: i32) -> i32 {
+    match Score::from(score) {
+        Score::Centipawns(centipawns) => centipawns,
+        Score::Mate(moves) if moves < 0 => -MATE_SCORE + moves,
+        Score::Mate(moves) => MATE_SCORE + moves,
+    }
+}
+
+// The line of play in SAN, which is what XBoard shows
+fn line(position: &Position, pv: &[Move]) -> String {
+    let mut position = position.clone();
+    let mut moves = Vec::new();
+    for mv in pv {
+        moves.push(position.san(*mv));
+        position.make_move(*mv);
+    }
+    moves.join(" ")
+}
+
+// "ply score time nodes pv", with the time in centiseconds
+pub fn thinking(position: &Position, result: &SearchResult) -> String {
+    format!(
+        "{} {} {} {} {}",
+        result.depth,
+        score(result.score),
+        result.time.as_millis() / 10,
+        result.nodes,
+        line(position, &result.pv)
+    )
+}
+
+// "40 5 0" is 40 moves in 5 minutes with no increment, and "0 2:30 1" all of
+// them in two and a half minutes with a second for each move
+fn parse_level(arguments: &[&str]) -> Result<(u32, Duration, Duration), String> {
+    let usage = "expected level <moves> <minutes[:seconds]> <increment>";
+    let (moves, base, increment) = match arguments {
+        [moves, base, increment] => (moves, base, increment),
+        _ => return Err(usage.to_string()),
+    };
+    let moves = moves.parse().map_err(|_| usage.to_string())?;
+    let mut parts = base.split(':');
+    let minutes: u64 = parts
+        .next()
+        .and_then(|minutes| minutes.parse().ok())
+        .ok_or(usage)?;
+    let seconds: u64 = match parts.next() {
+        Some(seconds) => seconds.parse().map_err(|_| usage.to_string())?,
+        None => 0,
+    };
+    let increment: f64 = increment.parse().map_err(|_| usage.to_string())?;
+    let base = minutes
+        .checked_mul(60)
+        .and_then(|base| base.checked_add(seconds))
+        .ok_or(usage)?;
+    let increment = Duration::try_from_secs_f64(increment.max(0.0)).map_err(|_| usage)?;
+    Ok((moves, Duration::from_secs(base), increment))
+}
+
+impl Cecp {
+    pub fn new(output: Sender<String>) -> Cecp {
+        Cecp {
+            output,
+            position: position::start(),
+            searcher: Some(Searcher::new(search::Options::default())),
+            search: None,
+            force: false,
+            post: false,
+            depth: None,
+            move_time: None,
+            moves_per_session: 0,
+            increment: Duration::default(),
+            clock: None,
+            chess960: false,
+            crazyhouse: false,
+        }
+    }
+
+    fn send<S: Into<String>>(&self, line: S) {
+        // Nobody to tell if the other end has gone
+        let _ = self.output.send(line.into());
+    }
+
+    // Carries out one command, returning false once it's time to quit
+    pub fn command(&mut self, line: &str) -> bool {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let (command, arguments) = match words.split_first() {
+            Some((command, arguments)) => (*command, arguments),
+            None => return true,
+        };
+        // A move we sent since the last command is on the board from here on
+        if self.search.as_ref().is_some_and(Background::is_done) {
+            self.cancel();
+        }
+
+        let outcome = match command {
+            "xboard" | "accepted" | "rejected" | "random" | "computer" | "name" | "rating"
+            | "ics" | "hard" | "easy" | "otim" | "draw" | "hint" | "bk" | "white" | "black"
+            | "holding" => Ok(()),
+            "protover" => {
+                self.send(format!(
+                    "feature myname=\"{} {}\" ping=1 setboard=1 usermove=1 time=1 draw=0 \
+                     sigint=0 sigterm=0 reuse=1 analyze=0 colors=0 memory=1 smp=1 \
+                     variants=\"normal,fischerandom,crazyhouse\" done=1",
+                    NAME,
+                    env!("CARGO_PKG_VERSION")
+                ));
+                Ok(())
+            }
+            "new" => {
+                self.cancel();
+                self.position = position::start();
+                self.chess960 = false;
+                self.crazyhouse = false;
+                self.force = false;
+                self.depth = None;
+                self.move_time = None;
+                self.searcher().clear();
+                Ok(())
+            }
+            "force" | "result" => {
+                self.cancel();
+                self.force = true;
+                Ok(())
+            }
+            "go" => {
+                self.cancel();
+                self.force = false;
+                self.think()
+            }
+            "playother" => {
+                self.cancel();
+                self.force = false;
+                Ok(())
+            }
+            "usermove" => match arguments.first() {
+                Some(text) => self.user_move(text),
+                None => Err("no move given".to_string()),
+            },
+            "variant" => match arguments.first() {
+                Some(&"normal") | Some(&"fischerandom") | Some(&"crazyhouse") => {
+                    self.cancel();
+                    self.chess960 = arguments[0] == "fischerandom";
+                    self.crazyhouse = arguments[0] == "crazyhouse";
+                    self.position.chess960 = self.chess960;
+                    self.position.crazyhouse = self.crazyhouse;
+                    Ok(())
+                }
+                Some(variant) => Err(format!("no variant called {}", variant)),
+                None => Err("expected a variant".to_string()),
+            },
+            "setboard" => {
+                self.cancel();
+                match position::from_fen(&arguments.join(" ")) {
+                    Ok(mut position) => {
+                        position.chess960 |= self.chess960;
+                        position.crazyhouse |= self.crazyhouse;
+                        self.position = position;
+                        Ok(())
+                    }
+                    Err(_) => {
+                        self.send("tellusererror Illegal position");
+                        Ok(())
+                    }
+                }
+            }
+            "level" => parse_level(arguments).map(|(moves, base, increment)| {
+                self.moves_per_session = moves;
+                self.increment = increment;
+                self.clock = Some(base);
+                self.move_time = None;
+            }),
+            "st" => match arguments
+                .first()
+                .and_then(|seconds| seconds.parse::<f64>().ok())
+                .and_then(|seconds| Duration::try_from_secs_f64(seconds.max(0.0)).ok())
+            {
+                Some(seconds) => {
+                    self.move_time = Some(seconds);
+                    Ok(())
+                }
+                None => Err("expected st <seconds>".to_string()),
+            },
+            "sd" => match arguments.first().and_then(|depth| depth.parse().ok()) {
+                Some(depth) => {
+                    self.depth = Some(depth);
+                    Ok(())
+                }
+                None => Err("expected sd <depth>".to_string()),
+            },
+            "time" => match arguments.first().and_then(|time| time.parse::<u64>().ok()) {
+                Some(centiseconds) => {
+                    self.clock = Some(Duration::from_millis(centiseconds.saturating_mul(10)));
+                    Ok(())
+                }
+                None => Err("expected time <centiseconds>".to_string()),
+            },
+            "post" => {
+                self.post = true;
+                Ok(())
+            }
+            "nopost" => {
+                self.post = false;
+                Ok(())
+            }
+            "undo" | "remove" => {
+                self.cancel();
+                let plies = if command == "undo" { 1 } else { 2 };
+                for _ in 0..plies {
+                    if !self.position.history.is_empty() {
+                        self.position.unmake_move();
+                    }
+                }
+                Ok(())
+            }
+            "?" => {
+                if let Some(search) = self.search.take() {
+                    let (searcher, result) = search.stop();
+                    self.take_back(searcher, Some(result));
+                }
+                Ok(())
+            }
+            "ping" => {
+                self.send(format!("pong {}", arguments.first().unwrap_or(&"")));
+                Ok(())
+            }
+            "memory" => match arguments
+                .first()
+                .and_then(|size| size.parse::<usize>().ok())
+            {
+                Some(megabytes) => {
+                    self.searcher().set_hash_size(megabytes.max(1));
+                    Ok(())
+                }
+                None => Err("expected memory <megabytes>".to_string()),
+            },
+            "cores" => match arguments
+                .first()
+                .and_then(|cores| cores.parse::<usize>().ok())
+            {
+                Some(cores) => {
+                    self.searcher().set_threads(cores);
+                    Ok(())
+                }
+                None => Err("expected cores <threads>".to_string()),
+            },
+            "quit" => {
+                self.cancel();
+                return false;
+            }
+            // Old interfaces send the move on its own
+            _ if self.parse_move(command).is_some() => self.user_move(command),
+            _ => {
+                self.send(format!("Error (unknown command): {}", command));
+                Ok(())
+            }
+        };
+        if let Err(error) = outcome {
+            self.send(format!("Error ({}): {}", error, line.trim()));
+        }
+        true
+    }
+
+    // Coordinate notation, or SAN for interfaces that send that
+    fn parse_move(&mut self, text: &str) -> Option<Move> {
+        self.position
+            .parse_move(text)
+            .or_else(|| self.position.parse_san(text))
+    }
+
+    fn user_move(&mut self, text: &str) -> Result<(), String> {
+        self.cancel();
+        let mv = match self.parse_move(text) {
+            Some(mv) => mv,
+            None => {
+                self.send(format!("Illegal move: {}", text));
+                return Ok(());
+            }
+        };
+        self.position.make_move(mv);
+        if let Some(ending) = self.position.ending() {
+            self.send(format!("{} {{{}}}", ending.result(), ending.reason()));
+            return Ok(());
+        }
+        if self.force {
+            Ok(())
+        } else {
+            self.think()
+        }
+    }
+
+    // The searcher, once any search that has it is over
+    fn searcher(&mut self) -> &mut Searcher {
+        self.cancel();
+        self.searcher
+            .as_mut()
+            .expect("the searcher comes back when a search ends")
+    }
+
+    // Takes the searcher back, and plays the move the search sent if it got
+    // that far
+    fn take_back(&mut self, searcher: Searcher, result: Option<SearchResult>) {
+        self.searcher = Some(searcher);
+        if let Some(mv) = result.and_then(|result| result.best_move) {
+            self.position.make_move(mv);
+        }
+    }
+
+    // Stops thinking without moving, unless the move already went out
+    fn cancel(&mut self) {
+        if let Some(search) = self.search.take() {
+            let (searcher, result) = search.cancel();
+            self.take_back(searcher, result);
+        }
+    }
+
+    fn limits(&self) -> Limits {
+        let mut limits = Limits {
+            depth: self.depth,
+            time: self.move_time,
+            ..Default::default()
+        };
+        if limits.time.is_none() {
+            if let Some(clock) = self.clock {
+                let played = self.position.fullmove_number.saturating_sub(1);
+                let moves_to_go = match self.moves_per_session {
+                    0 => None,
+                    moves => Some(moves - played % moves),
+                };
+                limits.time = Some(time::allocate(clock, self.increment, moves_to_go));
+            }
+        }
+        limits
+    }
+
+    // Starts looking for a move for the side to move, which gets sent when
+    // the search is done
+    fn think(&mut self) -> Result<(), String> {
+        if self.position.ending().is_some() {
+            return Ok(());
+        }
+        let limits = self.limits();
+        let mut searcher = self
+            .searcher
+            .take()
+            .expect("the searcher comes back when a search ends");
+
+        let reporter: Option<search::Reporter> = if self.post {
+            let output = self.output.clone();
+            let position = self.position.clone();
+            Some(Box::new(move |result: &SearchResult| {
+                let _ = output.send(thinking(&position, result));
+            }))
+        } else {
+            None
+        };
+        searcher.set_reporter(reporter);
+
+        let output = self.output.clone();
+        let mut after = self.position.clone();
+        self.search = Some(Background::start(
+            searcher,
+            self.position.clone(),
+            limits,
+            false,
+            move |result| {
+                if let Some(mv) = result.best_move {
+                    // XBoard wants castling in Chess960 as O-O or O-O-O
+                    let text = if after.chess960 && after.is_castling(mv) {
+                        after.san(mv).trim_end_matches(['+', '#']).to_string()
+                    } else {
+                        mv.to_string()
+                    };
+                    let _ = output.send(format!("move {}", text));
+                    after.make_move(mv);
+                    if let Some(ending) = after.ending() {
+                        let _ = output.send(format!("{} {{{}}}", ending.result(), ending.reason()));
+                    }
+                }
+            },
+        )?);
+        Ok(())
+    }
+}
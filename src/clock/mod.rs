@@ -0,0 +1,275 @@
+use crate::piece::Sides;
+use crate::position::{Ending, Position};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+// A chess clock: time for each side, running for one of them at a time, set
+// by a time control of one or more stages. Everything takes the time it's
+// asked at, so what the clock says only depends on what it's told.
+
+// What happens around each move, besides the time going down
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Timing {
+    // Sudden death: nothing
+    Plain,
+    // Fischer: this much gets added after every move
+    Increment(Duration),
+    // Bronstein: what a move took gets added back, up to this much
+    Bronstein(Duration),
+    // Simple or US delay: the time only starts going down after this much
+    Delay(Duration),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stage {
+    // Moves to make in the time, or None for the rest of the game
+    pub moves: Option<u32>,
+    pub time: Duration,
+    pub timing: Timing,
+}
+
+// Stages one after another, each adding its time once the one before is
+// over. A last stage with a number of moves starts again every time it's
+// done, the way 40 moves in 90 minutes repeats.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimeControl {
+    pub stages: Vec<Stage>,
+}
+
+impl TimeControl {
+    pub fn sudden_death(time: Duration) -> TimeControl {
+        TimeControl::single(time, Timing::Plain)
+    }
+
+    pub fn fischer(time: Duration, increment: Duration) -> TimeControl {
+        TimeControl::single(time, Timing::Increment(increment))
+    }
+
+    pub fn bronstein(time: Duration, delay: Duration) -> TimeControl {
+        TimeControl::single(time, Timing::Bronstein(delay))
+    }
+
+    pub fn delay(time: Duration, delay: Duration) -> TimeControl {
+        TimeControl::single(time, Timing::Delay(delay))
+    }
+
+    fn single(time: Duration, timing: Timing) -> TimeControl {
+        TimeControl {
+            stages: vec![Stage {
+                moves: None,
+                time,
+                timing,
+            }],
+        }
+    }
+
+    // Stages separated by commas, each "[moves/]minutes" and then what
+    // happens on each move in seconds: "+30" for an increment, "d5" for a
+    // delay and "b5" for Bronstein. "5+3" is five minutes with three seconds
+    // a move, and "40/90+30,30+30" the usual classical control.
+    pub fn parse(text: &str) -> Result<TimeControl, String> {
+        let error = || format!("can't read the time control {}", text);
+        let seconds = |text: &str| -> Result<Duration, String> {
+            let seconds = text.parse::<f64>().map_err(|_| error())?;
+            Duration::try_from_secs_f64(seconds).map_err(|_| error())
+        };
+        let mut stages = Vec::new();
+        for stage in text.split(',').map(str::trim) {
+            let (moves, rest) = match stage.split_once('/') {
+                Some((moves, rest)) => match moves.parse() {
+                    Ok(moves) if moves > 0 => (Some(moves), rest),
+                    _ => return Err(error()),
+                },
+                None => (None, stage),
+            };
+            let (minutes, timing) = match rest.find(['+', 'd', 'b']) {
+                Some(split) => rest.split_at(split),
+                None => (rest, ""),
+            };
+            let timing = match timing.chars().next() {
+                None => Timing::Plain,
+                Some('+') => Timing::Increment(seconds(&timing[1..])?),
+                Some('d') => Timing::Delay(seconds(&timing[1..])?),
+                _ => Timing::Bronstein(seconds(&timing[1..])?),
+            };
+            stages.push(Stage {
+                moves,
+                time: seconds(minutes)?.checked_mul(60).ok_or_else(error)?,
+                timing,
+            });
+        }
+        Ok(TimeControl { stages })
+    }
+}
+
+// The way parse reads it
+impl fmt::Display for TimeControl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (index, stage) in self.stages.iter().enumerate() {
+            if index > 0 {
+                write!(f, ",")?;
+            }
+            if let Some(moves) = stage.moves {
+                write!(f, "{}/", moves)?;
+            }
+            write!(f, "{}", stage.time.as_secs_f64() / 60.0)?;
+            match stage.timing {
+                Timing::Plain => {}
+                Timing::Increment(time) => write!(f, "+{}", time.as_secs_f64())?,
+                Timing::Delay(time) => write!(f, "d{}", time.as_secs_f64())?,
+                Timing::Bronstein(time) => write!(f, "b{}", time.as_secs_f64())?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Clock {
+    control: TimeControl,
+    remaining: [Duration; 2],
+    // The stage each side is in, and the moves it has made in it
+    stage: [usize; 2],
+    moves: [u32; 2],
+    // Whose time is going, and since when
+    running: Option<(Sides, Instant)>,
+}
+
+impl Clock {
+    pub fn new(control: TimeControl) -> Clock {
+        let time = control
+            .stages
+            .first()
+            .map_or(Duration::default(), |stage| stage.time);
+        Clock {
+            control,
+            remaining: [time; 2],
+            stage: [0; 2],
+            moves: [0; 2],
+            running: None,
+        }
+    }
+
+    pub fn control(&self) -> &TimeControl {
+        &self.control
+    }
+
+    fn current(&self, side: Sides) -> Option<&Stage> {
+        self.control.stages.get(self.stage[side.index()])
+    }
+
+    fn timing(&self, side: Sides) -> Timing {
+        self.current(side)
+            .map_or(Timing::Plain, |stage| stage.timing)
+    }
+
+    // Time that comes back with each move one way or another, for planning
+    pub fn increment(&self, side: Sides) -> Duration {
+        match self.timing(side) {
+            Timing::Plain => Duration::default(),
+            Timing::Increment(time) | Timing::Bronstein(time) | Timing::Delay(time) => time,
+        }
+    }
+
+    // Moves left until the next stage's time comes, if it does
+    pub fn moves_to_go(&self, side: Sides) -> Option<u32> {
+        let moves = self.current(side)?.moves?;
+        Some(moves - self.moves[side.index()])
+    }
+
+    pub fn running(&self) -> Option<Sides> {
+        self.running.map(|(side, _)| side)
+    }
+
+    pub fn remaining(&self, side: Sides, now: Instant) -> Duration {
+        let remaining = self.remaining[side.index()];
+        match self.running {
+            Some((running, since)) if running == side => {
+                let elapsed = now.saturating_duration_since(since);
+                let counted = match self.timing(side) {
+                    Timing::Delay(delay) => elapsed.saturating_sub(delay),
+                    _ => elapsed,
+                };
+                remaining.saturating_sub(counted)
+            }
+            _ => remaining,
+        }
+    }
+
+    // Starts `side`'s time, stopping the other's as it is
+    pub fn start(&mut self, side: Sides, now: Instant) {
+        self.stop(now);
+        self.running = Some((side, now));
+    }
+
+    pub fn stop(&mut self, now: Instant) {
+        if let Some((side, _)) = self.running {
+            self.remaining[side.index()] = self.remaining(side, now);
+            self.running = None;
+        }
+    }
+
+    // The side whose time is going has moved. Unless it had already run out
+    // it gets whatever comes back for that, and the next stage's time once
+    // it's made the moves for this one. Then the other side's time starts.
+    pub fn press(&mut self, now: Instant) {
+        let (side, since) = match self.running {
+            Some(running) => running,
+            None => return,
+        };
+        self.stop(now);
+        let index = side.index();
+        if !self.remaining[index].is_zero() {
+            let back = match self.timing(side) {
+                Timing::Increment(increment) => increment,
+                Timing::Bronstein(delay) => now.saturating_duration_since(since).min(delay),
+                Timing::Plain | Timing::Delay(_) => Duration::default(),
+            };
+            self.remaining[index] = self.remaining[index].saturating_add(back);
+            self.moves[index] += 1;
+            if self.moves_to_go(side) == Some(0) {
+                self.moves[index] = 0;
+                self.stage[index] = (self.stage[index] + 1).min(self.control.stages.len() - 1);
+                let time = self.control.stages[self.stage[index]].time;
+                self.remaining[index] = self.remaining[index].saturating_add(time);
+            }
+        }
+        self.running = Some((side.opposite(), now));
+    }
+
+    // The side that has run out of time, if one has
+    pub fn flagged(&self, now: Instant) -> Option<Sides> {
+        [Sides::White, Sides::Black]
+            .iter()
+            .copied()
+            .find(|side| self.remaining(*side, now).is_zero())
+    }
+}
+
+// How the game ends when `flagged` runs out of time: a loss, unless the other
+// side couldn't have mated anyway
+pub fn flag_fall(position: &Position, flagged: Sides) -> Ending {
+    if position.can_mate(flagged.opposite()) {
+        Ending::Timeout(flagged.opposite())
+    } else {
+        Ending::TimeoutDraw
+    }
+}
+
+// "4:59", or "1:02:03" once there's an hour or more, and "0:09.7" with tenths
+// when it gets close
+pub fn format(time: Duration) -> String {
+    let seconds = time.as_secs();
+    if seconds >= 3600 {
+        format!(
+            "{}:{:02}:{:02}",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        )
+    } else if seconds >= 10 {
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    } else {
+        format!("0:{:02}.{}", seconds, time.subsec_millis() / 100)
+    }
+}
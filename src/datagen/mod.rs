@@ -0,0 +1,160 @@
+use crate::piece::Sides;
+use crate::position::{self, Position};
+use crate::search::skill::next_random;
+use crate::search::{Limits, Searcher, TB_BOUND};
+use std::io::Write;
+
+// Training data for the network from self-play: every game starts with a few
+// random moves so no two are alike, then the engine plays both sides and each
+// quiet position it passes through is written out with its search score and
+// how the game ended. Lines look like
+//
+//     <fen> | <score> | <result>
+//
+// with the score in centipawns and the result 1.0, 0.5 or 0.0, both for
+// White. The tuner reads the same lines.
+
+// Games that go on this long are stopped and decided by the score
+const ADJUDICATE_SCORE: i32 = 1000;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Options {
+    pub games: usize,
+    // How hard to search each move
+    pub limits: Limits,
+    // Random moves at the start of each game
+    pub random_plies: usize,
+    pub max_plies: usize,
+    pub seed: u64,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            games: 100,
+            limits: Limits {
+                depth: Some(6),
+                ..Default::default()
+            },
+            random_plies: 8,
+            max_plies: 300,
+            seed: 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record {
+    pub fen: String,
+    // Centipawns for White
+    pub score: i32,
+    // 1.0 when White went on to win, 0.5 for a draw and 0.0 for a loss
+    pub result: f64,
+}
+
+impl Record {
+    pub fn to_line(&self) -> String {
+        format!("{} | {} | {:.1}", self.fen, self.score, self.result)
+    }
+}
+
+fn for_white(score: i32, side: Sides) -> i32 {
+    match side {
+        Sides::White => score,
+        Sides::Black => -score,
+    }
+}
+
+// Plays random moves, trying again whenever they run into the end of the
+// game, since the point is to have a game left to play
+fn random_opening(plies: usize, random: &mut u64) -> Position {
+    loop {
+        let mut position = position::start();
+        for _ in 0..plies {
+            let moves = position.legal_moves();
+            if moves.is_empty() {
+                break;
+            }
+            let mv = moves[(next_random(random) % moves.len() as u64) as usize];
+            position.make_move(mv);
+        }
+        if position.ending().is_none() {
+            return position;
+        }
+    }
+}
+
+// How a finished game came out for White, or nothing while it goes on. Unlike
+// in the search, a position coming round a second time doesn't end it.
+pub fn game_result(position: &mut Position) -> Option<f64> {
+    let ending = position.ending()?;
+    Some(match ending.winner() {
+        Some(Sides::White) => 1.0,
+        Some(Sides::Black) => 0.0,
+        None => 0.5,
+    })
+}
+
+// One game of self-play and the positions worth training on from it. Those
+// are left out when the side to move is in check, when the best move is a
+// capture or promotion, or once a mate has been found, as the score there
+// says more about the search than the position.
+pub fn play_game(searcher: &mut Searcher, options: &Options, random: &mut u64) -> Vec<Record> {
+    let mut position = random_opening(options.random_plies, random);
+    searcher.clear();
+
+    let mut positions: Vec<(String, i32)> = Vec::new();
+    let mut last_score = 0;
+    let result = loop {
+        if let Some(result) = game_result(&mut position) {
+            break result;
+        }
+        if position.history.len() >= options.max_plies {
+            break if last_score >= ADJUDICATE_SCORE {
+                1.0
+            } else if last_score <= -ADJUDICATE_SCORE {
+                0.0
+            } else {
+                0.5
+            };
+        }
+
+        let result = searcher.search(&mut position, &options.limits);
+        let mv = match result.best_move {
+            Some(mv) => mv,
+            None => break 0.5,
+        };
+        last_score = for_white(result.score, position.side_to_move);
+        let quiet = !position.is_capture(mv) && mv.promotion.is_none();
+        if quiet && !position.in_check() && result.score.abs() < TB_BOUND {
+            positions.push((position.to_fen(), last_score));
+        }
+        position.make_move(mv);
+    };
+
+    positions
+        .into_iter()
+        .map(|(fen, score)| Record { fen, score, result })
+        .collect()
+}
+
+// Plays `options.games` games and writes their positions to `output`,
+// returning how many positions that was. `progress` hears after every game.
+pub fn generate<W: Write, F: FnMut(usize, usize)>(
+    searcher: &mut Searcher,
+    options: &Options,
+    output: &mut W,
+    mut progress: F,
+) -> Result<usize, String> {
+    let mut random = options.seed.max(1);
+    let mut written = 0;
+    for game in 1..=options.games {
+        for record in play_game(searcher, options, &mut random) {
+            writeln!(output, "{}", record.to_line())
+                .map_err(|error| format!("can't write training data: {}", error))?;
+            written += 1;
+        }
+        progress(game, written);
+    }
+    Ok(written)
+}
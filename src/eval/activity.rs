@@ -0,0 +1,143 @@
+use crate::bitboard::{self, adjacent_files, file_mask};
+use crate::eval::{AttackMap, Params, Tapered};
+use crate::piece::{Sides, Types};
+use crate::position::Position;
+
+// How well `side`'s pieces are doing, term by term, so each one can be looked
+// at on its own
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Activity {
+    pub mobility: Tapered,
+    pub rook_files: Tapered,
+    pub rook_seventh: Tapered,
+    pub bishop_pair: Tapered,
+    pub outposts: Tapered,
+    pub trapped: Tapered,
+}
+
+impl Activity {
+    pub fn total(&self) -> Tapered {
+        self.mobility
+            + self.rook_files
+            + self.rook_seventh
+            + self.bishop_pair
+            + self.outposts
+            + self.trapped
+    }
+}
+
+// Squares a piece can go to that count for mobility: not blocked by our own
+// pieces and not covered by an enemy pawn
+pub fn mobility_area(position: &Position, attacks: &AttackMap, side: Sides) -> u64 {
+    let enemy_pawn_attacks = attacks.by_type[side.opposite().index()][Types::Pawn.index()];
+    !position.occupancy(side) & !enemy_pawn_attacks
+}
+
+pub fn evaluate(
+    position: &Position,
+    params: &Params,
+    attacks: &AttackMap,
+    side: Sides,
+) -> Activity {
+    let mut activity = Activity::default();
+    let them = side.opposite();
+    let occupied = position.all();
+    let area = mobility_area(position, attacks, side);
+    let our_pawns = position.pieces(Types::Pawn, side);
+    let their_pawns = position.pieces(Types::Pawn, them);
+
+    for _type in [Types::Rook, Types::Bishop, Types::Knight, Types::Queen].iter() {
+        for square in bitboard::squares(position.pieces(*_type, side)) {
+            let reachable = bitboard::attacks(*_type, side, square, occupied) & area;
+            let count = reachable.count_ones() as usize;
+            activity.mobility += params.mobility[_type.index()][count];
+
+            match _type {
+                Types::Rook => {
+                    let file = file_mask(bitboard::file_of(square));
+                    if file & (our_pawns | their_pawns) == 0 {
+                        activity.rook_files += params.rook_open_file;
+                    } else if file & our_pawns == 0 {
+                        activity.rook_files += params.rook_semi_open_file;
+                    }
+
+                    if on_seventh(position, side, square) {
+                        activity.rook_seventh += params.rook_seventh;
+                    }
+
+                    if count <= 3 && hemmed_in_by_king(position, side, square) {
+                        activity.trapped += params.trapped_rook;
+                    }
+                }
+                Types::Bishop if bishop_trapped(position, side, square) => {
+                    activity.trapped += params.trapped_bishop;
+                }
+                Types::Knight if is_outpost(position, side, square) => {
+                    activity.outposts += params.knight_outpost;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    if position.pieces(Types::Bishop, side).count_ones() >= 2 {
+        activity.bishop_pair += params.bishop_pair;
+    }
+
+    activity
+}
+
+// Only worth anything if there's something to do there: pawns to eat or the
+// enemy king stuck on its back rank
+fn on_seventh(position: &Position, side: Sides, square: usize) -> bool {
+    if bitboard::relative_rank(side, square) != 6 {
+        return false;
+    }
+    let them = side.opposite();
+    let seventh = bitboard::rank_mask(bitboard::rank_of(square));
+    let their_king = position.pieces(Types::King, them);
+    position.pieces(Types::Pawn, them) & seventh != 0
+        || (their_king != 0 && bitboard::relative_rank(them, position.king_square(them)) == 0)
+}
+
+// A rook on the back rank with our king standing between it and the centre,
+// the classic result of moving the king without castling
+fn hemmed_in_by_king(position: &Position, side: Sides, square: usize) -> bool {
+    if position.pieces(Types::King, side) == 0 || bitboard::relative_rank(side, square) != 0 {
+        return false;
+    }
+    let king = position.king_square(side);
+    if bitboard::rank_of(king) != bitboard::rank_of(square) {
+        return false;
+    }
+    let king_file = bitboard::file_of(king);
+    let rook_file = bitboard::file_of(square);
+    (king_file >= 4 && rook_file > king_file) || (king_file <= 3 && rook_file < king_file)
+}
+
+// A bishop that grabbed a pawn on a7/h7 (a2/h2 for Black) and got shut in by
+// the pawn on b6/g6
+fn bishop_trapped(position: &Position, side: Sides, square: usize) -> bool {
+    let (corner_a, corner_h, pawn_b, pawn_g) = match side {
+        Sides::White => (48, 55, 41, 46),
+        Sides::Black => (8, 15, 17, 22),
+    };
+    let their_pawns = position.pieces(Types::Pawn, side.opposite());
+    (square == corner_a && their_pawns & bitboard::bit(pawn_b) != 0)
+        || (square == corner_h && their_pawns & bitboard::bit(pawn_g) != 0)
+}
+
+// A knight on the 4th to 6th rank, defended by a pawn, that no enemy pawn can
+// ever chase away
+fn is_outpost(position: &Position, side: Sides, square: usize) -> bool {
+    let rank = bitboard::relative_rank(side, square);
+    if !(3..=5).contains(&rank) {
+        return false;
+    }
+    let defended =
+        bitboard::pawn_attacks(side.opposite(), square) & position.pieces(Types::Pawn, side) != 0;
+    let chasers = bitboard::forward_ranks(side, bitboard::rank_of(square))
+        & adjacent_files(bitboard::file_of(square))
+        & position.pieces(Types::Pawn, side.opposite());
+    defended && chasers == 0
+}
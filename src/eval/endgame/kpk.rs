@@ -0,0 +1,210 @@
+use crate::bitboard::{self, bit, file_of, king_attacks, pawn_attacks, rank_of};
+use crate::piece::Sides;
+use std::sync::OnceLock;
+
+// Whether king and pawn against king is a win, for every position of the
+// three pieces and either side to move. It's worked out backwards the first
+// time it's needed: positions where the pawn queens safely are wins, ones
+// where the defending king takes the pawn or is stalemated are draws, and
+// the rest get whatever their best move leads to until nothing changes.
+//
+// Positions are seen with the pawn's side as White and the pawn on the a to
+// d files; `probe` flips anything else into that shape first.
+
+// Pawn squares: files a to d, ranks 2 to 7
+const PAWN_SQUARES: usize = 24;
+const POSITIONS: usize = 2 * PAWN_SQUARES * 64 * 64;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Result {
+    Invalid,
+    Unknown,
+    Draw,
+    Win,
+}
+
+fn index(side_to_move: Sides, strong_king: usize, weak_king: usize, pawn: usize) -> usize {
+    let pawn = (rank_of(pawn) - 1) * 4 + file_of(pawn);
+    ((side_to_move.index() * PAWN_SQUARES + pawn) * 64 + strong_king) * 64 + weak_king
+}
+
+struct Position {
+    side_to_move: Sides,
+    strong_king: usize,
+    weak_king: usize,
+    pawn: usize,
+}
+
+impl Position {
+    fn from_index(index: usize) -> Position {
+        let weak_king = index % 64;
+        let strong_king = (index / 64) % 64;
+        let pawn = (index / 64 / 64) % PAWN_SQUARES;
+        let side_to_move = if index / 64 / 64 / PAWN_SQUARES == 0 {
+            Sides::White
+        } else {
+            Sides::Black
+        };
+        Position {
+            side_to_move,
+            strong_king,
+            weak_king,
+            pawn: (pawn / 4 + 1) * 8 + pawn % 4,
+        }
+    }
+
+    fn initial(&self) -> Result {
+        let (strong_king, weak_king, pawn) = (self.strong_king, self.weak_king, self.pawn);
+        if bitboard::distance(strong_king, weak_king) <= 1
+            || pawn == strong_king
+            || pawn == weak_king
+            || (self.side_to_move == Sides::White
+                && pawn_attacks(Sides::White, pawn) & bit(weak_king) != 0)
+        {
+            return Result::Invalid;
+        }
+
+        match self.side_to_move {
+            Sides::White => {
+                // Queens and the new queen can't be taken
+                let queening = pawn + 8;
+                if rank_of(pawn) == 6
+                    && strong_king != queening
+                    && weak_king != queening
+                    && (bitboard::distance(weak_king, queening) > 1
+                        || bitboard::distance(strong_king, queening) == 1)
+                {
+                    return Result::Win;
+                }
+            }
+            Sides::Black => {
+                let guarded = king_attacks(strong_king) | pawn_attacks(Sides::White, pawn);
+                let moves = king_attacks(weak_king) & !guarded;
+                // Stalemated, or the pawn can be taken
+                if moves == 0
+                    || king_attacks(weak_king) & bit(pawn) & !king_attacks(strong_king) != 0
+                {
+                    return Result::Draw;
+                }
+            }
+        }
+        Result::Unknown
+    }
+
+    // Where the side to move can go, as indices. Moves that leave a king
+    // next to the other or in check from the pawn come out as invalid
+    // positions, so they don't need checking here.
+    fn successors(&self) -> Vec<usize> {
+        let mut successors = Vec::new();
+        match self.side_to_move {
+            Sides::White => {
+                for square in bitboard::squares(king_attacks(self.strong_king)) {
+                    successors.push(index(Sides::Black, square, self.weak_king, self.pawn));
+                }
+                // Promotions are dealt with by `initial`
+                let push = self.pawn + 8;
+                if rank_of(self.pawn) < 6 && push != self.strong_king && push != self.weak_king {
+                    successors.push(index(Sides::Black, self.strong_king, self.weak_king, push));
+                    let double = push + 8;
+                    if rank_of(self.pawn) == 1
+                        && double != self.strong_king
+                        && double != self.weak_king
+                    {
+                        successors.push(index(
+                            Sides::Black,
+                            self.strong_king,
+                            self.weak_king,
+                            double,
+                        ));
+                    }
+                }
+            }
+            Sides::Black => {
+                for square in bitboard::squares(king_attacks(self.weak_king)) {
+                    if square != self.pawn {
+                        successors.push(index(Sides::White, self.strong_king, square, self.pawn));
+                    }
+                }
+            }
+        }
+        successors
+    }
+}
+
+fn generate() -> Vec<u64> {
+    let positions: Vec<Position> = (0..POSITIONS).map(Position::from_index).collect();
+    let mut results: Vec<Result> = positions.iter().map(Position::initial).collect();
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (index, position) in positions.iter().enumerate() {
+            if results[index] != Result::Unknown {
+                continue;
+            }
+            let outcomes: Vec<Result> = position
+                .successors()
+                .into_iter()
+                .map(|successor| results[successor])
+                .filter(|result| *result != Result::Invalid)
+                .collect();
+            // Each side picks the best it has, and only settles for the worse
+            // result once every move is known to lead there
+            let (good, bad) = match position.side_to_move {
+                Sides::White => (Result::Win, Result::Draw),
+                Sides::Black => (Result::Draw, Result::Win),
+            };
+            let result = if outcomes.contains(&good) {
+                good
+            } else if outcomes.contains(&Result::Unknown) {
+                Result::Unknown
+            } else {
+                bad
+            };
+            if result != Result::Unknown {
+                results[index] = result;
+                changed = true;
+            }
+        }
+    }
+
+    let mut bits = vec![0u64; POSITIONS / 64];
+    for (index, result) in results.iter().enumerate() {
+        if *result == Result::Win {
+            bits[index / 64] |= 1 << (index % 64);
+        }
+    }
+    bits
+}
+
+// Whether `strong` wins with its king on `strong_king` and pawn on `pawn`
+// against the king on `weak_king`
+pub fn probe(
+    strong: Sides,
+    side_to_move: Sides,
+    strong_king: usize,
+    weak_king: usize,
+    pawn: usize,
+) -> bool {
+    static BITS: OnceLock<Vec<u64>> = OnceLock::new();
+    let bits = BITS.get_or_init(generate);
+
+    let mut squares = [strong_king, weak_king, pawn];
+    if strong == Sides::Black {
+        for square in squares.iter_mut() {
+            *square ^= 56;
+        }
+    }
+    if file_of(squares[2]) > 3 {
+        for square in squares.iter_mut() {
+            *square ^= 7;
+        }
+    }
+    let side_to_move = if side_to_move == strong {
+        Sides::White
+    } else {
+        Sides::Black
+    };
+    let index = index(side_to_move, squares[0], squares[1], squares[2]);
+    bits[index / 64] & (1 << (index % 64)) != 0
+}
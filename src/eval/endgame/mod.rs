@@ -0,0 +1,185 @@
+pub mod kpk;
+
+use crate::bitboard::{self, file_mask, file_of, rank_of, FILE_A};
+use crate::eval::Params;
+use crate::piece::{Sides, Types};
+use crate::position::Position;
+
+// Endgames the general evaluation gets wrong, recognised by what material is
+// left and scored on their own: king and pawn against king from a bitbase,
+// lone kings driven to the edge (or the right corner) to be mated, and
+// endings a side is ahead in but can't win.
+
+// A won endgame scores at least this, so the search heads for it and then
+// makes progress in it, while staying well clear of mate scores
+pub const KNOWN_WIN: i32 = 10000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Endgame {
+    // King and pawn against king
+    Kpk,
+    // Bishop and knight against king, mated in a corner the bishop covers
+    Kbnk,
+    // Plenty to mate a lone king with: a queen, a rook or both bishops
+    Kxk,
+    // Two knights can't force mate
+    Knnk,
+    // Pawns on a rook file with no bishop or the wrong one, and the
+    // defending king already in front of them
+    RookPawns,
+    // A minor piece each and no pawns
+    Minors,
+}
+
+impl Endgame {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Endgame::Kpk => "KPK",
+            Endgame::Kbnk => "KBNK",
+            Endgame::Kxk => "KXK",
+            Endgame::Knnk => "KNNK",
+            Endgame::RookPawns => "rook pawns",
+            Endgame::Minors => "minor against minor",
+        }
+    }
+}
+
+fn count(position: &Position, _type: Types, side: Sides) -> u32 {
+    position.pieces(_type, side).count_ones()
+}
+
+fn dark(square: usize) -> bool {
+    (file_of(square) + rank_of(square)).is_multiple_of(2)
+}
+
+// Higher the further the king is from the middle of the board
+fn push_to_edge(square: usize) -> i32 {
+    let away = |line: usize| if line < 4 { 3 - line } else { line - 4 };
+    20 * (away(file_of(square)) + away(rank_of(square))) as i32
+}
+
+// Higher the closer the king is to one of the two corners of that colour
+fn push_to_corner(square: usize, dark_corners: bool) -> i32 {
+    let corners = if dark_corners { [0, 63] } else { [7, 56] };
+    let steps = |corner: usize| {
+        let files = (file_of(square) as i32 - file_of(corner) as i32).abs();
+        let ranks = (rank_of(square) as i32 - rank_of(corner) as i32).abs();
+        files + ranks
+    };
+    let nearest = steps(corners[0]).min(steps(corners[1])).min(7);
+    30 * (7 - nearest)
+}
+
+// Higher the closer the kings are, since the attacking king has to help
+fn push_close(from: usize, to: usize) -> i32 {
+    10 * (7 - bitboard::distance(from, to) as i32)
+}
+
+fn material(position: &Position, params: &Params, side: Sides) -> i32 {
+    Types::ALL
+        .iter()
+        .map(|_type| params.material[_type.index()].eg * count(position, *_type, side) as i32)
+        .sum()
+}
+
+// The endgame `strong` has against a lone king, and its score for `strong`
+fn against_lone_king(
+    position: &Position,
+    params: &Params,
+    strong: Sides,
+) -> Option<(Endgame, i32)> {
+    let weak = strong.opposite();
+    let weak_king = position.king_square(weak);
+    let strong_king = position.king_square(strong);
+    let pawns = count(position, Types::Pawn, strong);
+    let knights = count(position, Types::Knight, strong);
+    let bishops = position.pieces(Types::Bishop, strong);
+    let heavy = count(position, Types::Rook, strong) + count(position, Types::Queen, strong);
+
+    let pawn = position.pieces(Types::Pawn, strong).trailing_zeros() as usize;
+    if pawns == 1 && heavy == 0 && knights == 0 && bishops == 0 && (1..7).contains(&rank_of(pawn)) {
+        let score = if kpk::probe(strong, position.side_to_move, strong_king, weak_king, pawn) {
+            KNOWN_WIN
+                + params.material[Types::Pawn.index()].eg
+                + 10 * bitboard::relative_rank(strong, pawn) as i32
+        } else {
+            0
+        };
+        return Some((Endgame::Kpk, score));
+    }
+
+    if pawns == 0 && heavy == 0 && knights == 1 && bishops.count_ones() == 1 {
+        let bishop = bishops.trailing_zeros() as usize;
+        let score = KNOWN_WIN
+            + material(position, params, strong)
+            + push_to_corner(weak_king, dark(bishop))
+            + push_close(strong_king, weak_king);
+        return Some((Endgame::Kbnk, score));
+    }
+
+    let both_bishops = bitboard::squares(bishops).any(dark)
+        && bitboard::squares(bishops).any(|square| !dark(square));
+    if heavy > 0 || both_bishops {
+        let score = KNOWN_WIN
+            + material(position, params, strong)
+            + push_to_edge(weak_king)
+            + push_close(strong_king, weak_king);
+        return Some((Endgame::Kxk, score));
+    }
+
+    if pawns == 0 && knights == 2 && bishops == 0 {
+        return Some((Endgame::Knnk, 0));
+    }
+
+    // All the pawns on one rook file, with no bishop that covers the
+    // square they queen on, can't get past a king sitting in front of them
+    let all_pawns = position.pieces(Types::Pawn, strong);
+    let file = if all_pawns & FILE_A == all_pawns {
+        0
+    } else {
+        7
+    };
+    if pawns > 0 && knights == 0 && all_pawns & file_mask(file) == all_pawns {
+        let queening = match strong {
+            Sides::White => 56 + file,
+            Sides::Black => file,
+        };
+        let wrong = bitboard::squares(bishops).all(|bishop| dark(bishop) != dark(queening));
+        if wrong && bitboard::distance(weak_king, queening) <= 1 {
+            return Some((Endgame::RookPawns, 0));
+        }
+    }
+    None
+}
+
+// The endgame `position` is, if it's one of these, and its score in
+// centipawns for White. None of them are endgames in crazyhouse.
+pub fn probe(position: &Position, params: &Params) -> Option<(Endgame, i32)> {
+    if position.crazyhouse
+        || position.pieces(Types::King, Sides::White).count_ones() != 1
+        || position.pieces(Types::King, Sides::Black).count_ones() != 1
+    {
+        return None;
+    }
+    let pieces = [
+        position.occupancy(Sides::White).count_ones(),
+        position.occupancy(Sides::Black).count_ones(),
+    ];
+    for strong in [Sides::White, Sides::Black].iter() {
+        if pieces[strong.opposite().index()] == 1 && pieces[strong.index()] > 1 {
+            return against_lone_king(position, params, *strong).map(
+                |(endgame, score)| match strong {
+                    Sides::White => (endgame, score),
+                    Sides::Black => (endgame, -score),
+                },
+            );
+        }
+    }
+
+    let minor =
+        |side: Sides| count(position, Types::Knight, side) + count(position, Types::Bishop, side);
+    if pieces == [2, 2] && minor(Sides::White) == 1 && minor(Sides::Black) == 1 {
+        return Some((Endgame::Minors, 0));
+    }
+    None
+}
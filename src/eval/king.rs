@@ -0,0 +1,119 @@
+use crate::bitboard::{self, file_mask};
+use crate::eval::{s, AttackMap, Params, Tapered};
+use crate::piece::{Sides, Types};
+use crate::position::Position;
+
+// How exposed `side`'s king is. Everything here is a bonus or (mostly) a
+// penalty for `side`.
+pub fn evaluate(position: &Position, params: &Params, attacks: &AttackMap, side: Sides) -> Tapered {
+    if position.pieces(Types::King, side) == 0 {
+        return Tapered::default();
+    }
+    let king = position.king_square(side);
+
+    let mut score = shelter(position, params, side, king);
+
+    let danger = danger(position, params, attacks, side, king);
+    score += s(-danger * danger / 256, -danger / 8);
+
+    score
+}
+
+// Pawn shield, pawn storm and open files on the king's file and the two
+// beside it
+fn shelter(position: &Position, params: &Params, side: Sides, king: usize) -> Tapered {
+    let ours = position.pieces(Types::Pawn, side);
+    let theirs = position.pieces(Types::Pawn, side.opposite());
+    let in_front = bitboard::forward_ranks(side, bitboard::rank_of(king));
+    let king_rank = bitboard::relative_rank(side, king) as i32;
+
+    // Keep a king on the edge looking at three files too
+    let centre = bitboard::file_of(king).clamp(1, 6);
+
+    let mut score = Tapered::default();
+    for file in centre - 1..=centre + 1 {
+        let our_pawns = ours & file_mask(file);
+        let their_pawns = theirs & file_mask(file);
+
+        match closest(side, our_pawns & in_front) {
+            Some(square) => {
+                let distance = bitboard::relative_rank(side, square) as i32 - king_rank;
+                score += params.pawn_shield[(distance.min(3) - 1) as usize];
+            }
+            None => score += params.pawn_shield[3],
+        }
+
+        if let Some(square) = closest(side, their_pawns & in_front) {
+            let distance = bitboard::relative_rank(side, square) as i32 - king_rank;
+            score += params.pawn_storm[(distance.min(4) - 1) as usize];
+        }
+
+        if our_pawns == 0 {
+            if their_pawns == 0 {
+                score += params.king_open_file;
+            } else {
+                score += params.king_semi_open_file;
+            }
+        }
+    }
+    score
+}
+
+// The pawn nearest to `side`'s back rank
+fn closest(side: Sides, pawns: u64) -> Option<usize> {
+    if pawns == 0 {
+        None
+    } else if side == Sides::White {
+        Some(pawns.trailing_zeros() as usize)
+    } else {
+        Some(63 - pawns.leading_zeros() as usize)
+    }
+}
+
+// Enemy pieces bearing down on the squares around the king, plus checks the
+// opponent could give without losing the checking piece
+fn danger(
+    position: &Position,
+    params: &Params,
+    attacks: &AttackMap,
+    side: Sides,
+    king: usize,
+) -> i32 {
+    let them = side.opposite();
+    let zone = bitboard::king_attacks(king) | bitboard::bit(king);
+    let occupied = position.all();
+
+    let mut attackers = 0;
+    let mut danger = 0;
+    for _type in [Types::Rook, Types::Bishop, Types::Knight, Types::Queen].iter() {
+        for square in bitboard::squares(position.pieces(*_type, them)) {
+            let hits = bitboard::attacks(*_type, them, square, occupied) & zone;
+            if hits != 0 {
+                attackers += 1;
+                danger += params.king_attacker_weight[_type.index()];
+                danger += params.king_zone_attack * hits.count_ones() as i32;
+            }
+        }
+    }
+    // A lone attacker can't do much on its own
+    if attackers < 2 {
+        danger = 0;
+    }
+
+    let safe = !attacks.all[side.index()] & !position.occupancy(them);
+    let rook_lines = bitboard::rook_attacks(king, occupied);
+    let bishop_lines = bitboard::bishop_attacks(king, occupied);
+    let checks = [
+        (Types::Rook, rook_lines),
+        (Types::Bishop, bishop_lines),
+        (Types::Knight, bitboard::knight_attacks(king)),
+        (Types::Queen, rook_lines | bishop_lines),
+    ];
+    for (_type, lines) in checks.iter() {
+        if lines & safe & attacks.by_type[them.index()][_type.index()] != 0 {
+            danger += params.safe_check[_type.index()];
+        }
+    }
+
+    danger
+}
@@ -0,0 +1,382 @@
+pub mod activity;
+pub mod endgame;
+pub mod king;
+pub mod nnue;
+pub mod pawns;
+pub mod personality;
+pub mod pst;
+pub mod trace;
+pub mod values;
+
+use crate::bitboard;
+use crate::piece::{Sides, Types};
+use crate::position::Position;
+use std::ops::{Add, AddAssign, Mul, Neg, Sub, SubAssign};
+use std::sync::Arc;
+
+// Total phase with all minor and major pieces on the board. Knights and bishops
+// count 1, rooks 2 and queens 4.
+pub const MAX_PHASE: i32 = 24;
+pub const PHASE_WEIGHTS: [i32; 6] = [0, 2, 1, 1, 4, 0];
+
+// A middlegame / endgame pair of values, blended together by game phase
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct Tapered {
+    pub mg: i32,
+    pub eg: i32,
+}
+
+pub const fn s(mg: i32, eg: i32) -> Tapered {
+    Tapered { mg, eg }
+}
+
+impl Tapered {
+    pub fn taper(self, phase: i32) -> i32 {
+        (self.mg * phase + self.eg * (MAX_PHASE - phase)) / MAX_PHASE
+    }
+}
+
+impl Add for Tapered {
+    type Output = Tapered;
+
+    fn add(self, other: Tapered) -> Tapered {
+        s(self.mg + other.mg, self.eg + other.eg)
+    }
+}
+
+impl Sub for Tapered {
+    type Output = Tapered;
+
+    fn sub(self, other: Tapered) -> Tapered {
+        s(self.mg - other.mg, self.eg - other.eg)
+    }
+}
+
+impl Neg for Tapered {
+    type Output = Tapered;
+
+    fn neg(self) -> Tapered {
+        s(-self.mg, -self.eg)
+    }
+}
+
+impl Mul<i32> for Tapered {
+    type Output = Tapered;
+
+    fn mul(self, factor: i32) -> Tapered {
+        s(self.mg * factor, self.eg * factor)
+    }
+}
+
+impl AddAssign for Tapered {
+    fn add_assign(&mut self, other: Tapered) {
+        *self = *self + other;
+    }
+}
+
+impl SubAssign for Tapered {
+    fn sub_assign(&mut self, other: Tapered) {
+        *self = *self - other;
+    }
+}
+
+// Everything the evaluation can be tuned with. Arrays indexed by piece type
+// follow the order of `Types`.
+#[derive(Debug, Clone)]
+pub struct Params {
+    pub material: [Tapered; 6],
+    // Crazyhouse pieces in a pocket, which can go anywhere they're needed
+    pub hand: [Tapered; 6],
+    pub pst: [[Tapered; 64]; 6],
+    pub doubled: Tapered,
+    pub isolated: Tapered,
+    pub backward: Tapered,
+    // Pawns defended by another pawn
+    pub chain: Tapered,
+    // For every group of pawns after the first
+    pub island: Tapered,
+    // Passed pawn bonuses by relative rank, with an extra bonus when nothing
+    // at all stands between the pawn and its promotion square
+    pub passed: [Tapered; 8],
+    pub passed_free_path: [Tapered; 8],
+    // King shelter, indexed by how many ranks in front of the king our
+    // closest pawn is on each of the three files around it (1, 2, 3 or more,
+    // missing)
+    pub pawn_shield: [Tapered; 4],
+    // Enemy pawns marching at the king, indexed by the same distance (1, 2,
+    // 3, 4 or more)
+    pub pawn_storm: [Tapered; 4],
+    pub king_semi_open_file: Tapered,
+    pub king_open_file: Tapered,
+    // King danger is built up from these and turned into a penalty that grows
+    // with the square of the danger
+    pub king_attacker_weight: [i32; 6],
+    pub king_zone_attack: i32,
+    pub safe_check: [i32; 6],
+    // By piece type and number of squares the piece can get to
+    pub mobility: [[Tapered; 28]; 6],
+    pub rook_open_file: Tapered,
+    pub rook_semi_open_file: Tapered,
+    pub rook_seventh: Tapered,
+    pub bishop_pair: Tapered,
+    pub knight_outpost: Tapered,
+    pub trapped_rook: Tapered,
+    pub trapped_bishop: Tapered,
+}
+
+impl Default for Params {
+    fn default() -> Params {
+        Params {
+            material: [
+                s(100, 120),
+                s(500, 550),
+                s(330, 320),
+                s(320, 300),
+                s(950, 1000),
+                s(0, 0),
+            ],
+            hand: [
+                s(120, 130),
+                s(520, 560),
+                s(350, 330),
+                s(360, 330),
+                s(1000, 1020),
+                s(0, 0),
+            ],
+            pst: [
+                pst_pairs(&pst::PAWN_MG, &pst::PAWN_EG),
+                pst_pairs(&pst::ROOK_MG, &pst::ROOK_EG),
+                pst_pairs(&pst::BISHOP_MG, &pst::BISHOP_EG),
+                pst_pairs(&pst::KNIGHT_MG, &pst::KNIGHT_EG),
+                pst_pairs(&pst::QUEEN_MG, &pst::QUEEN_EG),
+                pst_pairs(&pst::KING_MG, &pst::KING_EG),
+            ],
+            doubled: s(-10, -20),
+            isolated: s(-10, -15),
+            backward: s(-8, -10),
+            chain: s(8, 6),
+            island: s(-5, -10),
+            passed: [
+                s(0, 0),
+                s(5, 10),
+                s(5, 15),
+                s(10, 25),
+                s(25, 45),
+                s(45, 80),
+                s(70, 120),
+                s(0, 0),
+            ],
+            passed_free_path: [
+                s(0, 0),
+                s(0, 5),
+                s(0, 5),
+                s(5, 10),
+                s(10, 20),
+                s(20, 40),
+                s(30, 60),
+                s(0, 0),
+            ],
+            pawn_shield: [s(15, 0), s(8, 0), s(0, 0), s(-15, 0)],
+            pawn_storm: [s(-5, 0), s(-25, 0), s(-15, 0), s(-5, 0)],
+            king_semi_open_file: s(-15, 0),
+            king_open_file: s(-25, 0),
+            king_attacker_weight: [0, 45, 35, 40, 70, 0],
+            king_zone_attack: 12,
+            safe_check: [0, 90, 50, 70, 80, 0],
+            mobility: [
+                [Tapered::default(); 28],
+                mobility_table(15, s(2, 4), 7),
+                mobility_table(14, s(5, 5), 6),
+                mobility_table(9, s(4, 4), 4),
+                mobility_table(28, s(1, 2), 13),
+                [Tapered::default(); 28],
+            ],
+            rook_open_file: s(25, 10),
+            rook_semi_open_file: s(12, 6),
+            rook_seventh: s(10, 25),
+            bishop_pair: s(30, 50),
+            knight_outpost: s(25, 15),
+            trapped_rook: s(-50, -10),
+            trapped_bishop: s(-100, -100),
+        }
+    }
+}
+
+// Starting values for a mobility table: a fixed amount for every square
+// above or below what's typical for the piece, with the entries past the most
+// squares it could ever reach left at zero
+fn mobility_table(squares: usize, step: Tapered, typical: i32) -> [Tapered; 28] {
+    let mut table = [Tapered::default(); 28];
+    for (count, value) in table.iter_mut().take(squares).enumerate() {
+        *value = step * (count as i32 - typical);
+    }
+    table
+}
+
+fn pst_pairs(mg: &[i32; 64], eg: &[i32; 64]) -> [Tapered; 64] {
+    let mut pairs = [Tapered::default(); 64];
+    for (index, pair) in pairs.iter_mut().enumerate() {
+        *pair = s(mg[index], eg[index]);
+    }
+    pairs
+}
+
+// The tables are written from White's point of view with rank 8 first, so
+// White's squares get flipped vertically and Black's can be used as is.
+pub fn pst_index(square: usize, side: Sides) -> usize {
+    match side {
+        Sides::White => square ^ 56,
+        Sides::Black => square,
+    }
+}
+
+pub const PAWN_TABLE_SIZE: usize = 1 << 14;
+
+pub struct Evaluator {
+    pub params: Params,
+    pub pawn_table: pawns::PawnTable,
+    // Evaluate with this network instead of the hand-crafted terms
+    pub network: Option<Arc<nnue::Network>>,
+}
+
+impl Evaluator {
+    pub fn new() -> Evaluator {
+        Evaluator::with_params(Params::default())
+    }
+
+    pub fn with_params(params: Params) -> Evaluator {
+        Evaluator {
+            params,
+            pawn_table: pawns::PawnTable::new(PAWN_TABLE_SIZE),
+            network: None,
+        }
+    }
+
+    // Score in centipawns from the point of view of the side to move. Known
+    // endgames are scored on their own, network or not. The network only
+    // sees the board, so what's in crazyhouse pockets goes on top of its
+    // score.
+    pub fn evaluate(&mut self, position: &Position) -> i32 {
+        if let Some((_, score)) = endgame::probe(position, &self.params) {
+            return match position.side_to_move {
+                Sides::White => score,
+                Sides::Black => -score,
+            };
+        }
+        if let Some(network) = &self.network {
+            let hand = self.hand(position, position.side_to_move)
+                - self.hand(position, position.side_to_move.opposite());
+            return nnue::evaluate(network, position) + hand.taper(phase(position));
+        }
+        let mut score = self.score_side(position, Sides::White);
+        score -= self.score_side(position, Sides::Black);
+
+        let pawns = self.pawn_structure(position);
+        score += pawns.score;
+        score += self.passed_pawns(position, &pawns, Sides::White);
+        score -= self.passed_pawns(position, &pawns, Sides::Black);
+
+        let attacks = attack_map(position);
+        score += king::evaluate(position, &self.params, &attacks, Sides::White);
+        score -= king::evaluate(position, &self.params, &attacks, Sides::Black);
+
+        score += activity::evaluate(position, &self.params, &attacks, Sides::White).total();
+        score -= activity::evaluate(position, &self.params, &attacks, Sides::Black).total();
+
+        let score = score.taper(phase(position));
+
+        match position.side_to_move {
+            Sides::White => score,
+            Sides::Black => -score,
+        }
+    }
+
+    fn score_side(&self, position: &Position, side: Sides) -> Tapered {
+        let mut score = Tapered::default();
+        for (square, _type, piece_side) in position.occupied() {
+            if piece_side == side {
+                score += self.params.material[_type.index()];
+                score += self.params.pst[_type.index()][pst_index(square, side)];
+            }
+        }
+        score + self.hand(position, side)
+    }
+
+    // Material in the side's crazyhouse pocket
+    pub fn hand(&self, position: &Position, side: Sides) -> Tapered {
+        Types::ALL.iter().fold(Tapered::default(), |score, _type| {
+            score + self.params.hand[_type.index()] * position.in_hand(*_type, side) as i32
+        })
+    }
+
+    pub fn pawn_structure(&mut self, position: &Position) -> pawns::PawnEntry {
+        if let Some(entry) = self.pawn_table.probe(position.pawn_key) {
+            return entry;
+        }
+        let entry = pawns::evaluate(position, &self.params);
+        self.pawn_table.store(entry);
+        entry
+    }
+
+    // The part of the passed pawn evaluation that depends on the other
+    // pieces, so it can't live in the pawn table
+    fn passed_pawns(&self, position: &Position, pawns: &pawns::PawnEntry, side: Sides) -> Tapered {
+        let mut score = Tapered::default();
+        for square in bitboard::squares(pawns.passed[side.index()]) {
+            if bitboard::forward_file(side, square) & position.all() == 0 {
+                score += self.params.passed_free_path[bitboard::relative_rank(side, square)];
+            }
+        }
+        score
+    }
+}
+
+impl Default for Evaluator {
+    fn default() -> Evaluator {
+        Evaluator::new()
+    }
+}
+
+// Squares attacked by each kind of piece on each side
+pub struct AttackMap {
+    pub by_type: [[u64; 6]; 2],
+    pub all: [u64; 2],
+}
+
+pub fn attack_map(position: &Position) -> AttackMap {
+    let mut map = AttackMap {
+        by_type: [[0; 6]; 2],
+        all: [0; 2],
+    };
+    let occupied = position.all();
+    for (square, _type, side) in position.occupied() {
+        let attacks = bitboard::attacks(_type, side, square, occupied);
+        map.by_type[side.index()][_type.index()] |= attacks;
+        map.all[side.index()] |= attacks;
+    }
+    map
+}
+
+// How much non-pawn material is left, from MAX_PHASE at the start of the game
+// down to 0 with bare kings and pawns. Pieces in crazyhouse pockets count as
+// well, since they'll be back.
+pub fn phase(position: &Position) -> i32 {
+    let on_board: i32 = position
+        .occupied()
+        .iter()
+        .map(|(_, _type, _)| PHASE_WEIGHTS[_type.index()])
+        .sum();
+    let in_hand: i32 = Types::ALL
+        .iter()
+        .map(|_type| {
+            let count =
+                position.in_hand(*_type, Sides::White) + position.in_hand(*_type, Sides::Black);
+            PHASE_WEIGHTS[_type.index()] * count as i32
+        })
+        .sum();
+    (on_board + in_hand).min(MAX_PHASE)
+}
+
+pub fn evaluate(position: &Position) -> i32 {
+    Evaluator::new().evaluate(position)
+}
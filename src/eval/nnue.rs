@@ -0,0 +1,248 @@
+use crate::piece::{Sides, Types};
+use crate::position::Position;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+// A small NNUE style network: every piece on its square is an input feature,
+// seen from each side's point of view, and a feature transformer turns the
+// features into one hidden layer per side. Those hidden layers, the
+// accumulators, only change by a few weights when a piece moves, so the
+// position keeps them up to date as moves are made and taken back rather than
+// working them out again for every evaluation. The side to move's layer and
+// then the other side's go through a clipped ReLU into a single output.
+//
+// Everything is quantised: hidden values are in units of 1/QA, output
+// weights in units of 1/QB.
+
+// 2 sides, 6 piece types, 64 squares
+pub const FEATURES: usize = 768;
+const QA: i32 = 255;
+const QB: i32 = 64;
+// The output is trained as a win probability logit, which this turns into
+// centipawns
+const SCALE: i32 = 400;
+// Kept well below mate scores whatever the weights say
+const MAX_SCORE: i32 = 10000;
+
+const MAGIC: &[u8; 4] = b"RCNN";
+const VERSION: u32 = 1;
+
+// Weights files are MAGIC, then VERSION and the hidden layer size as little
+// endian u32s, then little endian i16s: the feature weights feature by
+// feature, the feature biases, the output weights, and last the output bias
+// as an i32.
+pub struct Network {
+    hidden: usize,
+    feature_weights: Vec<i16>,
+    feature_bias: Vec<i16>,
+    output_weights: Vec<i16>,
+    output_bias: i32,
+}
+
+impl fmt::Debug for Network {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Network {{ hidden: {} }}", self.hidden)
+    }
+}
+
+// Reads little endian values off the front of a byte slice
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl Reader<'_> {
+    fn take(&mut self, count: usize) -> Result<&[u8], String> {
+        if self.bytes.len() < count {
+            return Err("weights file is too short".to_string());
+        }
+        let (taken, rest) = self.bytes.split_at(count);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn i16s(&mut self, count: usize) -> Result<Vec<i16>, String> {
+        Ok(self
+            .take(count * 2)?
+            .chunks(2)
+            .map(|pair| i16::from_le_bytes([pair[0], pair[1]]))
+            .collect())
+    }
+}
+
+impl Network {
+    // Small random weights from `seed`, as a starting point for training
+    pub fn random(hidden: usize, seed: u64) -> Network {
+        let mut state = seed.max(1);
+        let mut next = |range: i32| {
+            let value = crate::search::skill::next_random(&mut state);
+            ((value % (2 * range as u64 + 1)) as i32 - range) as i16
+        };
+        Network {
+            hidden,
+            feature_weights: (0..FEATURES * hidden).map(|_| next(32)).collect(),
+            feature_bias: (0..hidden).map(|_| next(32)).collect(),
+            output_weights: (0..2 * hidden).map(|_| next(32)).collect(),
+            output_bias: 0,
+        }
+    }
+
+    pub fn hidden(&self) -> usize {
+        self.hidden
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Network, String> {
+        let mut reader = Reader { bytes };
+        if reader.take(4)? != MAGIC {
+            return Err("not a network weights file".to_string());
+        }
+        let version = reader.u32()?;
+        if version != VERSION {
+            return Err(format!("can't read version {} weights files", version));
+        }
+        let hidden = reader.u32()? as usize;
+        if hidden == 0 || hidden > 4096 {
+            return Err(format!("unlikely hidden layer size {}", hidden));
+        }
+        let network = Network {
+            hidden,
+            feature_weights: reader.i16s(FEATURES * hidden)?,
+            feature_bias: reader.i16s(hidden)?,
+            output_weights: reader.i16s(2 * hidden)?,
+            output_bias: reader.u32()? as i32,
+        };
+        if !reader.bytes.is_empty() {
+            return Err("weights file is too long".to_string());
+        }
+        Ok(network)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        bytes.extend_from_slice(&(self.hidden as u32).to_le_bytes());
+        for weight in self
+            .feature_weights
+            .iter()
+            .chain(self.feature_bias.iter())
+            .chain(self.output_weights.iter())
+        {
+            bytes.extend_from_slice(&weight.to_le_bytes());
+        }
+        bytes.extend_from_slice(&self.output_bias.to_le_bytes());
+        bytes
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Network, String> {
+        let path = path.as_ref();
+        let bytes =
+            fs::read(path).map_err(|error| format!("can't read {}: {}", path.display(), error))?;
+        Network::from_bytes(&bytes)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let path = path.as_ref();
+        fs::write(path, self.to_bytes())
+            .map_err(|error| format!("can't write {}: {}", path.display(), error))
+    }
+
+    fn weights(&self, feature: usize) -> &[i16] {
+        &self.feature_weights[feature * self.hidden..(feature + 1) * self.hidden]
+    }
+}
+
+// The input feature for a piece as `perspective` sees it: its own pieces
+// first, and the board flipped for Black so both sides see it the same way
+fn feature(perspective: Sides, square: usize, _type: Types, side: Sides) -> usize {
+    let relative = (side != perspective) as usize;
+    let square = match perspective {
+        Sides::White => square,
+        Sides::Black => square ^ 56,
+    };
+    relative * 384 + _type.index() * 64 + square
+}
+
+// The hidden layer for each side, indexed by `Sides`, for one position
+#[derive(Debug, Clone)]
+pub struct Accumulators {
+    network: Arc<Network>,
+    values: [Vec<i16>; 2],
+}
+
+impl Accumulators {
+    // Works everything out from scratch
+    pub fn new(network: Arc<Network>, position: &Position) -> Accumulators {
+        let mut accumulators = Accumulators {
+            values: [network.feature_bias.clone(), network.feature_bias.clone()],
+            network,
+        };
+        for (square, _type, side) in position.occupied() {
+            accumulators.update(square, _type, side, true);
+        }
+        accumulators
+    }
+
+    pub fn network(&self) -> &Arc<Network> {
+        &self.network
+    }
+
+    pub fn values(&self, perspective: Sides) -> &[i16] {
+        &self.values[perspective.index()]
+    }
+
+    // A piece was put on or taken off `square`
+    pub fn update(&mut self, square: usize, _type: Types, side: Sides, added: bool) {
+        for perspective in [Sides::White, Sides::Black].iter() {
+            let weights = self
+                .network
+                .weights(feature(*perspective, square, _type, side));
+            let values = &mut self.values[perspective.index()];
+            for (value, weight) in values.iter_mut().zip(weights) {
+                *value = if added {
+                    value.wrapping_add(*weight)
+                } else {
+                    value.wrapping_sub(*weight)
+                };
+            }
+        }
+    }
+
+    // Centipawns from the point of view of `side_to_move`
+    pub fn evaluate(&self, side_to_move: Sides) -> i32 {
+        let network = &self.network;
+        let hidden = network.hidden;
+        let ours = &self.values[side_to_move.index()];
+        let theirs = &self.values[side_to_move.opposite().index()];
+
+        let mut sum: i64 = 0;
+        for (values, weights) in [
+            (ours, &network.output_weights[..hidden]),
+            (theirs, &network.output_weights[hidden..]),
+        ]
+        .iter()
+        {
+            for (value, weight) in values.iter().zip(weights.iter()) {
+                sum += ((*value as i32).clamp(0, QA) * *weight as i32) as i64;
+            }
+        }
+        let score = (sum + network.output_bias as i64) * SCALE as i64 / (QA * QB) as i64;
+        score.clamp(-MAX_SCORE as i64, MAX_SCORE as i64) as i32
+    }
+}
+
+// Evaluates with the position's own accumulators when they belong to this
+// network, otherwise from scratch
+pub fn evaluate(network: &Arc<Network>, position: &Position) -> i32 {
+    match &position.nnue {
+        Some(accumulators) if Arc::ptr_eq(&accumulators.network, network) => {
+            accumulators.evaluate(position.side_to_move)
+        }
+        _ => Accumulators::new(network.clone(), position).evaluate(position.side_to_move),
+    }
+}
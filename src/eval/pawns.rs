@@ -0,0 +1,118 @@
+use crate::bitboard::{self, adjacent_files, file_mask, forward_file, passed_pawn_span};
+use crate::eval::{Params, Tapered};
+use crate::piece::{Sides, Types};
+use crate::position::Position;
+
+// What we remember about a pawn structure. The score is White's minus Black's
+// and the passed pawns are kept so the parts of the passed pawn evaluation
+// that depend on other pieces can be done without rescanning the pawns.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PawnEntry {
+    pub key: u64,
+    pub score: Tapered,
+    pub passed: [u64; 2],
+}
+
+// Pawn structure hardly ever changes from one node to the next, so the result
+// is cached by the position's pawn key
+pub struct PawnTable {
+    entries: Vec<Option<PawnEntry>>,
+}
+
+impl PawnTable {
+    // `size` is rounded up to a power of two
+    pub fn new(size: usize) -> PawnTable {
+        PawnTable {
+            entries: vec![None; size.max(1).next_power_of_two()],
+        }
+    }
+
+    fn index(&self, key: u64) -> usize {
+        (key as usize) & (self.entries.len() - 1)
+    }
+
+    pub fn probe(&self, key: u64) -> Option<PawnEntry> {
+        match self.entries[self.index(key)] {
+            Some(entry) if entry.key == key => Some(entry),
+            _ => None,
+        }
+    }
+
+    pub fn store(&mut self, entry: PawnEntry) {
+        let index = self.index(entry.key);
+        self.entries[index] = Some(entry);
+    }
+
+    pub fn clear(&mut self) {
+        for entry in self.entries.iter_mut() {
+            *entry = None;
+        }
+    }
+}
+
+pub fn evaluate(position: &Position, params: &Params) -> PawnEntry {
+    let (white, white_passed) = evaluate_side(position, params, Sides::White);
+    let (black, black_passed) = evaluate_side(position, params, Sides::Black);
+
+    PawnEntry {
+        key: position.pawn_key,
+        score: white - black,
+        passed: [white_passed, black_passed],
+    }
+}
+
+pub fn evaluate_side(position: &Position, params: &Params, side: Sides) -> (Tapered, u64) {
+    let ours = position.pieces(Types::Pawn, side);
+    let theirs = position.pieces(Types::Pawn, side.opposite());
+
+    let mut score = Tapered::default();
+    let mut passed = 0;
+
+    for square in bitboard::squares(ours) {
+        let file = bitboard::file_of(square);
+        let rank = bitboard::relative_rank(side, square);
+        let neighbours = ours & adjacent_files(file);
+
+        if neighbours == 0 {
+            score += params.isolated;
+        } else if rank < 7 {
+            // Nothing beside or behind it can ever come up to defend it, and
+            // it can't move up either without being taken
+            let behind = neighbours & !bitboard::forward_ranks(side, bitboard::rank_of(square));
+            let stop = match side {
+                Sides::White => square + 8,
+                Sides::Black => square - 8,
+            };
+            if behind == 0 && bitboard::pawn_attacks(side, stop) & theirs != 0 {
+                score += params.backward;
+            }
+        }
+
+        if bitboard::pawn_attacks(side.opposite(), square) & ours != 0 {
+            score += params.chain;
+        }
+
+        if passed_pawn_span(side, square) & theirs == 0 && forward_file(side, square) & ours == 0 {
+            score += params.passed[rank];
+            passed |= bitboard::bit(square);
+        }
+    }
+
+    let mut islands = 0;
+    let mut on_previous_file = false;
+    for file in 0..8 {
+        let count = (ours & file_mask(file)).count_ones() as i32;
+        if count > 1 {
+            score += params.doubled * (count - 1);
+        }
+        if count > 0 && !on_previous_file {
+            islands += 1;
+        }
+        on_previous_file = count > 0;
+    }
+    if islands > 1 {
+        score += params.island * (islands - 1);
+    }
+
+    (score, passed)
+}
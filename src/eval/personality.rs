@@ -0,0 +1,135 @@
+use crate::eval::{Params, Tapered};
+
+// Playing styles, made by leaning on some evaluation terms and easing off
+// others. Balanced is the evaluation as it is.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Personality {
+    Balanced,
+    // Goes for the enemy king and active pieces, and will give up a little
+    // material to get there
+    Aggressive,
+    // Keeps its own king wrapped up and its pawns healthy
+    Defensive,
+    // Grabs material and worries less about everything else
+    Materialistic,
+}
+
+impl Personality {
+    pub const ALL: [Personality; 4] = [
+        Personality::Balanced,
+        Personality::Aggressive,
+        Personality::Defensive,
+        Personality::Materialistic,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Personality::Balanced => "balanced",
+            Personality::Aggressive => "aggressive",
+            Personality::Defensive => "defensive",
+            Personality::Materialistic => "materialistic",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Personality> {
+        Personality::ALL
+            .iter()
+            .copied()
+            .find(|personality| personality.name().eq_ignore_ascii_case(name))
+    }
+}
+
+// Percentages to scale each group of terms by: material, king attack,
+// king shelter, mobility and activity, pawn structure
+struct Weights {
+    material: i32,
+    attack: i32,
+    shelter: i32,
+    activity: i32,
+    structure: i32,
+}
+
+fn weights(personality: Personality) -> Weights {
+    match personality {
+        Personality::Balanced => Weights {
+            material: 100,
+            attack: 100,
+            shelter: 100,
+            activity: 100,
+            structure: 100,
+        },
+        Personality::Aggressive => Weights {
+            material: 90,
+            attack: 150,
+            shelter: 80,
+            activity: 130,
+            structure: 90,
+        },
+        Personality::Defensive => Weights {
+            material: 100,
+            attack: 80,
+            shelter: 150,
+            activity: 90,
+            structure: 125,
+        },
+        Personality::Materialistic => Weights {
+            material: 120,
+            attack: 75,
+            shelter: 100,
+            activity: 75,
+            structure: 100,
+        },
+    }
+}
+
+fn scale(value: Tapered, percent: i32) -> Tapered {
+    Tapered {
+        mg: value.mg * percent / 100,
+        eg: value.eg * percent / 100,
+    }
+}
+
+fn scale_all(values: &mut [Tapered], percent: i32) {
+    for value in values.iter_mut() {
+        *value = scale(*value, percent);
+    }
+}
+
+impl Params {
+    // These parameters with `personality`'s leanings applied on top
+    pub fn with_personality(&self, personality: Personality) -> Params {
+        let weights = weights(personality);
+        let mut params = self.clone();
+
+        scale_all(&mut params.material, weights.material);
+
+        for weight in params.king_attacker_weight.iter_mut() {
+            *weight = *weight * weights.attack / 100;
+        }
+        params.king_zone_attack = params.king_zone_attack * weights.attack / 100;
+        for weight in params.safe_check.iter_mut() {
+            *weight = *weight * weights.attack / 100;
+        }
+        scale_all(&mut params.pawn_storm, weights.attack);
+
+        scale_all(&mut params.pawn_shield, weights.shelter);
+        params.king_semi_open_file = scale(params.king_semi_open_file, weights.shelter);
+        params.king_open_file = scale(params.king_open_file, weights.shelter);
+
+        for table in params.mobility.iter_mut() {
+            scale_all(table, weights.activity);
+        }
+        params.rook_open_file = scale(params.rook_open_file, weights.activity);
+        params.rook_semi_open_file = scale(params.rook_semi_open_file, weights.activity);
+        params.rook_seventh = scale(params.rook_seventh, weights.activity);
+        params.knight_outpost = scale(params.knight_outpost, weights.activity);
+
+        params.doubled = scale(params.doubled, weights.structure);
+        params.isolated = scale(params.isolated, weights.structure);
+        params.backward = scale(params.backward, weights.structure);
+        params.chain = scale(params.chain, weights.structure);
+        params.island = scale(params.island, weights.structure);
+
+        params
+    }
+}
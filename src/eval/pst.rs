@@ -0,0 +1,134 @@
+// Piece-square tables, laid out the way you'd look at the board from White's
+// side: the first row is rank 8, the last row is rank 1.
+
+pub const PAWN_MG: [i32; 64] = [
+    0, 0, 0, 0, 0, 0, 0, 0, //
+    50, 50, 50, 50, 50, 50, 50, 50, //
+    10, 10, 20, 30, 30, 20, 10, 10, //
+    5, 5, 10, 25, 25, 10, 5, 5, //
+    0, 0, 0, 20, 20, 0, 0, 0, //
+    5, -5, -10, 0, 0, -10, -5, 5, //
+    5, 10, 10, -20, -20, 10, 10, 5, //
+    0, 0, 0, 0, 0, 0, 0, 0, //
+];
+
+pub const PAWN_EG: [i32; 64] = [
+    0, 0, 0, 0, 0, 0, 0, 0, //
+    60, 60, 60, 60, 60, 60, 60, 60, //
+    35, 35, 35, 35, 35, 35, 35, 35, //
+    20, 20, 20, 20, 20, 20, 20, 20, //
+    10, 10, 10, 10, 10, 10, 10, 10, //
+    5, 5, 5, 5, 5, 5, 5, 5, //
+    0, 0, 0, 0, 0, 0, 0, 0, //
+    0, 0, 0, 0, 0, 0, 0, 0, //
+];
+
+pub const KNIGHT_MG: [i32; 64] = [
+    -50, -40, -30, -30, -30, -30, -40, -50, //
+    -40, -20, 0, 0, 0, 0, -20, -40, //
+    -30, 0, 10, 15, 15, 10, 0, -30, //
+    -30, 5, 15, 20, 20, 15, 5, -30, //
+    -30, 0, 15, 20, 20, 15, 0, -30, //
+    -30, 5, 10, 15, 15, 10, 5, -30, //
+    -40, -20, 0, 5, 5, 0, -20, -40, //
+    -50, -40, -30, -30, -30, -30, -40, -50, //
+];
+
+pub const KNIGHT_EG: [i32; 64] = [
+    -50, -40, -30, -30, -30, -30, -40, -50, //
+    -40, -20, 0, 0, 0, 0, -20, -40, //
+    -30, 0, 10, 15, 15, 10, 0, -30, //
+    -30, 5, 15, 20, 20, 15, 5, -30, //
+    -30, 0, 15, 20, 20, 15, 0, -30, //
+    -30, 5, 10, 15, 15, 10, 5, -30, //
+    -40, -20, 0, 5, 5, 0, -20, -40, //
+    -50, -40, -30, -30, -30, -30, -40, -50, //
+];
+
+pub const BISHOP_MG: [i32; 64] = [
+    -20, -10, -10, -10, -10, -10, -10, -20, //
+    -10, 0, 0, 0, 0, 0, 0, -10, //
+    -10, 0, 5, 10, 10, 5, 0, -10, //
+    -10, 5, 5, 10, 10, 5, 5, -10, //
+    -10, 0, 10, 10, 10, 10, 0, -10, //
+    -10, 10, 10, 10, 10, 10, 10, -10, //
+    -10, 5, 0, 0, 0, 0, 5, -10, //
+    -20, -10, -10, -10, -10, -10, -10, -20, //
+];
+
+pub const BISHOP_EG: [i32; 64] = [
+    -15, -10, -10, -10, -10, -10, -10, -15, //
+    -10, 0, 0, 0, 0, 0, 0, -10, //
+    -10, 0, 5, 5, 5, 5, 0, -10, //
+    -10, 0, 5, 10, 10, 5, 0, -10, //
+    -10, 0, 5, 10, 10, 5, 0, -10, //
+    -10, 0, 5, 5, 5, 5, 0, -10, //
+    -10, 0, 0, 0, 0, 0, 0, -10, //
+    -15, -10, -10, -10, -10, -10, -10, -15, //
+];
+
+pub const ROOK_MG: [i32; 64] = [
+    0, 0, 0, 0, 0, 0, 0, 0, //
+    5, 10, 10, 10, 10, 10, 10, 5, //
+    -5, 0, 0, 0, 0, 0, 0, -5, //
+    -5, 0, 0, 0, 0, 0, 0, -5, //
+    -5, 0, 0, 0, 0, 0, 0, -5, //
+    -5, 0, 0, 0, 0, 0, 0, -5, //
+    -5, 0, 0, 0, 0, 0, 0, -5, //
+    0, 0, 0, 5, 5, 0, 0, 0, //
+];
+
+pub const ROOK_EG: [i32; 64] = [
+    5, 5, 5, 5, 5, 5, 5, 5, //
+    10, 10, 10, 10, 10, 10, 10, 10, //
+    0, 0, 0, 0, 0, 0, 0, 0, //
+    0, 0, 0, 0, 0, 0, 0, 0, //
+    0, 0, 0, 0, 0, 0, 0, 0, //
+    0, 0, 0, 0, 0, 0, 0, 0, //
+    0, 0, 0, 0, 0, 0, 0, 0, //
+    0, 0, 0, 0, 0, 0, 0, 0, //
+];
+
+pub const QUEEN_MG: [i32; 64] = [
+    -20, -10, -10, -5, -5, -10, -10, -20, //
+    -10, 0, 0, 0, 0, 0, 0, -10, //
+    -10, 0, 5, 5, 5, 5, 0, -10, //
+    -5, 0, 5, 5, 5, 5, 0, -5, //
+    0, 0, 5, 5, 5, 5, 0, -5, //
+    -10, 5, 5, 5, 5, 5, 0, -10, //
+    -10, 0, 5, 0, 0, 0, 0, -10, //
+    -20, -10, -10, -5, -5, -10, -10, -20, //
+];
+
+pub const QUEEN_EG: [i32; 64] = [
+    -20, -10, -10, -5, -5, -10, -10, -20, //
+    -10, 0, 5, 5, 5, 5, 0, -10, //
+    -10, 5, 10, 10, 10, 10, 5, -10, //
+    -5, 5, 10, 15, 15, 10, 5, -5, //
+    -5, 5, 10, 15, 15, 10, 5, -5, //
+    -10, 5, 10, 10, 10, 10, 5, -10, //
+    -10, 0, 5, 5, 5, 5, 0, -10, //
+    -20, -10, -10, -5, -5, -10, -10, -20, //
+];
+
+pub const KING_MG: [i32; 64] = [
+    -30, -40, -40, -50, -50, -40, -40, -30, //
+    -30, -40, -40, -50, -50, -40, -40, -30, //
+    -30, -40, -40, -50, -50, -40, -40, -30, //
+    -30, -40, -40, -50, -50, -40, -40, -30, //
+    -20, -30, -30, -40, -40, -30, -30, -20, //
+    -10, -20, -20, -20, -20, -20, -20, -10, //
+    20, 20, 0, 0, 0, 0, 20, 20, //
+    20, 30, 10, 0, 0, 10, 30, 20, //
+];
+
+pub const KING_EG: [i32; 64] = [
+    -50, -40, -30, -20, -20, -30, -40, -50, //
+    -30, -20, -10, 0, 0, -10, -20, -30, //
+    -30, -10, 20, 30, 30, 20, -10, -30, //
+    -30, -10, 30, 40, 40, 30, -10, -30, //
+    -30, -10, 30, 40, 40, 30, -10, -30, //
+    -30, -10, 20, 30, 30, 20, -10, -30, //
+    -30, -30, 0, 0, 0, 0, -30, -30, //
+    -50, -30, -30, -30, -30, -30, -30, -50, //
+];
@@ -0,0 +1,229 @@
+use crate::bitboard;
+use crate::eval::endgame::{self, Endgame};
+use crate::eval::{
+    activity, attack_map, king, pawns, phase, pst_index, Evaluator, Tapered, MAX_PHASE,
+};
+use crate::piece::Sides;
+use crate::position::Position;
+use std::fmt;
+
+// The hand-crafted evaluation taken apart term by term, for working out why
+// the engine thinks what it does about a position. Every term is kept for
+// each side separately and as a middlegame / endgame pair, before the two are
+// blended by game phase, so adding them all up gives exactly what `evaluate`
+// does.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Term {
+    pub name: &'static str,
+    // Indexed by `Sides`, each from that side's point of view
+    pub sides: [Tapered; 2],
+}
+
+impl Term {
+    pub fn white(&self) -> Tapered {
+        self.sides[Sides::White.index()]
+    }
+
+    pub fn black(&self) -> Tapered {
+        self.sides[Sides::Black.index()]
+    }
+
+    // White's minus Black's
+    pub fn total(&self) -> Tapered {
+        self.white() - self.black()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trace {
+    pub terms: Vec<Term>,
+    pub phase: i32,
+    pub side_to_move: Sides,
+    // A known endgame, which is scored on its own and overrides the terms,
+    // with its score for White
+    pub endgame: Option<(Endgame, i32)>,
+}
+
+impl Trace {
+    pub fn term(&self, name: &str) -> Option<&Term> {
+        self.terms.iter().find(|term| term.name == name)
+    }
+
+    // Every term added up, for White, before tapering
+    pub fn total(&self) -> Tapered {
+        self.terms
+            .iter()
+            .fold(Tapered::default(), |total, term| total + term.total())
+    }
+
+    // Centipawns for White
+    pub fn score(&self) -> i32 {
+        match self.endgame {
+            Some((_, score)) => score,
+            None => self.total().taper(self.phase),
+        }
+    }
+
+    // Centipawns from the point of view of the side to move, the same as
+    // `Evaluator::evaluate`
+    pub fn evaluation(&self) -> i32 {
+        match self.side_to_move {
+            Sides::White => self.score(),
+            Sides::Black => -self.score(),
+        }
+    }
+}
+
+fn cell(value: Tapered) -> String {
+    format!("{:>6} {:>6}", value.mg, value.eg)
+}
+
+impl fmt::Display for Trace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let rule = format!("{:-<16}+{:-<15}+{:-<15}+{:-<15}", "", "", "", "");
+        writeln!(
+            f,
+            "{:<16}|{:^15}|{:^15}|{:^15}",
+            "Term", "White", "Black", "Total"
+        )?;
+        writeln!(
+            f,
+            "{:<16}| {:>6} {:>6} | {:>6} {:>6} | {:>6} {:>6}",
+            "", "MG", "EG", "MG", "EG", "MG", "EG"
+        )?;
+        writeln!(f, "{}", rule)?;
+        for term in self.terms.iter() {
+            writeln!(
+                f,
+                "{:<16}| {} | {} | {}",
+                term.name,
+                cell(term.white()),
+                cell(term.black()),
+                cell(term.total())
+            )?;
+        }
+        writeln!(f, "{}", rule)?;
+        writeln!(
+            f,
+            "{:<16}|{:15}|{:15}| {}",
+            "Total",
+            "",
+            "",
+            cell(self.total())
+        )?;
+        writeln!(f)?;
+        writeln!(f, "Phase: {}/{}", self.phase, MAX_PHASE)?;
+        if let Some((endgame, _)) = self.endgame {
+            writeln!(f, "Endgame: {}, which the score comes from", endgame.name())?;
+        }
+        writeln!(f, "Score for White: {}", self.score())?;
+        write!(f, "Score for the side to move: {}", self.evaluation())
+    }
+}
+
+impl Evaluator {
+    // The hand-crafted evaluation of `position` term by term. This is what
+    // `evaluate` works out when no network is set; with one, the network's
+    // score has no terms to show. Known endgames come out the same either
+    // way.
+    pub fn trace(&mut self, position: &Position) -> Trace {
+        let params = &self.params;
+        let mut material = [Tapered::default(); 2];
+        let mut pst = [Tapered::default(); 2];
+        for (square, _type, side) in position.occupied() {
+            material[side.index()] += params.material[_type.index()];
+            pst[side.index()] += params.pst[_type.index()][pst_index(square, side)];
+        }
+
+        // The pawn table only keeps White's structure minus Black's, so each
+        // side's is worked out again here, with the passed pawn bonuses moved
+        // over to go with the rest of the passed pawn terms
+        let entry = self.pawn_structure(position);
+        let mut structure = [Tapered::default(); 2];
+        let mut passed = [Tapered::default(); 2];
+        for side in [Sides::White, Sides::Black].iter() {
+            let (score, _) = pawns::evaluate_side(position, &self.params, *side);
+            let index = side.index();
+            for square in bitboard::squares(entry.passed[index]) {
+                passed[index] += self.params.passed[bitboard::relative_rank(*side, square)];
+            }
+            structure[index] = score - passed[index];
+            passed[index] += self.passed_pawns(position, &entry, *side);
+        }
+
+        let params = &self.params;
+        let attacks = attack_map(position);
+        let king_safety = [
+            king::evaluate(position, params, &attacks, Sides::White),
+            king::evaluate(position, params, &attacks, Sides::Black),
+        ];
+        let activity = [
+            activity::evaluate(position, params, &attacks, Sides::White),
+            activity::evaluate(position, params, &attacks, Sides::Black),
+        ];
+        let both =
+            |term: fn(&activity::Activity) -> Tapered| [term(&activity[0]), term(&activity[1])];
+
+        let terms = vec![
+            Term {
+                name: "Material",
+                sides: material,
+            },
+            Term {
+                name: "In hand",
+                sides: [
+                    self.hand(position, Sides::White),
+                    self.hand(position, Sides::Black),
+                ],
+            },
+            Term {
+                name: "PST",
+                sides: pst,
+            },
+            Term {
+                name: "Pawns",
+                sides: structure,
+            },
+            Term {
+                name: "Passed pawns",
+                sides: passed,
+            },
+            Term {
+                name: "King safety",
+                sides: king_safety,
+            },
+            Term {
+                name: "Mobility",
+                sides: both(|activity| activity.mobility),
+            },
+            Term {
+                name: "Rook files",
+                sides: both(|activity| activity.rook_files),
+            },
+            Term {
+                name: "Rook seventh",
+                sides: both(|activity| activity.rook_seventh),
+            },
+            Term {
+                name: "Bishop pair",
+                sides: both(|activity| activity.bishop_pair),
+            },
+            Term {
+                name: "Outposts",
+                sides: both(|activity| activity.outposts),
+            },
+            Term {
+                name: "Trapped pieces",
+                sides: both(|activity| activity.trapped),
+            },
+        ];
+
+        Trace {
+            terms,
+            phase: phase(position),
+            side_to_move: position.side_to_move,
+            endgame: endgame::probe(position, &self.params),
+        }
+    }
+}
@@ -0,0 +1,274 @@
+use crate::eval::{Params, Tapered};
+use std::fs;
+use std::path::Path;
+
+// Every number in Params as one flat list, each with a name like
+// "passed[3].eg" or "pst[1][12].mg". This is what the tuner works on, and
+// what parameter files are written in: one "name value" pair a line.
+
+enum Slot<'a> {
+    Tapered(&'a mut Tapered),
+    Plain(&'a mut i32),
+}
+
+// How a field's slots are laid out, for naming them and writing them back
+// out as Rust
+enum Shape {
+    Single,
+    List,
+    // Rows of this many slots each
+    Table(usize),
+}
+
+struct Field<'a> {
+    name: &'static str,
+    shape: Shape,
+    slots: Vec<Slot<'a>>,
+}
+
+fn single<'a>(name: &'static str, value: &'a mut Tapered) -> Field<'a> {
+    Field {
+        name,
+        shape: Shape::Single,
+        slots: vec![Slot::Tapered(value)],
+    }
+}
+
+fn list<'a>(name: &'static str, values: &'a mut [Tapered]) -> Field<'a> {
+    Field {
+        name,
+        shape: Shape::List,
+        slots: values.iter_mut().map(Slot::Tapered).collect(),
+    }
+}
+
+fn plain_list<'a>(name: &'static str, values: &'a mut [i32]) -> Field<'a> {
+    Field {
+        name,
+        shape: Shape::List,
+        slots: values.iter_mut().map(Slot::Plain).collect(),
+    }
+}
+
+fn table<'a, const N: usize>(name: &'static str, rows: &'a mut [[Tapered; N]]) -> Field<'a> {
+    Field {
+        name,
+        shape: Shape::Table(N),
+        slots: rows.iter_mut().flatten().map(Slot::Tapered).collect(),
+    }
+}
+
+impl Field<'_> {
+    fn slot_name(&self, index: usize) -> String {
+        match self.shape {
+            Shape::Single => self.name.to_string(),
+            Shape::List => format!("{}[{}]", self.name, index),
+            Shape::Table(width) => format!("{}[{}][{}]", self.name, index / width, index % width),
+        }
+    }
+}
+
+fn rust_slot(slot: &Slot) -> String {
+    match slot {
+        Slot::Tapered(value) => format!("s({}, {})", value.mg, value.eg),
+        Slot::Plain(value) => value.to_string(),
+    }
+}
+
+impl Params {
+    fn fields(&mut self) -> Vec<Field<'_>> {
+        let Params {
+            material,
+            hand,
+            pst,
+            doubled,
+            isolated,
+            backward,
+            chain,
+            island,
+            passed,
+            passed_free_path,
+            pawn_shield,
+            pawn_storm,
+            king_semi_open_file,
+            king_open_file,
+            king_attacker_weight,
+            king_zone_attack,
+            safe_check,
+            mobility,
+            rook_open_file,
+            rook_semi_open_file,
+            rook_seventh,
+            bishop_pair,
+            knight_outpost,
+            trapped_rook,
+            trapped_bishop,
+        } = self;
+        vec![
+            list("material", material),
+            list("hand", hand),
+            table("pst", pst),
+            single("doubled", doubled),
+            single("isolated", isolated),
+            single("backward", backward),
+            single("chain", chain),
+            single("island", island),
+            list("passed", passed),
+            list("passed_free_path", passed_free_path),
+            list("pawn_shield", pawn_shield),
+            list("pawn_storm", pawn_storm),
+            single("king_semi_open_file", king_semi_open_file),
+            single("king_open_file", king_open_file),
+            plain_list("king_attacker_weight", king_attacker_weight),
+            Field {
+                name: "king_zone_attack",
+                shape: Shape::Single,
+                slots: vec![Slot::Plain(king_zone_attack)],
+            },
+            plain_list("safe_check", safe_check),
+            table("mobility", mobility),
+            single("rook_open_file", rook_open_file),
+            single("rook_semi_open_file", rook_semi_open_file),
+            single("rook_seventh", rook_seventh),
+            single("bishop_pair", bishop_pair),
+            single("knight_outpost", knight_outpost),
+            single("trapped_rook", trapped_rook),
+            single("trapped_bishop", trapped_bishop),
+        ]
+    }
+
+    // Middlegame then endgame for tapered values
+    pub fn values(&self) -> Vec<i32> {
+        let mut params = self.clone();
+        let mut values = Vec::new();
+        for field in params.fields() {
+            for slot in field.slots {
+                match slot {
+                    Slot::Tapered(value) => values.extend_from_slice(&[value.mg, value.eg]),
+                    Slot::Plain(value) => values.push(*value),
+                }
+            }
+        }
+        values
+    }
+
+    // Takes values in the same order `values` gives them
+    pub fn set_values(&mut self, values: &[i32]) {
+        let mut values = values.iter();
+        for field in self.fields() {
+            for slot in field.slots {
+                match slot {
+                    Slot::Tapered(value) => {
+                        value.mg = *values.next().unwrap_or(&value.mg);
+                        value.eg = *values.next().unwrap_or(&value.eg);
+                    }
+                    Slot::Plain(value) => *value = *values.next().unwrap_or(value),
+                }
+            }
+        }
+    }
+
+    // The names of `values`, in the same order
+    pub fn names(&self) -> Vec<String> {
+        let mut params = self.clone();
+        let mut names = Vec::new();
+        for field in params.fields() {
+            for (index, slot) in field.slots.iter().enumerate() {
+                let name = field.slot_name(index);
+                match slot {
+                    Slot::Tapered(_) => {
+                        names.push(format!("{}.mg", name));
+                        names.push(format!("{}.eg", name));
+                    }
+                    Slot::Plain(_) => names.push(name),
+                }
+            }
+        }
+        names
+    }
+
+    pub fn to_text(&self) -> String {
+        self.names()
+            .iter()
+            .zip(self.values())
+            .map(|(name, value)| format!("{} {}\n", name, value))
+            .collect()
+    }
+
+    // Reads a parameter file. Anything it doesn't mention keeps its default
+    // value, and blank lines and lines starting with # are skipped.
+    pub fn from_text(text: &str) -> Result<Params, String> {
+        let mut params = Params::default();
+        let names = params.names();
+        let mut values = params.values();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut words = line.split_whitespace();
+            let (name, value) = match (words.next(), words.next(), words.next()) {
+                (Some(name), Some(value), None) => (name, value),
+                _ => return Err(format!("line {}: expected a name and a value", number + 1)),
+            };
+            let index = names.iter().position(|known| known == name).ok_or(format!(
+                "line {}: no parameter called {}",
+                number + 1,
+                name
+            ))?;
+            values[index] = value
+                .parse()
+                .map_err(|_| format!("line {}: bad value {}", number + 1, value))?;
+        }
+        params.set_values(&values);
+        Ok(params)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Params, String> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .map_err(|error| format!("can't read {}: {}", path.display(), error))?;
+        Params::from_text(&text)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let path = path.as_ref();
+        fs::write(path, self.to_text())
+            .map_err(|error| format!("can't write {}: {}", path.display(), error))
+    }
+
+    // A Default impl for these values, to paste over the one in eval/mod.rs
+    pub fn to_rust(&self) -> String {
+        let mut params = self.clone();
+        let mut rust = String::new();
+        rust.push_str("impl Default for Params {\n");
+        rust.push_str("    fn default() -> Params {\n");
+        rust.push_str("        Params {\n");
+        for field in params.fields() {
+            let slots: Vec<String> = field.slots.iter().map(rust_slot).collect();
+            let value = match field.shape {
+                Shape::Single => slots[0].clone(),
+                Shape::List => format!("[{}]", slots.join(", ")),
+                Shape::Table(width) => {
+                    // Eight to a line, so the square tables come out as boards
+                    let rows: Vec<String> = slots
+                        .chunks(width)
+                        .map(|row| {
+                            let lines: Vec<String> = row
+                                .chunks(8)
+                                .map(|line| format!("                    {},\n", line.join(", ")))
+                                .collect();
+                            format!("                [\n{}                ],\n", lines.concat())
+                        })
+                        .collect();
+                    format!("[\n{}            ]", rows.concat())
+                }
+            };
+            rust.push_str(&format!("            {}: {},\n", field.name, value));
+        }
+        rust.push_str("        }\n");
+        rust.push_str("    }\n");
+        rust.push_str("}\n");
+        rust
+    }
+}
@@ -1,16 +1,253 @@
+pub mod bitboard;
 pub mod board;
+pub mod book;
+pub mod cecp;
+pub mod clock;
+pub mod datagen;
+pub mod eval;
 pub mod piece;
+pub mod play;
+pub mod position;
+pub mod render;
+pub mod search;
+pub mod tablebase;
+pub mod tui;
+pub mod tune;
+pub mod uci;
 
 fn main() {
-    let _board = board::create();
-    let pieces = piece::generate_all();
+    let args: Vec<String> = std::env::args().collect();
+    let command = match args.get(1).map(String::as_str) {
+        Some("makebook") => Some(make_book as fn(&[String]) -> Result<(), String>),
+        Some("tune") => Some(tune as fn(&[String]) -> Result<(), String>),
+        Some("datagen") => Some(datagen as fn(&[String]) -> Result<(), String>),
+        Some("eval") => Some(evaluate as fn(&[String]) -> Result<(), String>),
+        Some("play") => Some(play as fn(&[String]) -> Result<(), String>),
+        Some("tui") => Some(tui as fn(&[String]) -> Result<(), String>),
+        _ => None,
+    };
+    if let Some(command) = command {
+        if let Err(error) = command(&args[2..]) {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    // With no command it's an engine for a GUI to talk to
+    serve();
+}
+
+// Speaks UCI, or CECP if the first thing the interface says is "xboard", on
+// standard input and output until told to quit or the input runs out
+fn serve() {
+    use std::io::{BufRead, Write};
+
+    let (sender, receiver) = std::sync::mpsc::channel::<String>();
+    let printer = std::thread::spawn(move || {
+        let stdout = std::io::stdout();
+        for line in receiver {
+            let mut stdout = stdout.lock();
+            if writeln!(stdout, "{}", line)
+                .and_then(|_| stdout.flush())
+                .is_err()
+            {
+                break;
+            }
+        }
+    });
+
+    let stdin = std::io::stdin();
+    let mut lines = stdin.lock().lines().map_while(Result::ok);
+    let first = lines.by_ref().find(|line| !line.trim().is_empty());
+    let mut command: Box<dyn FnMut(&str) -> bool> = match first.as_deref().map(str::trim) {
+        Some("xboard") => {
+            let mut engine = cecp::Cecp::new(sender);
+            Box::new(move |line| engine.command(line))
+        }
+        _ => {
+            let mut engine = uci::Uci::new(sender);
+            Box::new(move |line| engine.command(line))
+        }
+    };
+    for line in first.into_iter().chain(lines) {
+        if !command(&line) {
+            break;
+        }
+    }
+    drop(command);
+    let _ = printer.join();
+}
+
+// chess makebook <games.pgn> <book.bin> [min games] [max plies]
+fn make_book(args: &[String]) -> Result<(), String> {
+    let usage = "usage: chess makebook <games.pgn> <book.bin> [min games] [max plies]";
+    let (input, output) = match args {
+        [input, output, ..] => (input, output),
+        _ => return Err(usage.to_string()),
+    };
+    let mut builder = book::builder::BookBuilder::new();
+    if let Some(min_games) = args.get(2) {
+        builder.min_games = min_games.parse().map_err(|_| usage.to_string())?;
+    }
+    if let Some(max_ply) = args.get(3) {
+        builder.max_ply = max_ply.parse().map_err(|_| usage.to_string())?;
+    }
+
+    let text = std::fs::read_to_string(input)
+        .map_err(|error| format!("can't read {}: {}", input, error))?;
+    let games = builder.add_pgn(&text);
+    let book = builder.build();
+    book.save(output)?;
+    println!(
+        "{} games, {} skipped, {} book entries written to {}",
+        games,
+        builder.skipped(),
+        book.len(),
+        output
+    );
+    Ok(())
+}
+
+// chess tune <positions> <output> [passes]
+// Writes Rust source when the output ends in .rs, a parameter file otherwise
+fn tune(args: &[String]) -> Result<(), String> {
+    let usage = "usage: chess tune <positions> <output> [passes]";
+    let (input, output) = match args {
+        [input, output, ..] => (input, output),
+        _ => return Err(usage.to_string()),
+    };
+    let passes = match args.get(2) {
+        Some(passes) => passes.parse().map_err(|_| usage.to_string())?,
+        None => 100,
+    };
+
+    let params = eval::Params::default();
+    let mut tuner = tune::Tuner::new(tune::load_samples(input)?);
+    let k = tuner.fit_k(&params);
+    println!(
+        "{} positions, k = {:.3}, error {:.6}",
+        tuner.samples(),
+        k,
+        tuner.error(&params)
+    );
+    let tuned = tuner.tune(&params, passes, |pass, error| {
+        println!("pass {}: error {:.6}", pass, error);
+    });
+
+    if output.ends_with(".rs") {
+        std::fs::write(output, tuned.to_rust())
+            .map_err(|error| format!("can't write {}: {}", output, error))
+    } else {
+        tuned.save(output)
+    }
+}
+
+// "--params <file>" taken out of the arguments, with the evaluation
+// parameters chess tune saved there. Without it, the built in ones.
+fn eval_params(args: &[String]) -> Result<(eval::Params, Vec<String>), String> {
+    let at = match args.iter().position(|arg| arg == "--params") {
+        Some(at) => at,
+        None => return Ok((eval::Params::default(), args.to_vec())),
+    };
+    let path = args.get(at + 1).ok_or("expected a file after --params")?;
+    let params = eval::Params::load(path)?;
+    let mut rest = args.to_vec();
+    rest.drain(at..at + 2);
+    Ok((params, rest))
+}
+
+// chess play [--params <file>] [white|black] [level]
+fn play(args: &[String]) -> Result<(), String> {
+    let usage = format!(
+        "usage: chess play [--params <file>] [white|black] [level 0-{}]",
+        search::skill::MAX_LEVEL
+    );
+    let (params, args) = eval_params(args)?;
+    let human = match args.first() {
+        Some(colour) => play::parse_colour(colour).map_err(|_| usage.clone())?,
+        None => piece::Sides::White,
+    };
+    let skill = match args.get(1) {
+        Some(level) => search::skill::Skill::level(level.parse().map_err(|_| usage.clone())?),
+        None => search::skill::Skill::full(),
+    };
+    play::run(human, skill, params)
+}
+
+// chess tui [--params <file>] [white|black] [level] [time control]
+// The time control goes the way clock::TimeControl::parse reads it, like 5+3
+// or 40/90+30,30+30. Without one there's no clock and the engine takes a
+// second a move.
+fn tui(args: &[String]) -> Result<(), String> {
+    let usage = format!(
+        "usage: chess tui [--params <file>] [white|black] [level 0-{}] [time control]",
+        search::skill::MAX_LEVEL
+    );
+    let (params, args) = eval_params(args)?;
+    let human = match args.first() {
+        Some(colour) => play::parse_colour(colour).map_err(|_| usage.clone())?,
+        None => piece::Sides::White,
+    };
+    let skill = match args.get(1) {
+        Some(level) => search::skill::Skill::level(level.parse().map_err(|_| usage.clone())?),
+        None => search::skill::Skill::full(),
+    };
+    let clock = match args.get(2) {
+        Some(control) => Some(clock::Clock::new(clock::TimeControl::parse(control)?)),
+        None => None,
+    };
+    tui::run(human, skill, clock, params)
+}
+
+// chess eval [--trace] [--params <file>] <fen>
+// The static evaluation, or with --trace a table of every term in it
+fn evaluate(args: &[String]) -> Result<(), String> {
+    let usage = "usage: chess eval [--trace] [--params <file>] <fen>";
+    let (params, args) = eval_params(args)?;
+    let trace = args.first().is_some_and(|arg| arg == "--trace");
+    let fen = args[trace as usize..].join(" ");
+    if fen.is_empty() {
+        return Err(usage.to_string());
+    }
+    let position = position::from_fen(&fen)?;
+    let mut evaluator = eval::Evaluator::with_params(params);
+    if trace {
+        println!("{}", evaluator.trace(&position));
+    } else {
+        println!("{}", evaluator.evaluate(&position));
+    }
+    Ok(())
+}
 
-    let knight = piece::build(piece::Types::Knight, piece::Sides::White, ('E', 4), 35);
+// chess datagen <output> [games] [depth] [network weights]
+fn datagen(args: &[String]) -> Result<(), String> {
+    let usage = "usage: chess datagen <output> [games] [depth] [network weights]";
+    let output = args.first().ok_or(usage.to_string())?;
+    let mut options = datagen::Options::default();
+    if let Some(games) = args.get(1) {
+        options.games = games.parse().map_err(|_| usage.to_string())?;
+    }
+    if let Some(depth) = args.get(2) {
+        options.limits.depth = Some(depth.parse().map_err(|_| usage.to_string())?);
+    }
+    let mut searcher = search::Searcher::new(search::Options::default());
+    if let Some(weights) = args.get(3) {
+        let network = eval::nnue::Network::load(weights)?;
+        searcher.evaluator.network = Some(std::sync::Arc::new(network));
+    }
 
-    println!("{:?}", knight.legal_moves(&pieces));
+    let file = std::fs::File::create(output)
+        .map_err(|error| format!("can't write {}: {}", output, error))?;
+    let mut writer = std::io::BufWriter::new(file);
+    datagen::generate(&mut searcher, &options, &mut writer, |game, positions| {
+        println!("game {}: {} positions", game, positions);
+    })?;
+    Ok(())
 }
 
 #[cfg(test)]
+#[allow(clippy::comparison_to_empty)]
 mod tests {
     use super::*;
 
@@ -26,6 +263,24 @@ mod tests {
         assert!(pieces[8].legal_moves(&pieces) == [('A', 3, 0), ('A', 4, 0)]);
     }
 
+    #[test]
+    fn knights_start_next_to_the_rooks_and_bishops_next_to_the_royals() {
+        use piece::Types::{Bishop, Knight};
+
+        let pieces = piece::generate_all();
+        let on = |location: (char, u32)| {
+            pieces
+                .iter()
+                .find(|piece| piece.location == location)
+                .map(|piece| piece._type)
+        };
+        for row in [1, 8] {
+            for (col, _type) in [('B', Knight), ('C', Bishop), ('F', Bishop), ('G', Knight)] {
+                assert!(on((col, row)) == Some(_type));
+            }
+        }
+    }
+
     #[test]
     fn a_pawn_can_capture_diagnally() {
         let mut pieces = piece::generate_all();
@@ -167,4 +422,2469 @@ mod tests {
                 ]
         );
     }
+
+    #[test]
+    fn the_starting_position_is_evaluated_as_level() {
+        let position = position::start();
+        assert!(eval::evaluate(&position) == 0);
+    }
+
+    #[test]
+    fn an_extra_queen_is_good_for_white_and_bad_for_black() {
+        let mut pieces = piece::generate_all();
+
+        pieces.push(piece::build(
+            piece::Types::Queen,
+            piece::Sides::White,
+            ('D', 4),
+            9,
+        ));
+
+        let white_to_move = position::build(&pieces, piece::Sides::White);
+        let black_to_move = position::build(&pieces, piece::Sides::Black);
+
+        assert!(eval::evaluate(&white_to_move) > 800);
+        assert!(eval::evaluate(&black_to_move) == -eval::evaluate(&white_to_move));
+    }
+
+    #[test]
+    fn a_knight_in_the_centre_is_worth_more_than_a_knight_on_the_rim() {
+        let kings = || {
+            vec![
+                piece::build(piece::Types::King, piece::Sides::White, ('E', 1), 0),
+                piece::build(piece::Types::King, piece::Sides::Black, ('E', 8), 0),
+            ]
+        };
+
+        let mut centre = kings();
+        centre.push(piece::build(
+            piece::Types::Knight,
+            piece::Sides::White,
+            ('E', 5),
+            3,
+        ));
+
+        let mut rim = kings();
+        rim.push(piece::build(
+            piece::Types::Knight,
+            piece::Sides::White,
+            ('A', 5),
+            3,
+        ));
+
+        let centre = position::build(&centre, piece::Sides::White);
+        let rim = position::build(&rim, piece::Sides::White);
+
+        assert!(eval::evaluate(&centre) > eval::evaluate(&rim));
+    }
+
+    #[test]
+    fn the_king_wants_to_be_central_in_the_endgame_and_tucked_away_in_the_middlegame() {
+        // Black keeps a pawn too, or it's king and pawn against king, which
+        // the endgame bitbase decides whatever the king's square is worth
+        let mut endgame = vec![
+            piece::build(piece::Types::King, piece::Sides::Black, ('E', 8), 0),
+            piece::build(piece::Types::Pawn, piece::Sides::White, ('A', 2), 1),
+            piece::build(piece::Types::Pawn, piece::Sides::Black, ('H', 7), 1),
+        ];
+        let mut middlegame = piece::generate_all();
+        middlegame
+            .retain(|piece| piece._type != piece::Types::King || piece.side == piece::Sides::Black);
+        middlegame.retain(|piece| piece.location != ('E', 2));
+
+        endgame.push(piece::build(
+            piece::Types::King,
+            piece::Sides::White,
+            ('E', 4),
+            0,
+        ));
+        let central_endgame = eval::evaluate(&position::build(&endgame, piece::Sides::White));
+        endgame.pop();
+        endgame.push(piece::build(
+            piece::Types::King,
+            piece::Sides::White,
+            ('G', 1),
+            0,
+        ));
+        let corner_endgame = eval::evaluate(&position::build(&endgame, piece::Sides::White));
+
+        middlegame.push(piece::build(
+            piece::Types::King,
+            piece::Sides::White,
+            ('E', 3),
+            0,
+        ));
+        let central_middlegame = eval::evaluate(&position::build(&middlegame, piece::Sides::White));
+        middlegame.pop();
+        middlegame.push(piece::build(
+            piece::Types::King,
+            piece::Sides::White,
+            ('E', 1),
+            0,
+        ));
+        let home_middlegame = eval::evaluate(&position::build(&middlegame, piece::Sides::White));
+
+        assert!(central_endgame > corner_endgame);
+        assert!(central_middlegame < home_middlegame);
+    }
+
+    fn kings_and(pieces: Vec<(piece::Types, piece::Sides, (char, u32))>) -> Vec<piece::Piece> {
+        let mut all = vec![
+            piece::build(piece::Types::King, piece::Sides::White, ('E', 1), 0),
+            piece::build(piece::Types::King, piece::Sides::Black, ('E', 8), 0),
+        ];
+        for (_type, side, location) in pieces {
+            all.push(piece::build(_type, side, location, 0));
+        }
+        all
+    }
+
+    #[test]
+    fn doubled_and_isolated_pawns_are_worse_than_connected_ones() {
+        use piece::Sides::*;
+        use piece::Types::*;
+
+        let healthy = kings_and(vec![
+            (Pawn, White, ('F', 2)),
+            (Pawn, White, ('G', 2)),
+            (Pawn, White, ('H', 2)),
+            (Pawn, Black, ('F', 7)),
+            (Pawn, Black, ('G', 7)),
+            (Pawn, Black, ('H', 7)),
+        ]);
+        let broken = kings_and(vec![
+            (Pawn, White, ('A', 2)),
+            (Pawn, White, ('C', 2)),
+            (Pawn, White, ('C', 3)),
+            (Pawn, Black, ('F', 7)),
+            (Pawn, Black, ('G', 7)),
+            (Pawn, Black, ('H', 7)),
+        ]);
+
+        let healthy = eval::evaluate(&position::build(&healthy, White));
+        let broken = eval::evaluate(&position::build(&broken, White));
+
+        assert!(healthy == 0);
+        assert!(broken < -30);
+    }
+
+    #[test]
+    fn passed_pawns_are_worth_more_the_further_they_get() {
+        use piece::Sides::*;
+        use piece::Types::*;
+
+        let far = eval::evaluate(&position::build(
+            &kings_and(vec![(Pawn, White, ('A', 6))]),
+            White,
+        ));
+        let near = eval::evaluate(&position::build(
+            &kings_and(vec![(Pawn, White, ('A', 3))]),
+            White,
+        ));
+        let blocked = eval::evaluate(&position::build(
+            &kings_and(vec![(Pawn, White, ('A', 6)), (Knight, Black, ('A', 7))]),
+            White,
+        ));
+        let unblocked = eval::evaluate(&position::build(
+            &kings_and(vec![(Pawn, White, ('A', 6)), (Knight, Black, ('B', 7))]),
+            White,
+        ));
+
+        assert!(far > near);
+        assert!(unblocked > blocked);
+    }
+
+    #[test]
+    fn pawn_structure_is_remembered_in_the_pawn_table() {
+        let position = position::start();
+        let mut evaluator = eval::Evaluator::new();
+
+        assert!(evaluator.pawn_table.probe(position.pawn_key).is_none());
+        evaluator.evaluate(&position);
+        assert!(evaluator.pawn_table.probe(position.pawn_key).is_some());
+    }
+
+    fn pieces_from(pieces: Vec<(piece::Types, piece::Sides, (char, u32))>) -> Vec<piece::Piece> {
+        pieces
+            .into_iter()
+            .map(|(_type, side, location)| piece::build(_type, side, location, 0))
+            .collect()
+    }
+
+    #[test]
+    fn a_king_is_safer_behind_its_pawns_than_after_pushing_them() {
+        use piece::Sides::*;
+        use piece::Types::*;
+
+        let castled = |pawns: [(char, u32); 3]| {
+            let mut pieces = vec![
+                (King, White, ('G', 1)),
+                (Rook, White, ('F', 1)),
+                (Queen, White, ('D', 1)),
+                (King, Black, ('G', 8)),
+                (Rook, Black, ('F', 8)),
+                (Queen, Black, ('D', 8)),
+            ];
+            for location in pawns.iter() {
+                pieces.push((Pawn, White, *location));
+            }
+            position::build(&pieces_from(pieces), White)
+        };
+
+        let params = eval::Params::default();
+        let safety = |position: &position::Position| {
+            eval::king::evaluate(position, &params, &eval::attack_map(position), White).mg
+        };
+
+        let sheltered = castled([('F', 2), ('G', 2), ('H', 2)]);
+        let pushed = castled([('F', 2), ('G', 4), ('H', 4)]);
+        let gone = castled([('A', 2), ('B', 2), ('C', 2)]);
+
+        assert!(safety(&sheltered) > safety(&pushed));
+        assert!(safety(&pushed) > safety(&gone));
+    }
+
+    #[test]
+    fn pieces_swarming_the_king_are_dangerous() {
+        use piece::Sides::*;
+        use piece::Types::*;
+
+        let attacked = |queen: (char, u32), knight: (char, u32)| {
+            let pieces = vec![
+                (King, White, ('G', 1)),
+                (Pawn, White, ('F', 2)),
+                (Pawn, White, ('G', 2)),
+                (Pawn, White, ('H', 2)),
+                (King, Black, ('G', 8)),
+                (Queen, Black, queen),
+                (Knight, Black, knight),
+            ];
+            position::build(&pieces_from(pieces), White)
+        };
+
+        let params = eval::Params::default();
+        let safety = |position: &position::Position| {
+            eval::king::evaluate(position, &params, &eval::attack_map(position), White).mg
+        };
+
+        let near = attacked(('H', 4), ('G', 4));
+        let far = attacked(('A', 5), ('A', 4));
+
+        assert!(safety(&near) < safety(&far) - 30);
+    }
+
+    #[test]
+    fn rooks_like_open_files_and_each_term_is_reported_on_its_own() {
+        use piece::Sides::*;
+        use piece::Types::*;
+
+        let rook_on = |file: char| {
+            let pieces = kings_and(vec![
+                (Rook, White, (file, 1)),
+                (Pawn, White, ('A', 2)),
+                (Pawn, Black, ('B', 7)),
+            ]);
+            let position = position::build(&pieces, White);
+            let params = eval::Params::default();
+            eval::activity::evaluate(&position, &params, &eval::attack_map(&position), White)
+        };
+
+        let params = eval::Params::default();
+        assert!(rook_on('D').rook_files == params.rook_open_file);
+        assert!(rook_on('B').rook_files == params.rook_semi_open_file);
+        assert!(rook_on('A').rook_files == eval::Tapered::default());
+        assert!(rook_on('D').mobility.mg > rook_on('A').mobility.mg);
+        assert!(rook_on('D').bishop_pair == eval::Tapered::default());
+    }
+
+    #[test]
+    fn outposts_bishop_pairs_and_trapped_bishops_are_noticed() {
+        use piece::Sides::*;
+        use piece::Types::*;
+
+        let pieces = kings_and(vec![
+            (Knight, White, ('D', 5)),
+            (Pawn, White, ('E', 4)),
+            (Bishop, White, ('A', 7)),
+            (Bishop, White, ('C', 1)),
+            (Pawn, Black, ('B', 6)),
+            (Pawn, Black, ('D', 6)),
+        ]);
+        let position = position::build(&pieces, White);
+        let params = eval::Params::default();
+        let activity =
+            eval::activity::evaluate(&position, &params, &eval::attack_map(&position), White);
+
+        assert!(activity.outposts == params.knight_outpost);
+        assert!(activity.bishop_pair == params.bishop_pair);
+        assert!(activity.trapped == params.trapped_bishop);
+    }
+
+    #[test]
+    fn the_move_generator_matches_known_perft_numbers() {
+        let cases = [
+            (position::START_FEN, 3, 8902),
+            (
+                "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+                2,
+                2039,
+            ),
+            ("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", 3, 2812),
+            (
+                "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+                2,
+                264,
+            ),
+        ];
+        for (fen, depth, expected) in cases.iter() {
+            let mut position = position::from_fen(fen).unwrap();
+            assert!(position::movegen::perft(&mut position, *depth) == *expected);
+        }
+    }
+
+    #[test]
+    fn the_starting_pieces_make_the_starting_position() {
+        let position = position::start();
+        assert!(position.to_fen() == position::START_FEN);
+        assert!(position.hash == position::from_fen(position::START_FEN).unwrap().hash);
+    }
+
+    #[test]
+    fn fens_with_pawns_on_the_back_ranks_are_rejected() {
+        assert!(position::from_fen("4k2P/8/8/8/8/8/8/4K3 w - - 0 1").is_err());
+        assert!(position::from_fen("4k3/8/8/8/8/8/8/p3K3 b - - 0 1").is_err());
+        assert!(position::from_fen("4k3/7P/8/8/8/8/8/4K3 w - - 0 1").is_ok());
+    }
+
+    #[test]
+    fn making_and_unmaking_moves_puts_everything_back() {
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let mut position = position::from_fen(fen).unwrap();
+        let hash = position.hash;
+
+        for text in ["e1g1", "h3g2", "a2a4", "b4a3", "e5f7", "e8g8"].iter() {
+            let mv = position.parse_move(text).unwrap();
+            position.make_move(mv);
+            let fresh = position::from_fen(&position.to_fen()).unwrap();
+            assert!(position.hash == fresh.hash);
+        }
+        for _ in 0..6 {
+            position.unmake_move();
+        }
+
+        assert!(position.to_fen() == fen);
+        assert!(position.hash == hash);
+    }
+
+    #[test]
+    fn the_search_finds_mate_in_one() {
+        let mut position = position::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let best = search::best_move(&mut position, 3).unwrap();
+        assert!(best.to_string() == "a1a8");
+    }
+
+    #[test]
+    fn the_search_finds_mate_with_every_selectivity_option_on_its_own() {
+        let fen = "r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4";
+        let limits = search::Limits {
+            depth: Some(3),
+            ..Default::default()
+        };
+
+        let mut variants = vec![search::Options::default(), search::Options::none()];
+        for index in 0..5 {
+            let mut options = search::Options::none();
+            match index {
+                0 => options.null_move = true,
+                1 => options.late_move_reductions = true,
+                2 => options.futility_pruning = true,
+                3 => options.razoring = true,
+                _ => options.check_extensions = true,
+            }
+            variants.push(options);
+        }
+
+        for options in variants {
+            let mut position = position::from_fen(fen).unwrap();
+            let result = search::Searcher::new(options).search(&mut position, &limits);
+            assert!(result.best_move.unwrap().to_string() == "h5f7");
+            assert!(result.score >= search::MATE_BOUND);
+            assert!(position.to_fen() == fen);
+        }
+    }
+
+    #[test]
+    fn pruning_searches_fewer_nodes_than_plain_alpha_beta() {
+        let fen = "r1bqk2r/pppp1ppp/2n2n2/2b1p3/2B1P3/3P1N2/PPP2PPP/RNBQK2R w KQkq - 1 5";
+        let limits = search::Limits {
+            depth: Some(3),
+            ..Default::default()
+        };
+
+        let mut position = position::from_fen(fen).unwrap();
+        let plain = search::Searcher::new(search::Options::none()).search(&mut position, &limits);
+        let pruning = search::Options {
+            check_extensions: false,
+            ..Default::default()
+        };
+        let selective = search::Searcher::new(pruning).search(&mut position, &limits);
+
+        assert!(selective.nodes < plain.nodes);
+    }
+
+    #[test]
+    fn a_node_limit_stops_the_search_with_a_move_in_hand() {
+        let mut position = position::start();
+        let limits = search::Limits {
+            nodes: Some(2000),
+            ..Default::default()
+        };
+        let result =
+            search::Searcher::new(search::Options::default()).search(&mut position, &limits);
+
+        assert!(result.best_move.is_some());
+        assert!(result.nodes <= 2001);
+        assert!(position.to_fen() == position::START_FEN);
+    }
+
+    #[test]
+    fn the_principal_variation_plays_out_the_mate_it_found() {
+        let fen = "k7/8/2K5/8/8/8/8/7R w - - 0 1";
+        let mut position = position::from_fen(fen).unwrap();
+        let limits = search::Limits {
+            depth: Some(5),
+            ..Default::default()
+        };
+        let result =
+            search::Searcher::new(search::Options::default()).search(&mut position, &limits);
+
+        assert!(result.score == search::MATE - 3);
+        assert!(result.pv.len() == 3);
+        assert!(result.pv.first() == result.best_move.as_ref());
+        for mv in result.pv.iter() {
+            assert!(position.legal_moves().contains(mv));
+            position.make_move(*mv);
+        }
+        assert!(position.in_check() && position.legal_moves().is_empty());
+    }
+
+    #[test]
+    fn a_single_threaded_search_is_deterministic() {
+        let fen = "r1bqk2r/pppp1ppp/2n2n2/2b1p3/2B1P3/3P1N2/PPP2PPP/RNBQK2R w KQkq - 1 5";
+        let limits = search::Limits {
+            depth: Some(4),
+            ..Default::default()
+        };
+
+        let mut results = Vec::new();
+        for _ in 0..2 {
+            let mut position = position::from_fen(fen).unwrap();
+            let mut searcher = search::Searcher::new(search::Options::default());
+            results.push(searcher.search(&mut position, &limits));
+        }
+
+        assert!(results[0].nodes == results[1].nodes);
+        assert!(results[0].score == results[1].score);
+        assert!(results[0].pv == results[1].pv);
+    }
+
+    #[test]
+    fn several_threads_share_the_work_and_add_up_their_nodes() {
+        let fen = "k7/8/2K5/8/8/8/8/7R w - - 0 1";
+        let limits = search::Limits {
+            depth: Some(5),
+            ..Default::default()
+        };
+        let mut position = position::from_fen(fen).unwrap();
+
+        let mut searcher = search::Searcher::new(search::Options::default());
+        let single = searcher.search(&mut position, &limits);
+        searcher.clear();
+        searcher.set_threads(4);
+        assert!(searcher.threads() == 4);
+        let parallel = searcher.search(&mut position, &limits);
+
+        assert!(parallel.score == search::MATE - 3);
+        assert!(parallel.nodes > single.nodes);
+        assert!(parallel.nps() > 0);
+        assert!(position.to_fen() == fen);
+    }
+
+    #[test]
+    fn multi_pv_ranks_the_best_few_moves_with_their_lines() {
+        let fen = "r1bqk2r/pppp1ppp/2n2n2/2b1p3/2B1P3/3P1N2/PPP2PPP/RNBQK2R w KQkq - 1 5";
+        let mut position = position::from_fen(fen).unwrap();
+        let limits = search::Limits {
+            depth: Some(3),
+            ..Default::default()
+        };
+        let mut searcher = search::Searcher::new(search::Options::default());
+        searcher.set_multi_pv(3);
+        let result = searcher.search(&mut position, &limits);
+
+        assert!(result.lines.len() == 3);
+        assert!(result.best_move == Some(result.lines[0].mv));
+        assert!(result.score == result.lines[0].score);
+        for (index, line) in result.lines.iter().enumerate() {
+            assert!(line.pv.first() == Some(&line.mv));
+            if index > 0 {
+                assert!(line.score <= result.lines[index - 1].score);
+                assert!(line.mv != result.lines[index - 1].mv);
+            }
+        }
+        assert!(position.to_fen() == fen);
+    }
+
+    #[test]
+    fn multi_pv_never_asks_for_more_lines_than_there_are_moves() {
+        let fen = "k7/8/2K5/8/8/8/8/7R b - - 0 1";
+        let mut position = position::from_fen(fen).unwrap();
+        let limits = search::Limits {
+            depth: Some(3),
+            ..Default::default()
+        };
+        let mut searcher = search::Searcher::new(search::Options::default());
+        searcher.set_multi_pv(5);
+        let result = searcher.search(&mut position, &limits);
+
+        assert!(result.lines.len() == position.legal_moves().len());
+    }
+
+    #[test]
+    fn a_ponder_hit_turns_pondering_into_a_normal_timed_search() {
+        let mut position = position::start();
+        let mut searcher = search::Searcher::new(search::Options::default());
+        let first = searcher.search(
+            &mut position,
+            &search::Limits {
+                depth: Some(3),
+                ..Default::default()
+            },
+        );
+        position.make_move(first.best_move.unwrap());
+        position.make_move(first.ponder_move().unwrap());
+
+        let handle = searcher.handle();
+        let limits = search::Limits {
+            time: Some(std::time::Duration::from_millis(50)),
+            ponder: true,
+            ..Default::default()
+        };
+        let pondering = std::thread::spawn(move || searcher.search(&mut position, &limits));
+
+        std::thread::sleep(std::time::Duration::from_millis(200));
+        assert!(!pondering.is_finished());
+        handle.ponder_hit();
+        let result = pondering.join().unwrap();
+
+        assert!(result.best_move.is_some());
+        assert!(result.time >= std::time::Duration::from_millis(200));
+    }
+
+    #[test]
+    fn a_ponder_miss_stops_cleanly_and_the_next_search_runs_normally() {
+        let mut position = position::start();
+        let mv = position.parse_move("e2e4").unwrap();
+        position.make_move(mv);
+        let mv = position.parse_move("e7e5").unwrap();
+        position.make_move(mv);
+        let fen = position.to_fen();
+
+        let mut searcher = search::Searcher::new(search::Options::default());
+        let handle = searcher.handle();
+        let limits = search::Limits {
+            ponder: true,
+            ..Default::default()
+        };
+        let pondering = std::thread::spawn(move || {
+            let result = searcher.search(&mut position, &limits);
+            (searcher, position, result)
+        });
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        handle.stop();
+        let (mut searcher, position, result) = pondering.join().unwrap();
+
+        assert!(position.to_fen() == fen);
+        assert!(result.best_move.is_some());
+
+        let mut actual = position::start();
+        let mv = actual.parse_move("e2e4").unwrap();
+        actual.make_move(mv);
+        let mv = actual.parse_move("c7c5").unwrap();
+        actual.make_move(mv);
+        let next = searcher.search(
+            &mut actual,
+            &search::Limits {
+                depth: Some(3),
+                ..Default::default()
+            },
+        );
+        assert!(next.depth == 3);
+    }
+
+    #[test]
+    fn skill_levels_map_to_elo_and_only_full_strength_is_unlimited() {
+        use search::skill::Skill;
+
+        assert!(Skill::default().is_full());
+        assert!(Skill::full().depth_limit().is_none());
+        assert!(Skill::full().node_limit().is_none());
+        assert!(Skill::from_elo(500).get() == 0);
+        assert!(Skill::from_elo(1500).get() < Skill::from_elo(2000).get());
+        assert!(Skill::from_elo(3200).is_full());
+        assert!(Skill::level(3).depth_limit() < Skill::level(15).depth_limit());
+        assert!(Skill::level(3).margin() > Skill::level(15).margin());
+    }
+
+    #[test]
+    fn weak_levels_search_less_and_pick_among_nearly_as_good_moves() {
+        let skill = search::skill::Skill::level(2);
+        let mut chosen = Vec::new();
+        for seed in 1..=12 {
+            let mut position = position::start();
+            let mut searcher = search::Searcher::new(search::Options::default());
+            searcher.set_skill(skill);
+            searcher.set_seed(seed);
+            searcher.set_multi_pv(search::skill::CANDIDATES);
+            let result = searcher.search(&mut position, &search::Limits::default());
+
+            assert!(result.depth <= skill.depth_limit().unwrap());
+            let picked = result
+                .lines
+                .iter()
+                .find(|line| Some(line.mv) == result.best_move)
+                .unwrap();
+            assert!(picked.score >= result.lines[0].score - skill.margin());
+            if !chosen.contains(&picked.mv) {
+                chosen.push(picked.mv);
+            }
+        }
+
+        assert!(chosen.len() > 1);
+    }
+
+    #[test]
+    fn personalities_lean_on_different_parts_of_the_evaluation() {
+        use eval::personality::Personality;
+
+        let balanced = eval::Params::default();
+        let aggressive = balanced.with_personality(Personality::Aggressive);
+        let defensive = balanced.with_personality(Personality::Defensive);
+        let materialistic = balanced.with_personality(Personality::Materialistic);
+
+        assert!(aggressive.king_zone_attack > balanced.king_zone_attack);
+        assert!(defensive.pawn_shield[0].mg > balanced.pawn_shield[0].mg);
+        assert!(defensive.king_open_file.mg < balanced.king_open_file.mg);
+        assert!(Personality::from_name("Materialistic") == Some(Personality::Materialistic));
+
+        let position = position::from_fen("4k3/8/8/8/8/8/8/3QK3 w - - 0 1").unwrap();
+        let greedy = eval::Evaluator::with_params(materialistic.clone()).evaluate(&position);
+        assert!(greedy > eval::Evaluator::new().evaluate(&position));
+
+        // Switching a searcher over forgets pawn scores from the old values
+        let mut searcher = search::Searcher::new(search::Options::default());
+        let start = position::start();
+        searcher.evaluator.evaluate(&start);
+        assert!(searcher
+            .evaluator
+            .pawn_table
+            .probe(start.pawn_key)
+            .is_some());
+        searcher.set_params(materialistic);
+        assert!(searcher
+            .evaluator
+            .pawn_table
+            .probe(start.pawn_key)
+            .is_none());
+        assert!(searcher.evaluator.evaluate(&position) == greedy);
+
+        // Chosen over UCI or in a game
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let mut engine = uci::Uci::new(sender);
+        engine.command("uci");
+        assert!(uci_until(&receiver, "uciok").iter().any(|line| line
+            == "option name Personality type combo default balanced var balanced \
+                var aggressive var defensive var materialistic"));
+        engine.command("setoption name Personality value Aggressive");
+        engine.command("setoption name Personality value reckless");
+        assert!(receiver.recv().unwrap() == "info string no personality called reckless");
+        let limits = search::Limits {
+            depth: Some(1),
+            ..Default::default()
+        };
+        let mut game = play::Game::new(piece::Sides::White, search::skill::Skill::full(), limits);
+        assert!(game
+            .input("personality defensive")
+            .unwrap()
+            .contains("defensive"));
+        assert!(game.input("personality reckless").is_err());
+    }
+
+    #[test]
+    fn search_scores_read_as_mate_distances_or_centipawns() {
+        use search::{Score, MATE, TB_BOUND, TB_WIN};
+
+        assert!(Score::from(MATE - 1) == Score::Mate(1));
+        assert!(Score::from(MATE - 3) == Score::Mate(2));
+        assert!(Score::from(-MATE + 2) == Score::Mate(-1));
+        assert!(Score::from(35) == Score::Centipawns(35));
+        assert!(Score::from(TB_WIN - 3) == Score::Centipawns(search::TB_CENTIPAWNS));
+        assert!(Score::from(TB_BOUND) == Score::Centipawns(search::TB_CENTIPAWNS));
+        assert!(Score::from(-TB_WIN + 4) == Score::Centipawns(-search::TB_CENTIPAWNS));
+        assert!(Score::from(TB_WIN - 3).to_string() == "+200.00");
+        assert!(Score::Mate(2).to_string() == "M2");
+        assert!(Score::Mate(-1).to_string() == "-M1");
+        assert!(Score::Centipawns(35).to_string() == "+0.35");
+        assert!(Score::Centipawns(-105).to_string() == "-1.05");
+    }
+
+    #[test]
+    fn mate_distances_stay_right_through_the_transposition_table() {
+        let mut position = position::from_fen("k7/8/2K5/8/8/8/8/7R w - - 0 1").unwrap();
+        let limits = search::Limits {
+            depth: Some(5),
+            ..Default::default()
+        };
+        let mut searcher = search::Searcher::new(search::Options::default());
+
+        let first = searcher.search(&mut position, &limits);
+        assert!(first.evaluation() == search::Score::Mate(2));
+        let again = searcher.search(&mut position, &limits);
+        assert!(again.evaluation() == search::Score::Mate(2));
+
+        position.make_move(first.pv[0]);
+        let defending = searcher.search(&mut position, &limits);
+        assert!(defending.evaluation() == search::Score::Mate(-1));
+
+        position.make_move(first.pv[1]);
+        let finishing = searcher.search(&mut position, &limits);
+        assert!(finishing.evaluation() == search::Score::Mate(1));
+    }
+
+    // A directory of its own under the system temp directory, emptied out
+    fn scratch_directory(name: &str) -> std::path::PathBuf {
+        let directory =
+            std::env::temp_dir().join(format!("rust-chess-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+        std::fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    // A KRvK WDL table where every position is a win with White to move and
+    // a loss with Black to move, which happens to be right
+    fn krk_table() -> Vec<u8> {
+        let mut bytes = vec![0x71, 0xe8, 0x23, 0x5d, 0x01, 0x00, 0x66, 0x44, 0xee, 0x00];
+        bytes.extend([0x80, 4, 0x80, 0].iter());
+        bytes.resize(64, 0);
+        bytes
+    }
+
+    #[test]
+    fn tablebases_need_a_directory_but_not_any_files() {
+        assert!(tablebase::Tablebases::open("/no/such/directory").is_err());
+
+        let tablebases = tablebase::Tablebases::open(scratch_directory("empty")).unwrap();
+        assert!(tablebases.largest() == 0);
+        let mut position = position::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 0 1").unwrap();
+        assert!(tablebases.probe_wdl(&mut position).is_none());
+        assert!(tablebases.root_moves(&mut position).is_none());
+
+        let limits = search::Limits {
+            depth: Some(4),
+            ..Default::default()
+        };
+        let mut without = search::Searcher::new(search::Options::default());
+        let mut with = search::Searcher::new(search::Options::default());
+        with.set_tablebases(Some(std::sync::Arc::new(tablebases)));
+        let expected = without.search(&mut position, &limits);
+        let result = with.search(&mut position, &limits);
+        assert!(result.best_move == expected.best_move);
+        assert!(result.score == expected.score);
+        assert!(result.tb_hits == 0);
+    }
+
+    #[test]
+    fn tablebases_probe_wdl_and_filter_root_moves() {
+        use tablebase::Wdl;
+
+        let directory = scratch_directory("krk");
+        std::fs::write(directory.join("KRvK.rtbw"), krk_table()).unwrap();
+        let tablebases = tablebase::Tablebases::open(&directory).unwrap();
+        assert!(tablebases.largest() == 3);
+        assert!(!tablebases.has_dtz());
+
+        let probe = |fen: &str| tablebases.probe_wdl(&mut position::from_fen(fen).unwrap());
+        assert!(probe("4k3/8/8/8/8/8/8/R3K3 w - - 0 1") == Some(Wdl::Win));
+        assert!(probe("4k3/8/8/8/8/8/8/R3K3 b - - 0 1") == Some(Wdl::Loss));
+        assert!(probe("r3k3/8/8/8/8/8/8/4K3 b - - 0 1") == Some(Wdl::Win));
+        // Black just takes the rook
+        assert!(probe("8/8/8/8/8/8/1k6/R6K b - - 0 1") == Some(Wdl::Draw));
+        // Castling rights are never in the tables
+        assert!(probe("4k3/8/8/8/8/8/8/R3K3 w Q - 0 1").is_none());
+
+        let hanging = ["b1b2", "b1b3", "b1b4"];
+        let mut position = position::from_fen("8/8/8/8/8/2k5/8/1R2K3 w - - 0 1").unwrap();
+        let moves = tablebases.root_moves(&mut position).unwrap();
+        assert!(!moves.is_empty());
+        assert!(moves
+            .iter()
+            .all(|mv| !hanging.contains(&mv.to_string().as_str())));
+
+        let mut searcher = search::Searcher::new(search::Options::default());
+        searcher.set_tablebases(Some(std::sync::Arc::new(tablebases)));
+        let limits = search::Limits {
+            depth: Some(3),
+            ..Default::default()
+        };
+        let result = searcher.search(&mut position, &limits);
+        assert!(moves.contains(&result.best_move.unwrap()));
+    }
+
+    // What one section of a Syzygy table holds: a value for every index, and
+    // for DTZ tables the four value maps
+    struct SyzygySection {
+        flags: u8,
+        values: Vec<u16>,
+        maps: Vec<Vec<u16>>,
+    }
+
+    // A table file the way the generator writes one. Values are paired up
+    // into symbols a few times over, Huffman coded and cut into 32 byte
+    // blocks, with a sparse index entry every 128 values. `header` is
+    // everything between the magic number and the section sizes.
+    fn syzygy_file(magic: [u8; 4], header: &[u8], sections: &[SyzygySection]) -> Vec<u8> {
+        let mut bytes = magic.to_vec();
+        bytes.extend(header.iter());
+        bytes.resize(bytes.len() + bytes.len() % 2, 0);
+
+        let mut parts = Vec::new();
+        for section in sections.iter() {
+            let (sizes, sparse, lengths, data) = syzygy_section(section.flags, &section.values);
+            bytes.extend(sizes.iter());
+            parts.push((sparse, lengths, data));
+        }
+        for section in sections.iter().filter(|section| section.flags & 2 != 0) {
+            let wide = section.flags & 16 != 0;
+            if wide {
+                bytes.resize(bytes.len() + bytes.len() % 2, 0);
+            }
+            for map in section.maps.iter() {
+                for entry in std::iter::once(map.len() as u16).chain(map.iter().copied()) {
+                    if wide {
+                        bytes.extend(entry.to_le_bytes().iter());
+                    } else {
+                        bytes.push(entry as u8);
+                    }
+                }
+            }
+        }
+        if magic[0] == 0xd7 {
+            bytes.resize(bytes.len() + bytes.len() % 2, 0);
+        }
+        for (sparse, _, _) in parts.iter() {
+            bytes.extend(sparse.iter());
+        }
+        for (_, lengths, _) in parts.iter() {
+            bytes.extend(lengths.iter());
+        }
+        for (_, _, data) in parts.iter() {
+            bytes.resize((bytes.len() + 0x3f) & !0x3f, 0);
+            bytes.extend(data.iter());
+        }
+        bytes
+    }
+
+    // The sizes, sparse index, block lengths and data of one section
+    fn syzygy_section(flags: u8, values: &[u16]) -> (Vec<u8>, Vec<u8>, Vec<u8>, Vec<u8>) {
+        if values.iter().all(|value| *value == values[0]) {
+            return (vec![flags | 128, values[0] as u8], vec![], vec![], vec![]);
+        }
+
+        // Every symbol is a value, or a pair of symbols that keeps turning
+        // up next to each other
+        let mut symbols: Vec<(usize, usize, usize)> = Vec::new();
+        let mut leaves = std::collections::BTreeMap::new();
+        let mut stream: Vec<usize> = Vec::new();
+        for value in values.iter() {
+            let next = symbols.len();
+            let symbol = *leaves.entry(*value).or_insert(next);
+            if symbol == next {
+                symbols.push((*value as usize, 0xfff, 1));
+            }
+            stream.push(symbol);
+        }
+        for _ in 0..12 {
+            let mut counts = std::collections::BTreeMap::new();
+            for pair in stream.windows(2) {
+                if symbols[pair[0]].2 + symbols[pair[1]].2 <= 64 {
+                    *counts.entry((pair[0], pair[1])).or_insert(0) += 1;
+                }
+            }
+            let (&(left, right), &count) = match counts.iter().max_by_key(|(_, count)| **count) {
+                Some(best) => best,
+                None => break,
+            };
+            if count < 8 {
+                break;
+            }
+            symbols.push((left, right, symbols[left].2 + symbols[right].2));
+            let paired = symbols.len() - 1;
+            let mut merged = Vec::new();
+            let mut i = 0;
+            while i < stream.len() {
+                if i + 1 < stream.len() && stream[i] == left && stream[i + 1] == right {
+                    merged.push(paired);
+                    i += 2;
+                } else {
+                    merged.push(stream[i]);
+                    i += 1;
+                }
+            }
+            stream = merged;
+        }
+
+        // Huffman code lengths, giving every symbol a code even if it only
+        // turns up inside pairs
+        let mut weights = vec![1u64; symbols.len()];
+        for symbol in stream.iter() {
+            weights[*symbol] += 1;
+        }
+        let mut parent = vec![usize::MAX; symbols.len()];
+        let mut heap: std::collections::BinaryHeap<_> = weights
+            .iter()
+            .enumerate()
+            .map(|(node, weight)| std::cmp::Reverse((*weight, node)))
+            .collect();
+        while heap.len() > 1 {
+            let std::cmp::Reverse((first, a)) = heap.pop().unwrap();
+            let std::cmp::Reverse((second, b)) = heap.pop().unwrap();
+            parent.push(usize::MAX);
+            parent[a] = parent.len() - 1;
+            parent[b] = parent.len() - 1;
+            heap.push(std::cmp::Reverse((first + second, parent.len() - 1)));
+        }
+        let length = |mut node: usize| {
+            let mut length = 0;
+            while parent[node] != usize::MAX {
+                node = parent[node];
+                length += 1;
+            }
+            length
+        };
+        let lengths: Vec<usize> = (0..symbols.len()).map(length).collect();
+        let min = *lengths.iter().min().unwrap();
+        let max = *lengths.iter().max().unwrap();
+        assert!(max <= 32);
+
+        // Canonical codes: symbols are numbered longest code first, and
+        // shorter codes take the higher values
+        let mut order: Vec<usize> = (0..symbols.len()).collect();
+        order.sort_by_key(|symbol| std::cmp::Reverse(lengths[*symbol]));
+        let mut number = vec![0; symbols.len()];
+        for (n, symbol) in order.iter().enumerate() {
+            number[*symbol] = n;
+        }
+        let mut count = vec![0usize; max - min + 1];
+        for length in lengths.iter() {
+            count[length - min] += 1;
+        }
+        let mut lowest = vec![0; count.len()];
+        let mut base = vec![0; count.len()];
+        for i in (0..count.len() - 1).rev() {
+            lowest[i] = lowest[i + 1] + count[i + 1];
+            assert!((base[i + 1] + count[i + 1]).is_multiple_of(2));
+            base[i] = (base[i + 1] + count[i + 1]) / 2;
+        }
+        assert!(base[0] + count[0] == 1 << min);
+        let code = |symbol: usize| {
+            let i = lengths[symbol] - min;
+            (base[i] + number[symbol] - lowest[i], lengths[symbol])
+        };
+
+        // Whole symbols into each block, noting where each value ends up
+        const BLOCK_SIZE: usize = 32;
+        let mut data = Vec::new();
+        let mut block_starts = vec![0];
+        let mut bits: Vec<bool> = Vec::new();
+        let mut value = 0;
+        for symbol in stream.iter() {
+            let (code, length) = code(*symbol);
+            if bits.len() + length > BLOCK_SIZE * 8 {
+                bits.resize(BLOCK_SIZE * 8, false);
+                data.extend(
+                    bits.chunks(8)
+                        .map(|byte| byte.iter().fold(0u8, |byte, bit| byte << 1 | *bit as u8)),
+                );
+                bits.clear();
+                block_starts.push(value);
+            }
+            bits.extend((0..length).rev().map(|bit| code >> bit & 1 == 1));
+            value += symbols[*symbol].2;
+        }
+        bits.resize(BLOCK_SIZE * 8, false);
+        data.extend(
+            bits.chunks(8)
+                .map(|byte| byte.iter().fold(0u8, |byte, bit| byte << 1 | *bit as u8)),
+        );
+        block_starts.push(values.len());
+        let blocks = block_starts.len() - 1;
+
+        let mut block_lengths = Vec::new();
+        for block in 0..blocks {
+            let length = block_starts[block + 1] - block_starts[block] - 1;
+            block_lengths.extend((length as u16).to_le_bytes().iter());
+        }
+
+        // Where every span'th value is, from the middle of its span. Past
+        // the end, the last block carries on.
+        const SPAN: usize = 128;
+        let mut sparse = Vec::new();
+        for k in 0..values.len().div_ceil(SPAN) {
+            let at = k * SPAN + SPAN / 2;
+            let block = block_starts[..blocks].partition_point(|start| *start <= at) - 1;
+            sparse.extend((block as u32).to_le_bytes().iter());
+            sparse.extend(((at - block_starts[block]) as u16).to_le_bytes().iter());
+        }
+
+        let mut sizes = vec![flags, 5, 7, 0];
+        sizes.extend((blocks as u32).to_le_bytes().iter());
+        sizes.extend([max as u8, min as u8].iter());
+        for lowest in lowest.iter() {
+            sizes.extend((*lowest as u16).to_le_bytes().iter());
+        }
+        sizes.extend((symbols.len() as u16).to_le_bytes().iter());
+        for symbol in order.iter() {
+            let (left, right, _) = symbols[*symbol];
+            let (left, right) = if right == 0xfff {
+                (left, right)
+            } else {
+                (number[left], number[right])
+            };
+            sizes.extend(
+                [
+                    left as u8,
+                    (left >> 8 | (right & 0xf) << 4) as u8,
+                    (right >> 4) as u8,
+                ]
+                .iter(),
+            );
+        }
+        if symbols.len() % 2 == 1 {
+            sizes.push(0);
+        }
+        (sizes, sparse, block_lengths, data)
+    }
+
+    // A FEN with just these pieces on the board
+    fn fen_with(pieces: &[(char, usize)], white_to_move: bool) -> String {
+        let mut rows = Vec::new();
+        for rank in (0..8).rev() {
+            let mut row = String::new();
+            let mut empty = 0;
+            for file in 0..8 {
+                match pieces.iter().find(|(_, square)| *square == rank * 8 + file) {
+                    Some((letter, _)) => {
+                        if empty > 0 {
+                            row.push_str(&empty.to_string());
+                        }
+                        empty = 0;
+                        row.push(*letter);
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                row.push_str(&empty.to_string());
+            }
+            rows.push(row);
+        }
+        let side = if white_to_move { "w" } else { "b" };
+        format!("{} {} - - 0 1", rows.join("/"), side)
+    }
+
+    // The same position with the colours swapped and the board turned round
+    fn colours_swapped(pieces: &[(char, usize)]) -> Vec<(char, usize)> {
+        let swap = |letter: char| {
+            if letter.is_uppercase() {
+                letter.to_ascii_lowercase()
+            } else {
+                letter.to_ascii_uppercase()
+            }
+        };
+        pieces
+            .iter()
+            .map(|(letter, square)| (swap(*letter), square ^ 56))
+            .collect()
+    }
+
+    // Every legal placement of White's king and pawn against the king, with
+    // the pawn on files a to d and the side to move, as (king, pawn, other
+    // king, White to move)
+    fn kpk_placements() -> Vec<(usize, usize, usize, bool)> {
+        let mut placements = Vec::new();
+        for pawn in (8..56).filter(|pawn| bitboard::file_of(*pawn) < 4) {
+            for king in 0..64 {
+                for other in 0..64 {
+                    for white_to_move in [true, false] {
+                        let in_check = bitboard::pawn_attacks(piece::Sides::White, pawn)
+                            & bitboard::bit(other)
+                            != 0;
+                        if king != pawn
+                            && other != pawn
+                            && bitboard::distance(king, other) > 1
+                            && !(white_to_move && in_check)
+                        {
+                            placements.push((king, pawn, other, white_to_move));
+                        }
+                    }
+                }
+            }
+        }
+        placements
+    }
+
+    // Where the generator keeps a KPvK position: the pawn's rank, then each
+    // king on the squares the pieces before it left
+    fn kpk_index(king: usize, pawn: usize, other: usize) -> usize {
+        let free = |square: usize, before: &[usize]| {
+            square - before.iter().filter(|taken| **taken < square).count()
+        };
+        bitboard::rank_of(pawn) - 1 + 6 * (free(king, &[pawn]) + 63 * free(other, &[pawn, king]))
+    }
+
+    // KPvK tables with every value in its place. The WDL values come from
+    // the KPK bitbase. The DTZ table stores made up values, the kings'
+    // distance apart plus the defending king's distance from the pawn, with
+    // a different side to move, value map and flags on each file.
+    fn kpk_tables() -> (Vec<u8>, Vec<u8>) {
+        let mut header = vec![0x03];
+        for _ in 0..4 {
+            header.extend([0x00, 0x11, 0x66, 0xee].iter());
+        }
+        let dtz_flags = [2 | 8, 2 | 1, 2 | 16, 2 | 16 | 4];
+        let mut wdl: Vec<SyzygySection> = (0..8)
+            .map(|_| SyzygySection {
+                flags: 0,
+                values: vec![2; 23436],
+                maps: vec![],
+            })
+            .collect();
+        let mut dtz: Vec<SyzygySection> = (0..4)
+            .map(|file| SyzygySection {
+                flags: dtz_flags[file],
+                values: vec![0; 23436],
+                maps: (0..4)
+                    .map(|map| {
+                        let wide = if file >= 2 { 300 } else { 0 };
+                        (0..15).map(|value| wide + 3 * value + map + 1).collect()
+                    })
+                    .collect(),
+            })
+            .collect();
+        for (king, pawn, other, white_to_move) in kpk_placements() {
+            let file = bitboard::file_of(pawn);
+            let index = kpk_index(king, pawn, other);
+            let side_to_move = if white_to_move {
+                piece::Sides::White
+            } else {
+                piece::Sides::Black
+            };
+            let wins =
+                eval::endgame::kpk::probe(piece::Sides::White, side_to_move, king, other, pawn);
+            let value = match (wins, white_to_move) {
+                (false, _) => 2,
+                (true, true) => 4,
+                (true, false) => 0,
+            };
+            wdl[file * 2 + !white_to_move as usize].values[index] = value;
+            if (dtz_flags[file] & 1 == 0) == white_to_move {
+                dtz[file].values[index] =
+                    (bitboard::distance(king, other) + bitboard::distance(other, pawn)) as u16;
+            }
+        }
+        (
+            syzygy_file([0x71, 0xe8, 0x23, 0x5d], &header, &wdl),
+            syzygy_file([0xd7, 0x66, 0x0c, 0xa5], &header, &dtz),
+        )
+    }
+
+    #[test]
+    fn syzygy_pawn_tables_decode_every_file_and_colour() {
+        use tablebase::table::{Kind, Lookup, Table};
+
+        let (wdl_bytes, dtz_bytes) = kpk_tables();
+        let wdl = Table::new(wdl_bytes.clone(), "KPvK", Kind::Wdl).unwrap();
+        let dtz = Table::new(dtz_bytes.clone(), "KPvK", Kind::Dtz).unwrap();
+        let dtz_flags = [2 | 8, 2 | 1, 2 | 16, 2 | 16 | 4];
+
+        for (king, pawn, other, white_to_move) in kpk_placements().into_iter().step_by(13) {
+            let side_to_move = if white_to_move {
+                piece::Sides::White
+            } else {
+                piece::Sides::Black
+            };
+            let wins =
+                eval::endgame::kpk::probe(piece::Sides::White, side_to_move, king, other, pawn);
+            let expected = match (wins, white_to_move) {
+                (false, _) => 0,
+                (true, true) => 2,
+                (true, false) => -2,
+            };
+            let file = bitboard::file_of(pawn);
+            let stored = (dtz_flags[file] & 1 == 0) == white_to_move;
+            let distances =
+                (bitboard::distance(king, other) + bitboard::distance(other, pawn)) as i32;
+
+            // Mirrored onto files e to h, and with Black having the pawn
+            let pieces = [('K', king), ('P', pawn), ('k', other)];
+            let mirrored: Vec<(char, usize)> = pieces
+                .iter()
+                .map(|(letter, square)| (*letter, square ^ 7))
+                .collect();
+            for (pieces, white_to_move) in [
+                (pieces.to_vec(), white_to_move),
+                (colours_swapped(&pieces), !white_to_move),
+                (mirrored.clone(), white_to_move),
+                (colours_swapped(&mirrored), !white_to_move),
+            ] {
+                let position = position::from_fen(&fen_with(&pieces, white_to_move)).unwrap();
+                assert!(wdl.probe(&position, 0) == Some(Lookup::Value(expected)));
+                for result in [-2, -1, 1, 2] {
+                    let map = [1, 3, 0, 2, 0][(result + 2) as usize];
+                    let wide = if file >= 2 { 300 } else { 0 };
+                    let plies = (result == 2 && dtz_flags[file] & 4 != 0)
+                        || (result == -2 && dtz_flags[file] & 8 != 0);
+                    let value = wide + 3 * distances + map + 1;
+                    let value = if plies { value } else { value * 2 };
+                    let expected = if stored {
+                        Lookup::Value(value + 1)
+                    } else {
+                        Lookup::OtherSideToMove
+                    };
+                    assert!(dtz.probe(&position, result) == Some(expected));
+                }
+            }
+        }
+
+        // And through the tablebases, which find the pawn being taken
+        let directory = scratch_directory("kpk");
+        std::fs::write(directory.join("KPvK.rtbw"), wdl_bytes).unwrap();
+        std::fs::write(directory.join("KPvK.rtbz"), dtz_bytes).unwrap();
+        let tablebases = tablebase::Tablebases::open(&directory).unwrap();
+        assert!(tablebases.has_dtz());
+        let probe = |fen: &str| tablebases.probe_wdl(&mut position::from_fen(fen).unwrap());
+        assert!(probe("4k3/8/4K3/4P3/8/8/8/8 b - - 0 1") == Some(tablebase::Wdl::Loss));
+        assert!(probe("8/8/8/8/8/4k3/4p3/4K3 w - - 0 1") == Some(tablebase::Wdl::Draw));
+        assert!(probe("8/8/8/8/8/8/3kp3/7K w - - 0 1") == Some(tablebase::Wdl::Loss));
+    }
+
+    // Where the generator keeps a KRvK position: turned so the king is in
+    // the a1-d1-d4 triangle and the first piece off the diagonal is below
+    // it, then the three squares together
+    fn krk_index(mut squares: [usize; 3]) -> usize {
+        let (rank, file) = (bitboard::rank_of, bitboard::file_of);
+        let off = |square: usize| rank(square) as i32 - file(square) as i32;
+        if file(squares[0]) > 3 {
+            squares.iter_mut().for_each(|square| *square ^= 7);
+        }
+        if rank(squares[0]) > 3 {
+            squares.iter_mut().for_each(|square| *square ^= 56);
+        }
+        if let Some(first) = (0..3).find(|i| off(squares[*i]) != 0) {
+            if off(squares[first]) > 0 {
+                for square in squares[first..].iter_mut() {
+                    *square = rank(*square) + file(*square) * 8;
+                }
+            }
+        }
+        let below = |square: usize| (0..square).filter(|other| off(*other) < 0).count();
+        let triangle = |square: usize| {
+            if off(square) == 0 {
+                6 + rank(square)
+            } else {
+                (0..square)
+                    .filter(|other| off(*other) < 0 && file(*other) < 4)
+                    .count()
+            }
+        };
+        let [first, second, third] = squares;
+        let adjust1 = (second > first) as usize;
+        let adjust2 = (third > first) as usize + (third > second) as usize;
+        if off(first) != 0 {
+            (triangle(first) * 63 + second - adjust1) * 62 + third - adjust2
+        } else if off(second) != 0 {
+            (6 * 63 + rank(first) * 28 + below(second)) * 62 + third - adjust2
+        } else if off(third) != 0 {
+            6 * 63 * 62
+                + 4 * 28 * 62
+                + rank(first) * 7 * 28
+                + (rank(second) - adjust1) * 28
+                + below(third)
+        } else {
+            6 * 63 * 62
+                + 4 * 28 * 62
+                + 4 * 7 * 28
+                + rank(first) * 7 * 6
+                + (rank(second) - adjust1) * 6
+                + rank(third)
+                - adjust2
+        }
+    }
+
+    // What KRvK is worth to the side to move, as the table stores it, or
+    // nothing if the position can't happen. White always wins, unless Black
+    // can take the rook or is stalemated.
+    fn krk_value(king: usize, rook: usize, other: usize, white_to_move: bool) -> Option<u16> {
+        let occupied = bitboard::bit(king) | bitboard::bit(rook) | bitboard::bit(other);
+        let in_check = bitboard::rook_attacks(rook, occupied) & bitboard::bit(other) != 0;
+        if king == rook || other == rook || bitboard::distance(king, other) < 2 {
+            return None;
+        }
+        if white_to_move {
+            return if in_check { None } else { Some(4) };
+        }
+        let guarded = bitboard::king_attacks(king)
+            | bitboard::rook_attacks(rook, occupied & !bitboard::bit(other));
+        let takes = bitboard::distance(other, rook) == 1 && guarded & bitboard::bit(rook) == 0;
+        let escapes = bitboard::king_attacks(other) & !guarded & !bitboard::bit(rook) != 0;
+        Some(if takes || (!escapes && !in_check) {
+            2
+        } else {
+            0
+        })
+    }
+
+    #[test]
+    fn syzygy_tables_without_pawns_decode_every_symmetry() {
+        use tablebase::table::{Kind, Lookup, Table};
+
+        let mut sections: Vec<SyzygySection> = (0..2)
+            .map(|_| SyzygySection {
+                flags: 0,
+                values: vec![0; 31332],
+                maps: vec![],
+            })
+            .collect();
+        let mut written = vec![[None; 2]; 31332];
+        for king in 0..64 {
+            for rook in 0..64 {
+                for other in 0..64 {
+                    for white_to_move in [true, false] {
+                        let value = match krk_value(king, rook, other, white_to_move) {
+                            Some(value) => value,
+                            None => continue,
+                        };
+                        let index = krk_index([king, rook, other]);
+                        let section = !white_to_move as usize;
+                        // Positions that turn into each other share a place
+                        assert!(written[index][section].is_none_or(|stored| stored == value));
+                        written[index][section] = Some(value);
+                        sections[section].values[index] = value;
+                    }
+                }
+            }
+        }
+        let header = [0x01, 0x00, 0x66, 0x44, 0xee];
+        let table = Table::new(
+            syzygy_file([0x71, 0xe8, 0x23, 0x5d], &header, &sections),
+            "KRvK",
+            Kind::Wdl,
+        )
+        .unwrap();
+
+        let transform = |square: usize, symmetry: usize| {
+            let square = if symmetry & 1 != 0 {
+                square ^ 7
+            } else {
+                square
+            };
+            let square = if symmetry & 2 != 0 {
+                square ^ 56
+            } else {
+                square
+            };
+            if symmetry & 4 != 0 {
+                bitboard::rank_of(square) + bitboard::file_of(square) * 8
+            } else {
+                square
+            }
+        };
+        let mut checked = 0;
+        for (n, (king, rook, other)) in (0..64 * 64 * 64)
+            .map(|n| (n / 4096, n / 64 % 64, n % 64))
+            .enumerate()
+        {
+            for white_to_move in [true, false] {
+                let value = match krk_value(king, rook, other, white_to_move) {
+                    Some(value) if (n + white_to_move as usize).is_multiple_of(97) => value,
+                    _ => continue,
+                };
+                let symmetry = n / 97 % 8;
+                let pieces: Vec<(char, usize)> = [('K', king), ('R', rook), ('k', other)]
+                    .iter()
+                    .map(|(letter, square)| (*letter, transform(*square, symmetry)))
+                    .collect();
+                let expected = Some(Lookup::Value(value as i32 - 2));
+                for (pieces, white_to_move) in [
+                    (pieces.clone(), white_to_move),
+                    (colours_swapped(&pieces), !white_to_move),
+                ] {
+                    let position = position::from_fen(&fen_with(&pieces, white_to_move)).unwrap();
+                    assert!(table.probe(&position, 0) == expected);
+                    checked += 1;
+                }
+            }
+        }
+        assert!(checked > 4000);
+    }
+
+    #[test]
+    fn san_moves_read_and_write_the_way_people_write_them() {
+        let mut position = position::start();
+        let e4 = position.parse_san("e4").unwrap();
+        assert!(e4.to_string() == "e2e4");
+        assert!(position.san(e4) == "e4");
+        assert!(position.parse_san("Nf3").unwrap().to_string() == "g1f3");
+        assert!(position.parse_san("Ke2").is_none());
+
+        let mut position = position::from_fen("r3k2r/1P6/8/8/8/2N5/8/R3K1NR w KQkq - 0 1").unwrap();
+        let castle = position.parse_san("O-O-O").unwrap();
+        assert!(castle.to_string() == "e1c1");
+        assert!(position.san(castle) == "O-O-O");
+        assert!(position.parse_san("0-0-0") == Some(castle));
+        // Two knights can reach e2, so the file is needed
+        assert!(position.parse_san("Ne2").is_none());
+        let knight = position.parse_san("Nge2").unwrap();
+        assert!(knight.to_string() == "g1e2");
+        assert!(position.san(knight) == "Nge2");
+        let promotion = position.parse_san("bxa8=Q+").unwrap();
+        assert!(promotion.to_string() == "b7a8q");
+        assert!(position.san(promotion) == "bxa8=Q+");
+        assert!(position.parse_san("b8N") == position.parse_move("b7b8n"));
+    }
+
+    #[test]
+    fn polyglot_keys_match_the_reference_values() {
+        // From the Polyglot book format description
+        let keys = vec![
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                0x463b96181691fc9c,
+            ),
+            (
+                "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1",
+                0x823c9b50fd114196,
+            ),
+            (
+                "rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 2",
+                0x0756b94461c50fb0,
+            ),
+            (
+                "rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR b KQkq - 0 2",
+                0x662fafb965db29d4,
+            ),
+            (
+                "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+                0x22a48b5a8e47ff78,
+            ),
+            (
+                "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPPKPPP/RNBQ1BNR b kq - 0 3",
+                0x652a607ca3f242c1,
+            ),
+            (
+                "rnbq1bnr/ppp1pkpp/8/3pPp2/8/8/PPPPKPPP/RNBQ1BNR w - - 0 4",
+                0x00fdd303c946bdd9,
+            ),
+            (
+                "rnbqkbnr/p1pppppp/8/8/PpP4P/8/1P1PPPP1/RNBQKBNR b KQkq c3 0 3",
+                0x3c8123ea7b067637,
+            ),
+            (
+                "rnbqkbnr/p1pppppp/8/8/P6P/R1p5/1P1PPPP1/1NBQKBNR b Kkq - 0 4",
+                0x5c3f9b829b279560,
+            ),
+        ];
+        for (fen, key) in keys {
+            assert!(book::key::key(&position::from_fen(fen).unwrap()) == key);
+        }
+    }
+
+    #[test]
+    fn polyglot_books_round_trip_and_choose_moves() {
+        let mut position = position::start();
+        let e4 = position.parse_move("e2e4").unwrap();
+        let d4 = position.parse_move("d2d4").unwrap();
+        let key = book::key::key(&position);
+        let entries = vec![
+            book::Entry {
+                key,
+                mv: book::encode_move(&position, d4),
+                weight: 1,
+                learn: 0,
+            },
+            book::Entry {
+                key,
+                mv: book::encode_move(&position, e4),
+                weight: 3,
+                learn: 0,
+            },
+        ];
+        let mut book = book::Book::new(entries);
+        let bytes = book.to_bytes();
+        assert!(bytes.len() == 32);
+        assert!(book::Book::from_bytes(&bytes[..31]).is_err());
+        let read = book::Book::from_bytes(&bytes).unwrap();
+        assert!(read.moves(&mut position) == vec![(e4, 3), (d4, 1)]);
+
+        book.choice = book::Choice::Best;
+        let mut random = 1;
+        assert!(book.choose(&mut position, &mut random) == Some(e4));
+
+        book.choice = book::Choice::Weighted;
+        let picks: Vec<_> = (0..200)
+            .map(|_| book.choose(&mut position, &mut random).unwrap())
+            .collect();
+        let e4_picks = picks.iter().filter(|mv| **mv == e4).count();
+        assert!(e4_picks > 100 && e4_picks < 200);
+
+        book.max_depth = Some(0);
+        assert!(book.choose(&mut position, &mut random).is_none());
+        position.make_move(e4);
+        book.max_depth = None;
+        assert!(book.choose(&mut position, &mut random).is_none());
+
+        // White castling is stored as the king taking its rook
+        let position = position::from_fen("4k3/8/8/8/8/8/8/R3K2R w KQ - 0 1").unwrap();
+        let castle = crate::position::Move::new(4, 6);
+        assert!(book::encode_move(&position, castle) == (4 << 6 | 7));
+    }
+
+    #[test]
+    fn searcher_plays_book_moves_without_searching() {
+        let mut position = position::start();
+        let e4 = position.parse_move("e2e4").unwrap();
+        let book = book::Book::new(vec![book::Entry {
+            key: book::key::key(&position),
+            mv: book::encode_move(&position, e4),
+            weight: 1,
+            learn: 0,
+        }]);
+        let mut searcher = search::Searcher::new(search::Options::default());
+        searcher.set_book(Some(std::sync::Arc::new(book)));
+        let limits = search::Limits {
+            depth: Some(3),
+            ..Default::default()
+        };
+        let result = searcher.search(&mut position, &limits);
+        assert!(result.best_move == Some(e4));
+        assert!(result.nodes == 0);
+
+        position.make_move(e4);
+        let result = searcher.search(&mut position, &limits);
+        assert!(result.nodes > 0);
+    }
+
+    #[test]
+    fn books_are_built_from_pgn_with_minimum_game_counts() {
+        let pgn = r#"
+[Event "One"]
+[Result "1-0"]
+
+1. e4 e5 {a comment} 2. Nf3 (2. f4 exf4) Nc6 $1 3. Bb5 1-0
+
+[Event "Two"]
+[Result "1/2-1/2"]
+
+1.e4 e5 2.Nf3 Nf6 1/2-1/2
+
+[Event "Three"]
+[Result "0-1"]
+
+1. d4 d5 ; rest of the line is ignored
+2. O-O 0-1
+"#;
+        let games = book::pgn::parse(pgn);
+        assert!(games.len() == 3);
+        assert!(games[0].tag("Event") == Some("One"));
+        assert!(games[0].moves == vec!["e4", "e5", "Nf3", "Nc6", "Bb5"]);
+        assert!(games[1].outcome == book::pgn::Outcome::Draw);
+
+        // The third game has an illegal move, so it's left out
+        let mut builder = book::builder::BookBuilder::new();
+        builder.min_games = 2;
+        assert!(builder.add_pgn(pgn) == 2);
+        assert!(builder.games() == 2);
+        assert!(builder.skipped() == 1);
+        let book = builder.build();
+        // e4, e5 and Nf3 were each played twice, and e5 scored for Black
+        // once, in the draw
+        assert!(book.len() == 3);
+        let mut position = position::start();
+        let moves = book.moves(&mut position);
+        assert!(moves.len() == 1);
+        assert!(moves[0].0.to_string() == "e2e4");
+        assert!(moves[0].1 == 3);
+    }
+
+    #[test]
+    fn evaluation_parameters_round_trip_through_text() {
+        let params = eval::Params::default();
+        let values = params.values();
+        assert!(values.len() == params.names().len());
+        assert!(params.names()[0] == "material[0].mg");
+        assert!(params.names().contains(&"pst[1][12].eg".to_string()));
+        assert!(params.names().contains(&"king_zone_attack".to_string()));
+
+        let mut changed = params.clone();
+        let mut new_values = values.clone();
+        new_values[2] += 7;
+        changed.set_values(&new_values);
+        assert!(changed.material[1].mg == params.material[1].mg + 7);
+
+        let read = eval::Params::from_text(&changed.to_text()).unwrap();
+        assert!(read.values() == new_values);
+        let partial = eval::Params::from_text("# a comment\n\nbishop_pair.eg 77\n").unwrap();
+        assert!(partial.bishop_pair.eg == 77);
+        assert!(partial.bishop_pair.mg == params.bishop_pair.mg);
+        assert!(eval::Params::from_text("no_such_thing 3").is_err());
+        assert!(eval::Params::from_text("doubled.mg lots").is_err());
+
+        let rust = params.to_rust();
+        assert!(rust.starts_with("impl Default for Params {"));
+        assert!(rust.contains("            doubled: s(-10, -20),\n"));
+        assert!(rust.contains("king_zone_attack: 12,"));
+    }
+
+    #[test]
+    fn saved_parameters_change_how_the_engine_evaluates() {
+        let mut params = eval::Params::default();
+        let doubled: Vec<i32> = params.values().iter().map(|value| value * 2).collect();
+        params.set_values(&doubled);
+        let file = scratch_directory("params").join("tuned.txt");
+        params.save(&file).unwrap();
+        let path = file.to_str().unwrap().to_string();
+
+        // A knight up, which isn't one of the endgames scored on their own
+        let fen = "r1bqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        let position = position::from_fen(fen).unwrap();
+        let usual = eval::Evaluator::new().evaluate(&position);
+        let loaded = eval::Params::load(&file).unwrap();
+        let tuned = eval::Evaluator::with_params(loaded).evaluate(&position);
+        assert!(tuned != usual);
+        assert!(tuned == eval::Evaluator::with_params(params.clone()).evaluate(&position));
+
+        // On the command line
+        let args: Vec<String> = ["black", "--params", &path, "5"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+        let (from_args, rest) = eval_params(&args).unwrap();
+        assert!(from_args.values() == doubled);
+        assert!(rest == ["black", "5"]);
+        assert!(eval_params(&["--params".to_string()]).is_err());
+
+        // And over UCI, where a personality leans on whichever values are
+        // loaded rather than going back to the built in ones
+        let score = |params: Option<&str>, personality: &str| {
+            let (sender, receiver) = std::sync::mpsc::channel();
+            let mut engine = uci::Uci::new(sender);
+            if let Some(path) = params {
+                engine.command(&format!("setoption name EvalParams value {}", path));
+            }
+            engine.command(&format!("setoption name Personality value {}", personality));
+            engine.command(&format!("position fen {}", fen));
+            engine.command("go depth 1");
+            let lines = uci_until(&receiver, "bestmove");
+            lines
+                .iter()
+                .rev()
+                .find_map(|line| line.split(" score cp ").nth(1))
+                .and_then(|rest| rest.split_whitespace().next())
+                .unwrap()
+                .parse::<i32>()
+                .unwrap()
+        };
+        assert!(score(Some(&path), "balanced") != score(None, "balanced"));
+        assert!(score(Some("<empty>"), "balanced") == score(None, "balanced"));
+        assert!(score(Some(&path), "aggressive") != score(None, "aggressive"));
+
+        // The same in a game
+        let reply = |params: &eval::Params| {
+            let limits = search::Limits {
+                depth: Some(1),
+                ..Default::default()
+            };
+            let mut game =
+                play::Game::new(piece::Sides::White, search::skill::Skill::full(), limits);
+            game.set_params(params.clone());
+            game.input("personality aggressive").unwrap();
+            game.input("e4").unwrap()
+        };
+        assert!(reply(&params) != reply(&eval::Params::default()));
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let mut engine = uci::Uci::new(sender);
+        engine.command("uci");
+        let options = uci_until(&receiver, "uciok");
+        assert!(options.contains(&"option name EvalParams type string default <empty>".to_string()));
+        engine.command("setoption name EvalParams value /no/such/params");
+        assert!(receiver
+            .recv()
+            .unwrap()
+            .starts_with("info string can't read /no/such/params"));
+    }
+
+    #[test]
+    fn tuning_samples_read_the_common_formats() {
+        let sample = tune::parse_sample("4k3/8/8/8/8/8/8/Q3K3 w - - 0 1 [1.0]").unwrap();
+        assert!(sample.result == 1.0);
+        let sample = tune::parse_sample("4k3/8/8/8/8/8/8/Q3K3 w - - c9 \"1/2-1/2\";").unwrap();
+        assert!(sample.result == 0.5);
+        let sample = tune::parse_sample("4k3/8/8/8/8/8/8/Q3K3 b - - 12 40; 0-1").unwrap();
+        assert!(sample.result == 0.0);
+        assert!(sample.position.halfmove_clock == 12);
+        assert!(tune::parse_sample("4k3/8/8/8/8/8/8/Q3K3 w - -").is_err());
+        assert!(
+            tune::parse_samples("\n4k3/8/8/8/8/8/8/Q3K3 w - - 1-0\n")
+                .unwrap()
+                .len()
+                == 1
+        );
+
+        assert!(tune::sigmoid(0.0, 1.0) == 0.5);
+        assert!(tune::sigmoid(400.0, 1.0) > 0.9);
+    }
+
+    #[test]
+    fn tuning_lowers_the_error() {
+        // Bishop pairs that keep losing should end up worth less
+        let lines = [
+            "4k3/pppp4/8/8/8/8/PPPP4/2BBK3 w - - 0 1 [0.0]",
+            "4k3/pppp4/8/8/8/8/PPPP4/2BBK3 b - - 0 1 [0.0]",
+            "2bbk3/pppp4/8/8/8/8/PPPP4/4K3 w - - 0 1 [1.0]",
+            "4k3/pppp4/8/8/8/8/PPPP4/4K3 w - - 0 1 [0.5]",
+        ];
+        let samples = tune::parse_samples(&lines.join("\n")).unwrap();
+        let mut tuner = tune::Tuner::new(samples);
+        let params = eval::Params::default();
+        tuner.k = 1.0;
+        let before = tuner.error(&params);
+
+        let mut passes = 0;
+        let tuned = tuner.tune(&params, 1, |_, _| passes += 1);
+        assert!(passes == 1);
+        assert!(tuner.error(&tuned) < before);
+        assert!(tuned.bishop_pair.eg < params.bishop_pair.eg);
+    }
+
+    #[test]
+    fn evaluation_traces_add_up_to_the_evaluation() {
+        let fens = [
+            position::START_FEN,
+            "r3k2r/pp3ppp/2n1b3/3pP3/1b1P4/2N2N2/PP3PPP/R2QKB1R b KQkq - 0 1",
+            "8/5k2/8/3P4/8/8/5K2/8 w - - 0 1",
+        ];
+        let mut evaluator = eval::Evaluator::new();
+        for fen in fens.iter() {
+            let position = position::from_fen(fen).unwrap();
+            let trace = evaluator.trace(&position);
+            assert!(trace.evaluation() == evaluator.evaluate(&position));
+        }
+
+        // A lone passed pawn is all passed pawn bonus and no structure
+        let position = position::from_fen(fens[2]).unwrap();
+        let trace = evaluator.trace(&position);
+        let passed = trace.term("Passed pawns").unwrap();
+        assert!(passed.white() != eval::Tapered::default());
+        assert!(passed.black() == eval::Tapered::default());
+        assert!(trace.term("Pawns").unwrap().white() == evaluator.params.isolated);
+        assert!(trace.term("Material").unwrap().total() == evaluator.params.material[0]);
+
+        let table = trace.to_string();
+        assert!(table.contains("King safety"));
+        assert!(table.contains("Phase: 0/24"));
+    }
+
+    #[test]
+    fn endgames_are_recognised_by_material() {
+        let params = eval::Params::default();
+        let probe = |fen: &str| eval::endgame::probe(&position::from_fen(fen).unwrap(), &params);
+        let win = eval::endgame::KNOWN_WIN;
+
+        // King in front of its pawn on the sixth wins, a king in front of a
+        // rook pawn draws, and a rook pawn the king can't catch queens
+        let (endgame, score) = probe("4k3/8/4K3/4P3/8/8/8/8 w - - 0 1").unwrap();
+        assert!(endgame == eval::endgame::Endgame::Kpk);
+        assert!(score > win);
+        assert!(probe("4k3/8/4K3/4P3/8/8/8/8 b - - 0 1").unwrap().1 > win);
+        assert!(probe("8/8/8/8/8/4k3/4P3/4K3 w - - 0 1").unwrap().1 == 0);
+        assert!(probe("k7/8/8/8/8/8/P7/K7 w - - 0 1").unwrap().1 == 0);
+        assert!(probe("7k/8/P7/8/8/8/8/K7 b - - 0 1").unwrap().1 > win);
+        // The same from Black's side
+        assert!(probe("8/8/8/8/4p3/4k3/8/4K3 b - - 0 1").unwrap().1 < -win);
+        assert!(probe("4k3/4p3/4K3/8/8/8/8/8 b - - 0 1").unwrap().1 == 0);
+
+        // Lone kings are better off in the middle, and against bishop and
+        // knight better off in the corners the bishop can't reach
+        let edge = probe("3k4/8/8/8/8/8/8/KR6 w - - 0 1").unwrap().1;
+        let middle = probe("8/8/8/3k4/8/8/8/KR6 w - - 0 1").unwrap().1;
+        assert!(middle > win && edge > middle);
+        assert!(probe("8/8/8/4k3/8/8/8/2b1Kb2 w - - 0 1").unwrap().1 < -win);
+        let right = probe("k7/8/8/8/4K3/8/8/1BN5 w - - 0 1").unwrap();
+        let wrong = probe("7k/8/8/8/4K3/8/8/1BN5 w - - 0 1").unwrap();
+        assert!(right.0 == eval::endgame::Endgame::Kbnk);
+        assert!(right.1 > wrong.1 && wrong.1 > win);
+
+        // Material that can't win
+        assert!(probe("k7/8/8/8/8/8/P7/K1B5 w - - 0 1").unwrap().1 == 0);
+        assert!(probe("1k6/8/P7/8/8/8/P7/K1B5 w - - 0 1").unwrap().1 == 0);
+        assert!(probe("k7/8/8/8/8/8/P7/KB6 w - - 0 1").is_none());
+        assert!(probe("8/8/3k4/8/8/8/8/KNN5 w - - 0 1").unwrap().1 == 0);
+        assert!(probe("8/8/3k4/5n2/8/8/8/KB6 w - - 0 1").unwrap().1 == 0);
+        assert!(probe(position::START_FEN).is_none());
+
+        // And the evaluation goes by them
+        let position = position::from_fen("8/8/8/8/4p3/4k3/8/4K3 w - - 0 1").unwrap();
+        assert!(eval::evaluate(&position) < -win);
+        let mut evaluator = eval::Evaluator::new();
+        let trace = evaluator.trace(&position);
+        assert!(trace.evaluation() == evaluator.evaluate(&position));
+        assert!(trace.to_string().contains("Endgame: KPK"));
+    }
+
+    // Everything the engine says up to and including the line starting with
+    // `last`
+    fn uci_until(receiver: &std::sync::mpsc::Receiver<String>, last: &str) -> Vec<String> {
+        let mut lines = Vec::new();
+        loop {
+            let line = receiver
+                .recv_timeout(std::time::Duration::from_secs(30))
+                .unwrap();
+            let done = line.starts_with(last);
+            lines.push(line);
+            if done {
+                return lines;
+            }
+        }
+    }
+
+    #[test]
+    fn uci_sessions_search_and_answer() {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let mut engine = uci::Uci::new(sender);
+
+        assert!(engine.command("uci"));
+        let lines = uci_until(&receiver, "uciok");
+        assert!(lines[0].starts_with("id name"));
+        assert!(lines
+            .iter()
+            .any(|line| line.starts_with("option name Hash type spin")));
+        engine.command("isready");
+        assert!(receiver.recv().unwrap() == "readyok");
+
+        engine.command("setoption name Hash value 4");
+        engine.command("setoption name MultiPV value 2");
+        engine.command("ucinewgame");
+        engine.command("position startpos moves e2e4 e7e5");
+        engine.command("go depth 3");
+        let lines = uci_until(&receiver, "bestmove");
+        let infos: Vec<&String> = lines
+            .iter()
+            .filter(|line| line.starts_with("info"))
+            .collect();
+        assert!(infos.len() == 6);
+        assert!(infos[5].starts_with("info depth 3 multipv 2 score cp"));
+        assert!(
+            infos[4].contains(" nodes ") && infos[4].contains(" nps ") && infos[4].contains(" pv ")
+        );
+        let best = lines.last().unwrap();
+        assert!(best.starts_with("bestmove ") && best.contains(" ponder "));
+
+        // Mate in one, found and shown as such
+        engine.command("setoption name MultiPV value 1");
+        engine.command("position fen 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1");
+        engine.command("go depth 2");
+        let lines = uci_until(&receiver, "bestmove");
+        assert!(lines.iter().any(|line| line.contains("score mate 1")));
+        assert!(lines.last().unwrap().starts_with("bestmove a1a8"));
+
+        // An infinite search only answers once stopped, and clocks work
+        engine.command("position startpos");
+        engine.command("go infinite");
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        engine.command("stop");
+        assert!(uci_until(&receiver, "bestmove")
+            .last()
+            .unwrap()
+            .starts_with("bestmove"));
+        engine.command("go wtime 1000 btime 1000 winc 10 binc 10");
+        assert!(uci_until(&receiver, "bestmove").len() > 1);
+
+        engine.command("position startpos moves e2e5");
+        assert!(receiver.recv().unwrap() == "info string illegal move: e2e5");
+        engine.command("setoption name Nonsense value 1");
+        assert!(receiver
+            .recv()
+            .unwrap()
+            .starts_with("info string no option"));
+        assert!(!engine.command("quit"));
+    }
+
+    #[test]
+    fn cecp_sessions_keep_the_game_and_play_moves() {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let mut engine = cecp::Cecp::new(sender);
+
+        engine.command("xboard");
+        engine.command("protover 2");
+        let features = receiver.recv().unwrap();
+        assert!(features.starts_with("feature ") && features.contains("usermove=1"));
+        assert!(features.ends_with("done=1"));
+
+        engine.command("new");
+        engine.command("sd 3");
+        engine.command("post");
+        engine.command("usermove e2e4");
+        let lines = uci_until(&receiver, "move ");
+        assert!(lines.len() == 4);
+        assert!(lines[2].starts_with("3 "));
+
+        // The reply is on the board once the next command comes in
+        engine.command("ping 7");
+        assert!(receiver.recv().unwrap() == "pong 7");
+        engine.command("force");
+        engine.command("usermove Nf3");
+        assert!(receiver.try_recv().is_err());
+        engine.command("usermove e2e4");
+        assert!(receiver.recv().unwrap() == "Illegal move: e2e4");
+        engine.command("undo");
+        engine.command("remove");
+        engine.command("usermove e2e4");
+        assert!(receiver.try_recv().is_err());
+
+        // Mate in one, which it plays and announces
+        engine.command("nopost");
+        engine.command("setboard 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1");
+        engine.command("level 40 5 0");
+        engine.command("time 30000");
+        engine.command("otim 30000");
+        engine.command("go");
+        assert!(receiver.recv().unwrap() == "move a1a8");
+        assert!(receiver.recv().unwrap() == "1-0 {White mates}");
+
+        engine.command("setboard not a position");
+        assert!(receiver.recv().unwrap() == "tellusererror Illegal position");
+        engine.command("level 40");
+        assert!(receiver
+            .recv()
+            .unwrap()
+            .starts_with("Error (expected level"));
+        engine.command("level 40 5 1e300");
+        assert!(receiver
+            .recv()
+            .unwrap()
+            .starts_with("Error (expected level"));
+        for line in ["st 1e300", "st inf"].iter() {
+            engine.command(line);
+            assert!(receiver.recv().unwrap() == format!("Error (expected st <seconds>): {}", line));
+        }
+        engine.command("result 1-0 {White mates}");
+        assert!(!engine.command("quit"));
+    }
+
+    #[test]
+    fn games_against_the_engine_take_moves_and_commands() {
+        let limits = search::Limits {
+            depth: Some(2),
+            ..Default::default()
+        };
+        let mut game = play::Game::new(piece::Sides::White, search::skill::Skill::full(), limits);
+
+        // Either notation, answered straight away
+        let reply = game.input("e4").unwrap();
+        assert!(reply.starts_with("1. ... ") && reply.contains("a  b  c  d  e  f  g  h"));
+        assert!(game.position().history.len() == 2);
+        game.input("g1f3").unwrap();
+        assert!(game.position().history.len() == 4);
+        assert!(game.input("e2e4").is_err());
+        assert!(game.input("castle").is_err());
+
+        // Undo takes back the reply as well
+        game.input("undo").unwrap();
+        assert!(game.position().history.len() == 2);
+        game.input("undo").unwrap();
+        assert!(game.position().history.is_empty());
+        assert!(game.input("undo").is_err());
+
+        assert!(game.input("fen").unwrap() == position::START_FEN);
+        assert!(game
+            .input("flip")
+            .unwrap()
+            .contains("h  g  f  e  d  c  b  a"));
+        assert!(game.input("hint").unwrap().starts_with("Try "));
+        assert!(game.input("level 25").is_err());
+        game.input("level 5").unwrap();
+        assert!(game.input("draw").is_ok());
+
+        assert!(game.input("resign").unwrap() == "You resign. 0-1");
+        assert!(game.result() == Some("0-1 {You resign}"));
+        assert!(game.input("e4").is_err());
+
+        // Playing Black, the engine opens
+        let board = game.input("new black").unwrap();
+        assert!(board.starts_with("1. ") && board.contains("h  g  f  e  d  c  b  a"));
+        assert!(game.position().side_to_move == piece::Sides::Black);
+        assert!(game.result().is_none());
+    }
+
+    #[test]
+    fn network_accumulators_follow_moves_and_take_backs() {
+        use eval::nnue::{Accumulators, Network};
+        use piece::Sides;
+        use std::sync::Arc;
+
+        let network = Arc::new(Network::random(16, 7));
+        let mut position =
+            position::from_fen("r3k2r/6p1/8/3pP3/8/8/1p6/R3K2R w KQkq d6 0 1").unwrap();
+        position.set_network(Some(network.clone()));
+        let start = position.nnue.clone().unwrap();
+
+        // En passant, castling, a capture promotion and an ordinary move
+        for text in ["e5d6", "e8c8", "e1g1", "b2a1q", "d6d7"].iter() {
+            let mv = position.parse_move(text).unwrap();
+            position.make_move(mv);
+            let fresh = Accumulators::new(network.clone(), &position);
+            let kept = position.nnue.as_ref().unwrap();
+            for side in [Sides::White, Sides::Black].iter() {
+                assert!(kept.values(*side) == fresh.values(*side));
+            }
+            assert!(kept.evaluate(position.side_to_move) == fresh.evaluate(position.side_to_move));
+        }
+        while !position.history.is_empty() {
+            position.unmake_move();
+        }
+        let back = position.nnue.as_ref().unwrap();
+        assert!(back.values(Sides::White) == start.values(Sides::White));
+        assert!(back.values(Sides::Black) == start.values(Sides::Black));
+    }
+
+    #[test]
+    fn networks_load_from_weights_files_and_evaluate_symmetrically() {
+        use eval::nnue::Network;
+        use std::sync::Arc;
+
+        let network = Network::random(8, 3);
+        let bytes = network.to_bytes();
+        let read = Network::from_bytes(&bytes).unwrap();
+        assert!(read.hidden() == 8);
+        assert!(read.to_bytes() == bytes);
+        assert!(Network::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(Network::from_bytes(b"NOPE").is_err());
+        let path = scratch_directory("nnue").join("net.bin");
+        network.save(&path).unwrap();
+        let network = Arc::new(Network::load(&path).unwrap());
+
+        // Both sides see the board the same way, so a position and its
+        // mirror image with the colours swapped score the same
+        let mut evaluator = eval::Evaluator::new();
+        evaluator.network = Some(network.clone());
+        let position = position::from_fen("4k3/8/8/3n4/8/2P5/8/4K3 w - - 0 1").unwrap();
+        let mirrored = position::from_fen("4k3/8/2p5/8/3N4/8/8/4K3 b - - 0 1").unwrap();
+        assert!(evaluator.evaluate(&position) == evaluator.evaluate(&mirrored));
+        assert!(evaluator.evaluate(&position) == eval::nnue::evaluate(&network, &position));
+
+        let mut searcher = search::Searcher::new(search::Options::default());
+        searcher.evaluator.network = Some(network);
+        let mut position = position::start();
+        let limits = search::Limits {
+            depth: Some(3),
+            ..Default::default()
+        };
+        let result = searcher.search(&mut position, &limits);
+        assert!(result.best_move.is_some());
+        assert!(position.nnue.is_none());
+        assert!(position.history.is_empty());
+    }
+
+    #[test]
+    fn self_play_writes_training_data_the_tuner_can_read() {
+        let mut searcher = search::Searcher::new(search::Options::default());
+        let options = datagen::Options {
+            games: 2,
+            limits: search::Limits {
+                depth: Some(1),
+                ..Default::default()
+            },
+            random_plies: 4,
+            max_plies: 16,
+            seed: 5,
+        };
+        let mut output = Vec::new();
+        let mut games = 0;
+        let written = datagen::generate(&mut searcher, &options, &mut output, |game, _| {
+            games = game;
+        })
+        .unwrap();
+        assert!(games == 2);
+        let text = String::from_utf8(output).unwrap();
+        assert!(written > 0 && text.lines().count() == written);
+        let samples = tune::parse_samples(&text).unwrap();
+        assert!(samples.len() == written);
+        assert!(text.lines().all(|line| line.split(" | ").count() == 3));
+
+        // Games go on past a position's second time, and stop at its third
+        let mut position = position::start();
+        for round in 0..2 {
+            assert!(datagen::game_result(&mut position).is_none());
+            for mv in ["g1f3", "g8f6", "f3g1", "f6g8"] {
+                let mv = position.parse_move(mv).unwrap();
+                position.make_move(mv);
+            }
+            assert!(position.is_draw());
+            assert!(datagen::game_result(&mut position).is_none() == (round == 0));
+        }
+        assert!(datagen::game_result(&mut position) == Some(0.5));
+        let mut mated =
+            position::from_fen("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3")
+                .unwrap();
+        assert!(datagen::game_result(&mut mated) == Some(0.0));
+    }
+
+    #[test]
+    fn positions_draw_as_boards() {
+        let mut position = position::start();
+        let text = position.to_string();
+        assert!(text.lines().count() == 9);
+        assert!(text.starts_with("8  r  n  b  q  k  b  n  r"));
+        assert!(text.contains("2  P  P  P  P  P  P  P  P"));
+        assert!(text.ends_with("a  b  c  d  e  f  g  h\n"));
+        assert!(format!("{:#}", position).contains("1  ♖  ♘  ♗  ♕  ♔  ♗  ♘  ♖"));
+
+        // The last move is marked, and so is a king in check
+        for mv in ["e2e4", "f7f6", "d1h5"].iter() {
+            let mv = position.parse_move(mv).unwrap();
+            position.make_move(mv);
+        }
+        let text = position.to_string();
+        assert!(text.contains("5  .  .  .  .  .  .  . [Q]"));
+        assert!(text.contains("1  R  N  B [.] K  B  N  R"));
+        assert!(text.contains("8  r  n  b  q (k) b  n  r"));
+
+        let style = render::Style {
+            flipped: true,
+            colours: true,
+            glyphs: render::Glyphs::Unicode,
+            ..Default::default()
+        };
+        let text = render::Board::new(&position, style).to_string();
+        assert!(text.starts_with("1 ") && text.contains("\x1b[48;5;160m"));
+        assert!(text.trim_end().ends_with("h  g  f  e  d  c  b  a"));
+
+        let knight = piece::build(piece::Types::Knight, piece::Sides::White, ('G', 1), 3);
+        assert!(knight.to_string() == "Ng1");
+    }
+
+    #[test]
+    fn clocks_run_for_the_side_to_move() {
+        use std::time::{Duration, Instant};
+        let start = Instant::now();
+        let at = |millis| start + Duration::from_millis(millis);
+        let mut clock = clock::Clock::new(clock::TimeControl::fischer(
+            Duration::from_secs(1),
+            Duration::from_secs(1),
+        ));
+        clock.start(piece::Sides::White, start);
+        assert!(clock.remaining(piece::Sides::White, at(300)) == Duration::from_millis(700));
+        assert!(clock.remaining(piece::Sides::Black, at(300)) == Duration::from_secs(1));
+
+        // Moving stops the time and adds the increment
+        clock.press(at(400));
+        assert!(clock.running() == Some(piece::Sides::Black));
+        assert!(clock.remaining(piece::Sides::White, at(900)) == Duration::from_millis(1600));
+        assert!(clock.flagged(at(1300)).is_none());
+        assert!(clock.flagged(at(1400)) == Some(piece::Sides::Black));
+        assert!(clock::format(clock.remaining(piece::Sides::White, at(900))) == "0:01.6");
+        assert!(clock::format(Duration::from_secs(299)) == "4:59");
+        assert!(clock::format(Duration::from_secs(3723)) == "1:02:03");
+    }
+
+    #[test]
+    fn time_controls_delay_add_time_and_decide_flag_falls() {
+        use clock::{Clock, TimeControl, Timing};
+        use piece::Sides::{Black, White};
+        use std::time::{Duration, Instant};
+        let start = Instant::now();
+        let at = |millis| start + Duration::from_millis(millis);
+        let seconds = Duration::from_secs;
+
+        let control = TimeControl::parse("40/90+30, 30+30").unwrap();
+        assert!(control.stages.len() == 2 && control.stages[0].moves == Some(40));
+        assert!(control.stages[1].time == seconds(1800));
+        assert!(control.stages[1].timing == Timing::Increment(seconds(30)));
+        assert!(control.to_string() == "40/90+30,30+30");
+        assert!(TimeControl::parse("5d3").unwrap() == TimeControl::delay(seconds(300), seconds(3)));
+        assert!(
+            TimeControl::parse("3b2").unwrap() == TimeControl::bronstein(seconds(180), seconds(2))
+        );
+        assert!(TimeControl::parse("0/5").is_err() && TimeControl::parse("5+x").is_err());
+
+        // Times too big for the clock are errors, and sums that get there stop
+        for control in ["1e30+1", "5+1e300", "5+inf", "-5", "4e17"].iter() {
+            assert!(TimeControl::parse(control).is_err());
+        }
+        let mut clock = Clock::new(TimeControl::parse("3e16+1.8e19").unwrap());
+        clock.start(White, at(0));
+        clock.press(at(0));
+        assert!(clock.remaining(White, at(0)) == Duration::MAX);
+        assert!(search::time::allocate(Duration::MAX, Duration::MAX, None) > Duration::ZERO);
+
+        // A simple delay goes by before the time does, and nothing comes back
+        let mut clock = Clock::new(TimeControl::delay(seconds(10), seconds(2)));
+        clock.start(White, start);
+        assert!(clock.remaining(White, at(1500)) == seconds(10));
+        assert!(clock.remaining(White, at(3000)) == seconds(9));
+        clock.press(at(3000));
+        assert!(clock.remaining(White, at(5000)) == seconds(9));
+
+        // Bronstein gives back what the move took, up to the delay
+        let mut clock = Clock::new(TimeControl::bronstein(seconds(10), seconds(2)));
+        clock.start(White, start);
+        clock.press(at(1500));
+        assert!(clock.remaining(White, at(1500)) == seconds(10));
+        clock.press(at(2000));
+        clock.press(at(5000));
+        assert!(clock.remaining(White, at(5000)) == seconds(9));
+
+        // The next stage's time comes once the moves are made, and a last
+        // stage with moves repeats
+        let mut clock = Clock::new(TimeControl::parse("2/1,1/0.5").unwrap());
+        clock.start(White, start);
+        assert!(clock.moves_to_go(White) == Some(2));
+        for second in 1..=6 {
+            clock.press(at(second * 1000));
+        }
+        assert!(clock.moves_to_go(White) == Some(1));
+        assert!(clock.remaining(White, at(6000)) == seconds(60 - 3 + 30 + 30));
+        assert!(clock.remaining(Black, at(6000)) == seconds(60 - 3 + 30 + 30));
+
+        // Running out loses, unless the other side has nothing to mate with
+        let position = position::from_fen("8/8/4k3/8/8/8/3PK3/8 b - - 0 1").unwrap();
+        assert!(clock::flag_fall(&position, Black) == position::Ending::Timeout(White));
+        assert!(clock::flag_fall(&position, White) == position::Ending::TimeoutDraw);
+        let ending = clock::flag_fall(&position, Black);
+        assert!(ending.result() == "1-0" && ending.reason() == "Black loses on time");
+        // A knight mates with the king's own pieces in the way, and bishops
+        // on one colour need a blocker on the other
+        let knight = position::from_fen("8/8/4k3/8/8/3n4/4K3/8 w - - 0 1").unwrap();
+        assert!(!knight.can_mate(Black));
+        let knight = position::from_fen("8/8/4k3/8/8/3n4/3PK3/8 w - - 0 1").unwrap();
+        assert!(knight.can_mate(Black));
+        let bishops = position::from_fen("8/8/2b1k3/8/8/3b4/4K3/8 w - - 0 1").unwrap();
+        assert!(!bishops.can_mate(Black));
+        let bishops = position::from_fen("8/8/2b1k3/8/8/3b4/3BK3/8 w - - 0 1").unwrap();
+        assert!(bishops.can_mate(Black));
+    }
+
+    #[test]
+    fn the_full_screen_game_moves_pieces_with_keys_and_clicks() {
+        use std::time::{Duration, Instant};
+        use tui::terminal::{self, Event};
+
+        let events = terminal::events(b"\x1b[A\x1b[<0;12;5M\x1b[<0;12;5m\r q\x03\x1b");
+        assert!(
+            events
+                == vec![
+                    Event::Up,
+                    Event::Click(5, 12),
+                    Event::Enter,
+                    Event::Enter,
+                    Event::Char('q'),
+                    Event::Interrupt,
+                    Event::Escape
+                ]
+        );
+
+        let limits = search::Limits {
+            depth: Some(2),
+            ..Default::default()
+        };
+        let clock = clock::Clock::new(clock::TimeControl::fischer(
+            Duration::from_secs(300),
+            Duration::from_secs(2),
+        ));
+        let mut game = tui::Tui::new(
+            piece::Sides::White,
+            search::skill::Skill::full(),
+            limits,
+            Some(clock),
+        );
+        let now = Instant::now();
+
+        // The cursor starts on e2: pick the pawn up and put it down on e4
+        for event in [Event::Enter, Event::Up, Event::Up, Event::Enter].iter() {
+            assert!(game.event(*event, now));
+        }
+        assert!(game.moves() == ["e4"]);
+        let waited = Instant::now();
+        while game.moves().len() < 2 {
+            assert!(waited.elapsed() < Duration::from_secs(30));
+            game.update(Instant::now()).unwrap();
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        let screen = game.draw(Instant::now());
+        assert!(screen.contains("1. e4 ") && screen.contains("Evaluation"));
+        assert!(screen.contains("White (You)  5:0") && screen.contains("Your move"));
+
+        // g1 is on the bottom row, the seventh column, and f3 two rows up and
+        // one to the left
+        game.event(Event::Click(10, 21), now);
+        game.event(Event::Click(8, 18), now);
+        assert!(game.moves()[2] == "Nf3");
+        // Taking it back stops the engine thinking about its reply
+        game.update(Instant::now()).unwrap();
+        assert!(game.is_thinking());
+        assert!(game.event(Event::Char('u'), now));
+        assert!(game.moves().len() == 2 && !game.is_thinking());
+        // Only our own pieces get picked up
+        game.event(Event::Click(3, 3), now);
+        assert!(!game.draw(now).contains("\x1b[48;5;71m"));
+        assert!(!game.event(Event::Char('q'), now));
+    }
+
+    #[test]
+    fn chess960_positions_castle_from_anywhere() {
+        use position::chess960;
+        use std::collections::HashSet;
+
+        // All 960 are different, and 518 is the standard one
+        let ranks: HashSet<Vec<usize>> = (0..chess960::POSITIONS)
+            .map(|index| {
+                let rank = chess960::back_rank(index).unwrap();
+                rank.iter().map(|_type| _type.index()).collect()
+            })
+            .collect();
+        assert!(ranks.len() == 960);
+        assert!(chess960::start(518).unwrap().to_fen() == position::START_FEN);
+        assert!(chess960::start(0)
+            .unwrap()
+            .to_fen()
+            .starts_with("bbqnnrkr/"));
+        assert!(chess960::start(960).is_err());
+
+        // Known move counts, with the castling rights in Shredder-FEN
+        for (fen, expected) in [
+            (
+                "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+                [21, 528, 12189],
+            ),
+            (
+                "2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9",
+                [21, 807, 18002],
+            ),
+        ]
+        .iter()
+        {
+            let mut position = position::from_fen(fen).unwrap();
+            assert!(position.chess960 && position.to_shredder_fen() == *fen);
+            for (depth, count) in expected.iter().enumerate() {
+                assert!(position::movegen::perft(&mut position, depth as u32 + 1) == *count);
+            }
+        }
+
+        // The king takes its own rook and they land on g1 and f1
+        let fen = "r3k2r/8/8/8/8/8/8/1R2K1R1 w GBkq - 0 1";
+        let mut position = position::from_fen(fen).unwrap();
+        assert!(position.to_fen() == "r3k2r/8/8/8/8/8/8/1R2K1R1 w KQkq - 0 1");
+        let castle = position.parse_move("e1g1").unwrap();
+        assert!(position.is_castling(castle) && !position.is_capture(castle));
+        assert!(position.san(castle) == "O-O" && position.parse_san("O-O") == Some(castle));
+        assert!(position.parse_san("O-O-O").unwrap().to_string() == "e1b1");
+        let hash = position.hash;
+        position.make_move(castle);
+        assert!(position.to_fen() == "r3k2r/8/8/8/8/8/8/1R3RK1 b kq - 1 1");
+        position.unmake_move();
+        assert!(position.to_shredder_fen() == "r3k2r/8/8/8/8/8/8/1R2K1R1 w GBha - 0 1");
+        assert!(position.hash == hash);
+
+        // X-FEN names a rook by its file when it isn't the outermost one
+        let position = position::from_fen("4k3/8/8/8/8/8/8/R2RK3 w D - 0 1").unwrap();
+        assert!(position.to_fen() == "4k3/8/8/8/8/8/8/R2RK3 w D - 0 1");
+
+        // UCI_Chess960 writes standard castling the same way
+        let mut position = position::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        assert!(!position.chess960 && position.parse_move("e1g1").is_some());
+        position.chess960 = true;
+        assert!(position.parse_move("e1h1").is_some() && position.parse_move("e1g1").is_none());
+    }
+
+    #[test]
+    fn crazyhouse_captures_go_into_pockets_and_drop_back() {
+        use position::crazyhouse;
+
+        // Nothing can be dropped until something's been taken
+        let mut position = crazyhouse::start();
+        assert!(position.crazyhouse && position.to_fen() == crazyhouse::START_FEN);
+        for (depth, count) in [20, 400, 8902].iter().enumerate() {
+            assert!(position::movegen::perft(&mut position, depth as u32 + 1) == *count);
+        }
+
+        // Every kind of drop on every empty square, except pawns on the first
+        // and last ranks
+        let mut position = position::from_fen("2k5/8/8/8/8/8/8/4K3[QRBNPqrbnp] w - - 0 1").unwrap();
+        assert!(position.to_fen() == "2k5/8/8/8/8/8/8/4K3[QRBNPqrbnp] w - - 0 1");
+        assert!(position::movegen::perft(&mut position, 1) == 301);
+        assert!(position::movegen::perft(&mut position, 2) == 75353);
+        assert!(position.parse_move("P@e8").is_none() && position.parse_move("P@e1").is_none());
+        let drop = position.parse_move("n@f3").unwrap();
+        assert!(drop.to_string() == "N@f3" && position.san(drop) == "N@f3");
+        assert!(position.parse_san("@e4").unwrap().to_string() == "P@e4");
+        let hash = position.hash;
+        position.make_move(drop);
+        assert!(position.to_fen() == "2k5/8/8/8/8/5N2/8/4K3[QRBPqrbnp] b - - 1 1");
+        assert!(position.hash == position::from_fen(&position.to_fen()).unwrap().hash);
+        position.unmake_move();
+        assert!(
+            position.hash == hash
+                && position.in_hand(piece::Types::Knight, piece::Sides::White) == 1
+        );
+
+        // A promoted queen goes back to being a pawn once it's taken
+        let mut position = position::from_fen("3rk3/8/8/8/8/8/8/3Q~K3[] b - - 0 1").unwrap();
+        let fen = position.to_fen();
+        assert!(fen == "3rk3/8/8/8/8/8/8/3Q~K3[] b - - 0 1");
+        let take = position.parse_san("Rxd1+").unwrap();
+        position.make_move(take);
+        assert!(position.to_fen() == "4k3/8/8/8/8/8/8/3rK3[p] w - - 0 2");
+        let recapture = position.parse_san("Kxd1").unwrap();
+        position.make_move(recapture);
+        assert!(position.to_fen() == "4k3/8/8/8/8/8/8/3K4[Rp] b - - 0 2");
+        assert!(position.hash == position::from_fen(&position.to_fen()).unwrap().hash);
+        assert!(!position.insufficient_material());
+        position.unmake_move();
+        position.unmake_move();
+        assert!(position.to_fen() == fen);
+
+        // Promoting marks the piece, and a drop can block a check
+        let mut position = position::from_fen("4k3/P7/8/8/8/8/8/r3K3[B] w - - 0 1").unwrap();
+        let drops: Vec<String> = position
+            .legal_moves()
+            .iter()
+            .filter(|mv| mv.drop.is_some())
+            .map(|mv| mv.to_string())
+            .collect();
+        assert!(drops == ["B@b1", "B@c1", "B@d1"]);
+        let mut position = position::from_fen("4k3/P7/8/8/8/8/8/4K3[] w - - 0 1").unwrap();
+        let promote = position.parse_move("a7a8q").unwrap();
+        position.make_move(promote);
+        assert!(position.to_fen().starts_with("Q~3k3/"));
+
+        // Material in hand counts towards the evaluation
+        let without = position::from_fen("4k3/8/8/8/8/8/8/4K3[] w - - 0 1").unwrap();
+        let with = position::from_fen("4k3/8/8/8/8/8/8/4K3[N] w - - 0 1").unwrap();
+        let mut evaluator = eval::Evaluator::new();
+        assert!(evaluator.evaluate(&with) > evaluator.evaluate(&without) + 200);
+        let trace = evaluator.trace(&with);
+        assert!(trace.term("In hand").unwrap().total() == evaluator.params.hand[3]);
+        assert!(trace.evaluation() == evaluator.evaluate(&with));
+    }
 }
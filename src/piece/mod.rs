@@ -1,8 +1,19 @@
-#[path = "../board/mod.rs"]
-pub mod board;
-
+// The move generation here is the original, board walking one, kept as it
+// was written
+#![allow(
+    clippy::collapsible_match,
+    clippy::explicit_counter_loop,
+    clippy::int_plus_one,
+    clippy::needless_bool,
+    clippy::unnecessary_cast,
+    clippy::useless_conversion
+)]
+
+use crate::board;
+use crate::position::piece_char;
 use std::cmp::Ordering;
 use std::convert::TryInto;
+use std::fmt;
 
 #[derive(Debug)]
 pub struct Piece {
@@ -13,7 +24,7 @@ pub struct Piece {
     pub value: i32,
 }
 
-#[derive(Debug, PartialEq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum Types {
     Pawn,
     Rook,
@@ -23,12 +34,53 @@ pub enum Types {
     King,
 }
 
-#[derive(Debug, PartialEq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum Sides {
     White,
     Black,
 }
 
+impl Types {
+    pub const ALL: [Types; 6] = [
+        Types::Pawn,
+        Types::Rook,
+        Types::Bishop,
+        Types::Knight,
+        Types::Queen,
+        Types::King,
+    ];
+
+    pub fn index(self) -> usize {
+        self as usize
+    }
+}
+
+impl Sides {
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    pub fn opposite(self) -> Sides {
+        match self {
+            Sides::White => Sides::Black,
+            Sides::Black => Sides::White,
+        }
+    }
+}
+
+// The letter and where it stands, "Ng1" or "pe7"
+impl fmt::Display for Piece {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}{}{}",
+            piece_char(self._type, self.side),
+            self.location.0.to_ascii_lowercase(),
+            self.location.1
+        )
+    }
+}
+
 impl Piece {
     pub fn legal_moves(&self, pieces: &[Piece]) -> Vec<(char, u32, i32)> {
         let mut moves: Vec<(char, u32, i32)> = Vec::new();
@@ -747,8 +799,8 @@ pub fn generate_all() -> Vec<Piece> {
         (Types::Bishop, Sides::White, ('C', 1), 3),
         (Types::Queen, Sides::White, ('D', 1), 9),
         (Types::King, Sides::White, ('E', 1), 0),
-        (Types::Knight, Sides::White, ('F', 1), 3),
-        (Types::Bishop, Sides::White, ('G', 1), 3),
+        (Types::Bishop, Sides::White, ('F', 1), 3),
+        (Types::Knight, Sides::White, ('G', 1), 3),
         (Types::Rook, Sides::White, ('H', 1), 5),
         (Types::Pawn, Sides::White, ('A', 2), 1),
         (Types::Pawn, Sides::White, ('B', 2), 1),
@@ -764,8 +816,8 @@ pub fn generate_all() -> Vec<Piece> {
         (Types::Bishop, Sides::Black, ('C', 8), -3),
         (Types::Queen, Sides::Black, ('D', 8), -9),
         (Types::King, Sides::Black, ('E', 8), -0),
-        (Types::Knight, Sides::Black, ('F', 8), -3),
-        (Types::Bishop, Sides::Black, ('G', 8), -3),
+        (Types::Bishop, Sides::Black, ('F', 8), -3),
+        (Types::Knight, Sides::Black, ('G', 8), -3),
         (Types::Rook, Sides::Black, ('H', 8), -5),
         (Types::Pawn, Sides::Black, ('A', 7), -1),
         (Types::Pawn, Sides::Black, ('B', 7), -1),
@@ -0,0 +1,326 @@
+use crate::eval::personality::Personality;
+use crate::eval::Params;
+use crate::piece::Sides;
+use crate::position::{self, Move, Position};
+use crate::render;
+use crate::search::skill::{self, Skill};
+use crate::search::{self, Limits, Score, Searcher};
+use std::io::{self, BufRead, Write};
+use std::time::Duration;
+
+// Playing a game against the engine in a terminal. Moves go in as SAN or
+// coordinates, and everything else is a command; the engine answers each move
+// straight away.
+
+// How long the engine thinks about each move
+pub const THINKING_TIME: Duration = Duration::from_secs(1);
+
+// The engine takes a draw when it thinks it's at least this much worse
+const DRAW_ACCEPT: i32 = -25;
+
+const HELP: &str = "Moves can be in SAN (Nf3, exd5, O-O, e8=Q) or coordinates (g1f3).
+Commands:
+  undo          take back your last move and the engine's reply
+  hint          suggest a move
+  resign        give up the game
+  draw          offer a draw
+  flip          turn the board around
+  fen           show the position as FEN
+  board         show the board again
+  level <0-20>  change how well the engine plays
+  personality <balanced|aggressive|defensive|materialistic>
+                change the engine's style
+  new [white|black]
+                start a new game
+  quit          leave";
+
+pub struct Game {
+    position: Position,
+    searcher: Searcher,
+    limits: Limits,
+    // The side the person at the keyboard plays
+    human: Sides,
+    flipped: bool,
+    // The evaluation parameters the engine's personality leans on
+    params: Params,
+    personality: Personality,
+    // How the game ended, once it has
+    result: Option<String>,
+}
+
+impl Game {
+    pub fn new(human: Sides, skill: Skill, limits: Limits) -> Game {
+        let mut searcher = Searcher::new(search::Options::default());
+        searcher.set_skill(skill);
+        Game {
+            position: position::start(),
+            searcher,
+            limits,
+            human,
+            flipped: human == Sides::Black,
+            params: Params::default(),
+            personality: Personality::Balanced,
+            result: None,
+        }
+    }
+
+    // Evaluation parameters to play with, like the ones chess tune writes
+    pub fn set_params(&mut self, params: Params) {
+        self.params = params;
+        self.searcher
+            .set_params(self.params.with_personality(self.personality));
+    }
+
+    pub fn position(&self) -> &Position {
+        &self.position
+    }
+
+    pub fn result(&self) -> Option<&str> {
+        self.result.as_deref()
+    }
+
+    pub fn board(&self) -> String {
+        render::board(&self.position, self.flipped)
+    }
+
+    // What to ask for next
+    pub fn prompt(&self) -> String {
+        match self.result {
+            Some(_) => "game over> ".to_string(),
+            None => format!("{}> ", self.position.fullmove_number),
+        }
+    }
+
+    fn engine_to_move(&self) -> bool {
+        self.result.is_none() && self.position.side_to_move != self.human
+    }
+
+    fn check_for_ending(&mut self) -> Option<String> {
+        let ending = self.position.ending()?;
+        let result = format!("{} {{{}}}", ending.result(), ending.reason());
+        self.result = Some(result.clone());
+        Some(result)
+    }
+
+    // Lets the engine move if it's its turn, and says what it played
+    pub fn engine_move(&mut self) -> Option<String> {
+        if !self.engine_to_move() {
+            return None;
+        }
+        let result = self.searcher.search(&mut self.position, &self.limits);
+        let mv = result.best_move?;
+        let san = self.position.san(mv);
+        // Scores are shown for White, the way they usually are
+        let (dots, score) = match self.position.side_to_move {
+            Sides::White => ("", result.score),
+            Sides::Black => ("... ", -result.score),
+        };
+        let mut text = format!(
+            "{}. {}{} ({})\n",
+            self.position.fullmove_number,
+            dots,
+            san,
+            Score::from(score)
+        );
+        self.position.make_move(mv);
+        text.push_str(&self.board());
+        if let Some(ending) = self.check_for_ending() {
+            text.push_str(&ending);
+            text.push('\n');
+        }
+        Some(text)
+    }
+
+    fn parse_move(&mut self, text: &str) -> Option<Move> {
+        self.position
+            .parse_move(text)
+            .or_else(|| self.position.parse_san(text))
+    }
+
+    fn human_move(&mut self, mv: Move) -> String {
+        self.position.make_move(mv);
+        let mut text = String::new();
+        if let Some(ending) = self.check_for_ending() {
+            text.push_str(&self.board());
+            text.push_str(&ending);
+            text.push('\n');
+        } else if let Some(reply) = self.engine_move() {
+            text.push_str(&reply);
+        }
+        text
+    }
+
+    // Takes back moves until it's the human's turn again, at least one of
+    // theirs included
+    fn undo(&mut self) -> Result<String, String> {
+        let mut position = self.position.clone();
+        let mut taken = 0;
+        while !position.history.is_empty() && (taken == 0 || position.side_to_move != self.human) {
+            position.unmake_move();
+            taken += 1;
+        }
+        if taken == 0 || position.side_to_move != self.human {
+            return Err("there's no move of yours to take back".to_string());
+        }
+        self.position = position;
+        self.result = None;
+        Ok(self.board())
+    }
+
+    fn hint(&mut self) -> Result<String, String> {
+        if self.result.is_some() {
+            return Err("the game is over".to_string());
+        }
+        let skill = self.searcher.skill();
+        self.searcher.set_skill(Skill::full());
+        let result = self.searcher.search(&mut self.position, &self.limits);
+        self.searcher.set_skill(skill);
+        match result.best_move {
+            Some(mv) => Ok(format!("Try {}", self.position.san(mv))),
+            None => Err("there's nothing to play".to_string()),
+        }
+    }
+
+    fn draw(&mut self) -> Result<String, String> {
+        if self.result.is_some() {
+            return Err("the game is over".to_string());
+        }
+        // The engine looks at it from its own side, whoever is to move
+        let mut score = self.searcher.search(&mut self.position, &self.limits).score;
+        if self.position.side_to_move == self.human {
+            score = -score;
+        }
+        if score <= DRAW_ACCEPT {
+            self.result = Some("1/2-1/2 {Draw agreed}".to_string());
+            Ok("Draw accepted. 1/2-1/2".to_string())
+        } else {
+            Ok("Draw declined.".to_string())
+        }
+    }
+
+    fn resign(&mut self) -> Result<String, String> {
+        if self.result.is_some() {
+            return Err("the game is over".to_string());
+        }
+        let result = match self.human {
+            Sides::White => "0-1",
+            Sides::Black => "1-0",
+        };
+        self.result = Some(format!("{} {{You resign}}", result));
+        Ok(format!("You resign. {}", result))
+    }
+
+    fn new_game(&mut self, colour: Option<&str>) -> Result<String, String> {
+        if let Some(colour) = colour {
+            self.human = parse_colour(colour)?;
+        }
+        self.position = position::start();
+        self.searcher.clear();
+        self.result = None;
+        self.flipped = self.human == Sides::Black;
+        let mut text = self.board();
+        if let Some(reply) = self.engine_move() {
+            text = reply;
+        }
+        Ok(text)
+    }
+
+    // Carries out a line of input: a move or a command. What comes back is
+    // what to show, or what was wrong with it.
+    pub fn input(&mut self, line: &str) -> Result<String, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let command = match words.first() {
+            Some(command) => *command,
+            None => return Ok(String::new()),
+        };
+        match command {
+            "help" | "?" => Ok(HELP.to_string()),
+            "undo" => self.undo(),
+            "hint" => self.hint(),
+            "resign" => self.resign(),
+            "draw" => self.draw(),
+            "flip" => {
+                self.flipped = !self.flipped;
+                Ok(self.board())
+            }
+            "fen" => Ok(self.position.to_fen()),
+            "board" => Ok(self.board()),
+            "level" => {
+                let level = words
+                    .get(1)
+                    .and_then(|level| level.parse::<u32>().ok())
+                    .filter(|level| *level <= skill::MAX_LEVEL)
+                    .ok_or(format!("expected a level from 0 to {}", skill::MAX_LEVEL))?;
+                self.searcher.set_skill(Skill::level(level));
+                Ok(format!("The engine now plays at level {}", level))
+            }
+            "personality" => {
+                self.personality = words
+                    .get(1)
+                    .and_then(|name| Personality::from_name(name))
+                    .ok_or("expected balanced, aggressive, defensive or materialistic")?;
+                self.searcher
+                    .set_params(self.params.with_personality(self.personality));
+                Ok(format!(
+                    "The engine now plays a {} game",
+                    self.personality.name()
+                ))
+            }
+            "new" => self.new_game(words.get(1).copied()),
+            _ => {
+                if self.result.is_some() {
+                    return Err("the game is over; try undo or new".to_string());
+                }
+                match self.parse_move(command) {
+                    Some(mv) => Ok(self.human_move(mv)),
+                    None => Err(format!(
+                        "{} isn't a legal move or a command (try help)",
+                        command
+                    )),
+                }
+            }
+        }
+    }
+}
+
+pub fn parse_colour(text: &str) -> Result<Sides, String> {
+    match text.to_ascii_lowercase().as_str() {
+        "white" | "w" => Ok(Sides::White),
+        "black" | "b" => Ok(Sides::Black),
+        _ => Err(format!("expected white or black, not {}", text)),
+    }
+}
+
+// A game on standard input and output until the player quits
+pub fn run(human: Sides, skill: Skill, params: Params) -> Result<(), String> {
+    let limits = Limits {
+        time: Some(THINKING_TIME),
+        ..Default::default()
+    };
+    let mut game = Game::new(human, skill, limits);
+    game.set_params(params);
+    println!("You play {:?}. Type help for the commands.", human);
+    match game.engine_move() {
+        Some(reply) => print!("{}", reply),
+        None => print!("{}", game.board()),
+    }
+
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    loop {
+        print!("{}", game.prompt());
+        io::stdout().flush().map_err(|error| error.to_string())?;
+        let line = match lines.next() {
+            Some(line) => line.map_err(|error| error.to_string())?,
+            None => return Ok(()),
+        };
+        if matches!(line.trim(), "quit" | "exit") {
+            return Ok(());
+        }
+        match game.input(&line) {
+            Ok(text) if text.is_empty() => {}
+            Ok(text) => println!("{}", text.trim_end()),
+            Err(error) => println!("{}", error),
+        }
+    }
+}
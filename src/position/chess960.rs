@@ -0,0 +1,79 @@
+use crate::piece::{Sides, Types};
+use crate::position::{self, piece_char, Position};
+
+// Chess960 starting positions, numbered 0 to 959 the usual way (Scharnagl's):
+// the index picks the light-squared bishop's file, then the dark-squared
+// bishop's, then the queen's and the knights' among the squares left, and
+// the rooks go either side of the king on the last three. 518 is the
+// standard position.
+
+pub const POSITIONS: usize = 960;
+
+// Where the two knights go among the five squares left after the bishops and
+// the queen
+const KNIGHTS: [(usize, usize); 10] = [
+    (0, 1),
+    (0, 2),
+    (0, 3),
+    (0, 4),
+    (1, 2),
+    (1, 3),
+    (1, 4),
+    (2, 3),
+    (2, 4),
+    (3, 4),
+];
+
+// White's back rank from a1 to h1, and Black's mirrors it
+pub fn back_rank(index: usize) -> Option<[Types; 8]> {
+    if index >= POSITIONS {
+        return None;
+    }
+    let mut rank = [None; 8];
+    let mut index = index;
+    rank[index % 4 * 2 + 1] = Some(Types::Bishop);
+    index /= 4;
+    rank[index % 4 * 2] = Some(Types::Bishop);
+    index /= 4;
+
+    let empty = |rank: &[Option<Types>; 8]| -> Vec<usize> {
+        (0..8).filter(|file| rank[*file].is_none()).collect()
+    };
+    rank[empty(&rank)[index % 6]] = Some(Types::Queen);
+    index /= 6;
+    let (first, second) = KNIGHTS[index];
+    let left = empty(&rank);
+    rank[left[first]] = Some(Types::Knight);
+    rank[left[second]] = Some(Types::Knight);
+    let left = empty(&rank);
+    rank[left[0]] = Some(Types::Rook);
+    rank[left[1]] = Some(Types::King);
+    rank[left[2]] = Some(Types::Rook);
+
+    let mut pieces = [Types::Pawn; 8];
+    for (piece, square) in pieces.iter_mut().zip(rank.iter()) {
+        *piece = square.expect("every square gets a piece");
+    }
+    Some(pieces)
+}
+
+// The starting position with that index, playing by Chess960's rules
+pub fn start(index: usize) -> Result<Position, String> {
+    let rank = back_rank(index).ok_or(format!(
+        "Chess960 positions go from 0 to {}, not {}",
+        POSITIONS - 1,
+        index
+    ))?;
+    let white: String = rank
+        .iter()
+        .map(|_type| piece_char(*_type, Sides::White))
+        .collect();
+    let fen = format!(
+        "{}/pppppppp/8/8/8/8/PPPPPPPP/{} w KQkq - 0 1",
+        white.to_ascii_lowercase(),
+        white
+    );
+    let mut position = position::from_fen(&fen)?;
+    position.chess960 = true;
+    Ok(position)
+}
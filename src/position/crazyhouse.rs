@@ -0,0 +1,117 @@
+use crate::bitboard;
+use crate::piece::{Sides, Types};
+use crate::position::{self, parse_piece_char, piece_char, zobrist, Move, Position};
+
+// Crazyhouse: a piece that's taken goes into the pocket of the side that took
+// it, changing colour, and instead of moving a side can drop anything from its
+// pocket onto any empty square. Pawns can't go on the first or last rank, and
+// a promoted piece goes back to being a pawn when it's taken.
+
+pub const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR[] w KQkq - 0 1";
+
+// More than there are of any kind, promoted pawns included
+pub const MAX_IN_HAND: u8 = 16;
+
+// The order pockets are written in FEN
+const POCKET_ORDER: [Types; 5] = [
+    Types::Queen,
+    Types::Rook,
+    Types::Bishop,
+    Types::Knight,
+    Types::Pawn,
+];
+
+impl Position {
+    pub fn in_hand(&self, _type: Types, side: Sides) -> u8 {
+        self.pockets[side.index()][_type.index()]
+    }
+
+    pub fn has_pockets(&self) -> bool {
+        self.pockets.iter().flatten().any(|count| *count > 0)
+    }
+
+    pub(super) fn add_to_pocket(&mut self, side: Sides, _type: Types) {
+        let count = &mut self.pockets[side.index()][_type.index()];
+        *count += 1;
+        self.hash ^= zobrist::POCKETS[side.index()][_type.index()][*count as usize];
+    }
+
+    pub(super) fn take_from_pocket(&mut self, side: Sides, _type: Types) {
+        let count = &mut self.pockets[side.index()][_type.index()];
+        self.hash ^= zobrist::POCKETS[side.index()][_type.index()][*count as usize];
+        *count -= 1;
+    }
+
+    // After a move on the board: the piece it took goes in our pocket, and
+    // whether the piece that moved was promoted goes with it
+    pub(super) fn pocket_capture(&mut self, mv: Move, captured: Option<Types>) {
+        let from = bitboard::bit(mv.from);
+        let to = bitboard::bit(mv.to);
+        if let Some(captured) = captured {
+            let _type = if self.promoted & to != 0 {
+                Types::Pawn
+            } else {
+                captured
+            };
+            self.add_to_pocket(self.side_to_move, _type);
+        }
+        let promoted = self.promoted & from != 0 || mv.promotion.is_some();
+        self.promoted &= !(from | to);
+        if promoted {
+            self.promoted |= to;
+        }
+    }
+
+    // Everything in our pocket onto every empty square it can go on
+    pub(super) fn drop_moves(&self, moves: &mut Vec<Move>) {
+        let us = self.side_to_move;
+        let empty = !self.all();
+        for _type in Types::ALL.iter() {
+            if self.in_hand(*_type, us) == 0 {
+                continue;
+            }
+            let targets = if *_type == Types::Pawn {
+                empty & !bitboard::RANK_1 & !bitboard::rank_mask(7)
+            } else {
+                empty
+            };
+            for to in bitboard::squares(targets) {
+                moves.push(Move::drop_piece(*_type, to));
+            }
+        }
+    }
+
+    // "[QRbn]", White's then Black's, biggest first
+    pub(super) fn pocket_field(&self) -> String {
+        let mut field = "[".to_string();
+        for side in [Sides::White, Sides::Black].iter() {
+            for _type in POCKET_ORDER.iter() {
+                for _ in 0..self.in_hand(*_type, *side) {
+                    field.push(piece_char(*_type, *side));
+                }
+            }
+        }
+        field.push(']');
+        field
+    }
+
+    // From what's between the brackets, which also makes it a crazyhouse
+    // position
+    pub(super) fn set_pockets(&mut self, pocket: &str) -> Result<(), String> {
+        self.crazyhouse = true;
+        for letter in pocket.chars() {
+            let (_type, side) = parse_piece_char(letter)
+                .filter(|(_type, _)| *_type != Types::King)
+                .ok_or(format!("bad piece in pocket: {}", letter))?;
+            if self.in_hand(_type, side) == MAX_IN_HAND {
+                return Err(format!("too many {} in pocket", letter));
+            }
+            self.add_to_pocket(side, _type);
+        }
+        Ok(())
+    }
+}
+
+pub fn start() -> Position {
+    position::from_fen(START_FEN).expect("the crazyhouse start position is valid")
+}
@@ -0,0 +1,966 @@
+pub mod chess960;
+pub mod crazyhouse;
+pub mod movegen;
+pub mod san;
+pub mod zobrist;
+
+use crate::bitboard;
+use crate::board;
+use crate::eval::nnue::{Accumulators, Network};
+use crate::piece::{self, Piece, Sides, Types};
+use std::fmt;
+use std::sync::Arc;
+
+pub const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+pub const WHITE_KINGSIDE: u8 = 1;
+pub const WHITE_QUEENSIDE: u8 = 2;
+pub const BLACK_KINGSIDE: u8 = 4;
+pub const BLACK_QUEENSIDE: u8 = 8;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Move {
+    pub from: usize,
+    pub to: usize,
+    pub promotion: Option<Types>,
+    // A piece put down from the pocket in crazyhouse, in which case `from` is
+    // the same square as `to`
+    pub drop: Option<Types>,
+}
+
+impl Move {
+    pub fn new(from: usize, to: usize) -> Move {
+        Move {
+            from,
+            to,
+            promotion: None,
+            drop: None,
+        }
+    }
+
+    pub fn drop_piece(_type: Types, to: usize) -> Move {
+        Move {
+            from: to,
+            to,
+            promotion: None,
+            drop: Some(_type),
+        }
+    }
+}
+
+// Coordinate notation, the way UCI wants it: "e2e4", "e7e8q", and "N@f3" for
+// a drop
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(_type) = self.drop {
+            return write!(
+                f,
+                "{}@{}",
+                piece_char(_type, Sides::White),
+                board::square_name(self.to)
+            );
+        }
+        write!(
+            f,
+            "{}{}",
+            board::square_name(self.from),
+            board::square_name(self.to)
+        )?;
+        if let Some(promotion) = self.promotion {
+            write!(f, "{}", piece_char(promotion, Sides::Black))?;
+        }
+        Ok(())
+    }
+}
+
+// The ways a game can end on the board
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ending {
+    // With the side that gave mate
+    Checkmate(Sides),
+    Stalemate,
+    InsufficientMaterial,
+    FiftyMoves,
+    Repetition,
+    // With the side that won on time
+    Timeout(Sides),
+    // Out of time, but the other side couldn't have mated
+    TimeoutDraw,
+}
+
+impl Ending {
+    pub fn winner(self) -> Option<Sides> {
+        match self {
+            Ending::Checkmate(side) | Ending::Timeout(side) => Some(side),
+            _ => None,
+        }
+    }
+
+    // As PGN writes it
+    pub fn result(self) -> &'static str {
+        match self.winner() {
+            Some(Sides::White) => "1-0",
+            Some(Sides::Black) => "0-1",
+            None => "1/2-1/2",
+        }
+    }
+
+    pub fn reason(self) -> &'static str {
+        match self {
+            Ending::Checkmate(Sides::White) => "White mates",
+            Ending::Checkmate(Sides::Black) => "Black mates",
+            Ending::Stalemate => "Stalemate",
+            Ending::InsufficientMaterial => "Insufficient material",
+            Ending::FiftyMoves => "Fifty move rule",
+            Ending::Repetition => "Threefold repetition",
+            Ending::Timeout(Sides::White) => "Black loses on time",
+            Ending::Timeout(Sides::Black) => "White loses on time",
+            Ending::TimeoutDraw => "Time forfeit against insufficient material",
+        }
+    }
+}
+
+// Everything make_move throws away that unmake_move needs to put back
+#[derive(Debug, Clone, Copy)]
+pub struct State {
+    // None for a null move
+    pub mv: Option<Move>,
+    pub captured: Option<Types>,
+    pub castling: u8,
+    pub en_passant: Option<usize>,
+    pub halfmove_clock: u32,
+    pub hash: u64,
+    pub pawn_key: u64,
+    // The move was castling, which unmake_move can't tell from the board
+    pub castled: bool,
+    pub pockets: [[u8; 6]; 2],
+    pub promoted: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Position {
+    pub squares: [Option<(Types, Sides)>; 64],
+    pub bitboards: [[u64; 6]; 2],
+    pub side_to_move: Sides,
+    pub castling: u8,
+    // Only set when a pawn could actually take en passant, so that positions
+    // that can't be told apart hash the same
+    pub en_passant: Option<usize>,
+    pub halfmove_clock: u32,
+    pub fullmove_number: u32,
+    pub hash: u64,
+    // Hash of just the pawns, for the evaluation's pawn structure cache
+    pub pawn_key: u64,
+    pub history: Vec<State>,
+    // Chess960 castling: the king takes its own rook in coordinate notation,
+    // and FEN names the rooks when it has to
+    pub chess960: bool,
+    // Where the rook for each castling right starts, in the order of the
+    // rights' bits
+    pub castling_rooks: [usize; 4],
+    // Castling rights that go away when anything moves from or to a square
+    castling_lost: [u8; 64],
+    // Crazyhouse: whatever a side takes goes into its pocket, to be dropped
+    // back on the board as its own later
+    pub crazyhouse: bool,
+    // Pieces in hand, by side and type
+    pub pockets: [[u8; 6]; 2],
+    // Pieces that got where they are by promoting, which go back to being
+    // pawns when they're taken
+    pub promoted: u64,
+    // The network evaluation's hidden layers, kept up to date move by move
+    // once a network has been set
+    pub nnue: Option<Box<Accumulators>>,
+}
+
+impl Position {
+    pub fn pieces(&self, _type: Types, side: Sides) -> u64 {
+        self.bitboards[side.index()][_type.index()]
+    }
+
+    pub fn occupancy(&self, side: Sides) -> u64 {
+        self.bitboards[side.index()]
+            .iter()
+            .fold(0, |all, bb| all | bb)
+    }
+
+    pub fn all(&self) -> u64 {
+        self.occupancy(Sides::White) | self.occupancy(Sides::Black)
+    }
+
+    pub fn king_square(&self, side: Sides) -> usize {
+        self.pieces(Types::King, side).trailing_zeros() as usize
+    }
+
+    // Start keeping accumulators for `network`, or stop with None
+    pub fn set_network(&mut self, network: Option<Arc<Network>>) {
+        self.nnue = network.map(|network| Box::new(Accumulators::new(network, self)));
+    }
+
+    fn put(&mut self, square: usize, _type: Types, side: Sides) {
+        self.squares[square] = Some((_type, side));
+        self.toggle(square, _type, side);
+    }
+
+    fn remove(&mut self, square: usize) -> Option<(Types, Sides)> {
+        let contents = self.squares[square].take();
+        if let Some((_type, side)) = contents {
+            self.toggle(square, _type, side);
+        }
+        contents
+    }
+
+    fn toggle(&mut self, square: usize, _type: Types, side: Sides) {
+        let key = zobrist::PIECES[side.index()][_type.index()][square];
+        self.bitboards[side.index()][_type.index()] ^= bitboard::bit(square);
+        self.hash ^= key;
+        if _type == Types::Pawn {
+            self.pawn_key ^= key;
+        }
+        if let Some(nnue) = &mut self.nnue {
+            let added = self.bitboards[side.index()][_type.index()] & bitboard::bit(square) != 0;
+            nnue.update(square, _type, side, added);
+        }
+    }
+
+    pub fn piece_at(&self, square: usize) -> Option<(Types, Sides)> {
+        self.squares[square]
+    }
+
+    // Every occupied square along with what's sitting on it
+    pub fn occupied(&self) -> Vec<(usize, Types, Sides)> {
+        let mut occupied = Vec::new();
+        for (square, contents) in self.squares.iter().enumerate() {
+            if let Some((_type, side)) = contents {
+                occupied.push((square, *_type, *side));
+            }
+        }
+        occupied
+    }
+
+    // Pieces of either side that attack `square`, given the board was
+    // occupied like `occupied`
+    pub fn attackers_to(&self, square: usize, occupied: u64) -> u64 {
+        let mut attackers = 0;
+        for side in [Sides::White, Sides::Black].iter() {
+            let side = *side;
+            let queens = self.pieces(Types::Queen, side);
+            attackers |=
+                bitboard::pawn_attacks(side.opposite(), square) & self.pieces(Types::Pawn, side);
+            attackers |= bitboard::knight_attacks(square) & self.pieces(Types::Knight, side);
+            attackers |= bitboard::king_attacks(square) & self.pieces(Types::King, side);
+            attackers |= bitboard::bishop_attacks(square, occupied)
+                & (self.pieces(Types::Bishop, side) | queens);
+            attackers |= bitboard::rook_attacks(square, occupied)
+                & (self.pieces(Types::Rook, side) | queens);
+        }
+        attackers
+    }
+
+    pub fn is_attacked(&self, square: usize, by: Sides) -> bool {
+        self.attackers_to(square, self.all()) & self.occupancy(by) != 0
+    }
+
+    pub fn in_check(&self) -> bool {
+        let us = self.side_to_move;
+        self.pieces(Types::King, us) != 0 && self.is_attacked(self.king_square(us), us.opposite())
+    }
+
+    // Bishops, rooks or queens, which is what a side needs to not be worried
+    // about zugzwang
+    pub fn has_non_pawn_material(&self, side: Sides) -> bool {
+        [Types::Rook, Types::Bishop, Types::Knight, Types::Queen]
+            .iter()
+            .any(|_type| self.pieces(*_type, side) != 0)
+    }
+
+    // Castling is the king going two squares in standard chess, and the king
+    // taking its own rook in Chess960, where it might not move at all
+    pub fn is_castling(&self, mv: Move) -> bool {
+        match self.squares[mv.from] {
+            Some((Types::King, side)) if self.chess960 => {
+                self.squares[mv.to] == Some((Types::Rook, side))
+            }
+            Some((Types::King, _)) => (mv.to as i32 - mv.from as i32).abs() == 2,
+            _ => false,
+        }
+    }
+
+    // Where the rook starts, and where the king and rook end up, for castling
+    // one way or the other. They land where they would in standard chess
+    // whatever squares they started on.
+    pub fn castling_squares(&self, side: Sides, kingside: bool) -> (usize, usize, usize) {
+        let rook = self.castling_rooks[castling_right(side, kingside).trailing_zeros() as usize];
+        let back = match side {
+            Sides::White => 0,
+            Sides::Black => 56,
+        };
+        if kingside {
+            (rook, back + 6, back + 5)
+        } else {
+            (rook, back + 2, back + 3)
+        }
+    }
+
+    // Sets which rooks the castling rights are for, from where they stand
+    // now, and which squares lose them
+    fn set_castling_rooks(&mut self, rooks: [usize; 4]) {
+        self.castling_rooks = rooks;
+        self.castling_lost = [0; 64];
+        for (index, rook) in rooks.iter().enumerate() {
+            let right = 1 << index;
+            if self.castling & right != 0 {
+                let side = if index < 2 {
+                    Sides::White
+                } else {
+                    Sides::Black
+                };
+                self.castling_lost[*rook] |= right;
+                self.castling_lost[self.king_square(side)] |= right;
+            }
+        }
+    }
+
+    pub fn make_move(&mut self, mv: Move) {
+        let us = self.side_to_move;
+        let them = us.opposite();
+        let moving = match mv.drop {
+            Some(dropped) => dropped,
+            None => {
+                let (moving, _) = self.squares[mv.from].expect("no piece on the from square");
+                moving
+            }
+        };
+        let castled = self.is_castling(mv);
+
+        self.history.push(State {
+            mv: Some(mv),
+            captured: None,
+            castling: self.castling,
+            en_passant: self.en_passant,
+            halfmove_clock: self.halfmove_clock,
+            hash: self.hash,
+            pawn_key: self.pawn_key,
+            castled,
+            pockets: self.pockets,
+            promoted: self.promoted,
+        });
+
+        let mut captured = None;
+        if let Some(dropped) = mv.drop {
+            self.take_from_pocket(us, dropped);
+            self.put(mv.to, dropped, us);
+        } else if castled {
+            let (rook_from, king_to, rook_to) = self.castling_squares(us, mv.to > mv.from);
+            self.remove(mv.from);
+            self.remove(rook_from);
+            self.put(king_to, Types::King, us);
+            self.put(rook_to, Types::Rook, us);
+        } else {
+            captured = self.remove(mv.to).map(|(_type, _)| _type);
+            if moving == Types::Pawn && Some(mv.to) == self.en_passant {
+                let behind = match us {
+                    Sides::White => mv.to - 8,
+                    Sides::Black => mv.to + 8,
+                };
+                captured = self.remove(behind).map(|(_type, _)| _type);
+            }
+            self.remove(mv.from);
+            self.put(mv.to, mv.promotion.unwrap_or(moving), us);
+            if self.crazyhouse {
+                self.pocket_capture(mv, captured);
+            }
+        }
+
+        self.hash ^= zobrist::CASTLING[self.castling as usize];
+        self.castling &= !(self.castling_lost[mv.from] | self.castling_lost[mv.to]);
+        self.hash ^= zobrist::CASTLING[self.castling as usize];
+
+        self.set_en_passant(None);
+        if moving == Types::Pawn && (mv.to as i32 - mv.from as i32).abs() == 16 {
+            let passed = (mv.from + mv.to) / 2;
+            let pawns = self.pieces(Types::Pawn, them);
+            if bitboard::pawn_attacks(us, passed) & pawns != 0 {
+                self.set_en_passant(Some(passed));
+            }
+        }
+
+        if moving == Types::Pawn || captured.is_some() {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock += 1;
+        }
+        if us == Sides::Black {
+            self.fullmove_number += 1;
+        }
+
+        self.side_to_move = them;
+        self.hash ^= zobrist::SIDE;
+        self.history.last_mut().unwrap().captured = captured;
+    }
+
+    pub fn unmake_move(&mut self) {
+        let state = self.history.pop().expect("no move to take back");
+        let mv = state.mv.expect("last move was a null move");
+        let them = self.side_to_move;
+        let us = them.opposite();
+
+        self.side_to_move = us;
+        if us == Sides::Black {
+            self.fullmove_number -= 1;
+        }
+
+        if mv.drop.is_some() {
+            self.remove(mv.to);
+        } else if state.castled {
+            let (rook_from, king_to, rook_to) = self.castling_squares(us, mv.to > mv.from);
+            self.remove(king_to);
+            self.remove(rook_to);
+            self.put(mv.from, Types::King, us);
+            self.put(rook_from, Types::Rook, us);
+        } else {
+            let (moved, _) = self.remove(mv.to).unwrap();
+            let moving = if mv.promotion.is_some() {
+                Types::Pawn
+            } else {
+                moved
+            };
+            self.put(mv.from, moving, us);
+        }
+
+        if let Some(captured) = state.captured {
+            let moving = self.squares[mv.from].map(|(_type, _)| _type);
+            let square = if moving == Some(Types::Pawn) && Some(mv.to) == state.en_passant {
+                match us {
+                    Sides::White => mv.to - 8,
+                    Sides::Black => mv.to + 8,
+                }
+            } else {
+                mv.to
+            };
+            self.put(square, captured, them);
+        }
+
+        self.castling = state.castling;
+        self.en_passant = state.en_passant;
+        self.halfmove_clock = state.halfmove_clock;
+        self.hash = state.hash;
+        self.pawn_key = state.pawn_key;
+        self.pockets = state.pockets;
+        self.promoted = state.promoted;
+    }
+
+    // Pass the move to the other side, for null move pruning
+    pub fn make_null(&mut self) {
+        self.history.push(State {
+            mv: None,
+            captured: None,
+            castling: self.castling,
+            en_passant: self.en_passant,
+            halfmove_clock: self.halfmove_clock,
+            hash: self.hash,
+            pawn_key: self.pawn_key,
+            castled: false,
+            pockets: self.pockets,
+            promoted: self.promoted,
+        });
+        self.set_en_passant(None);
+        self.halfmove_clock += 1;
+        self.side_to_move = self.side_to_move.opposite();
+        self.hash ^= zobrist::SIDE;
+    }
+
+    pub fn unmake_null(&mut self) {
+        let state = self.history.pop().expect("no move to take back");
+        self.side_to_move = self.side_to_move.opposite();
+        self.en_passant = state.en_passant;
+        self.halfmove_clock = state.halfmove_clock;
+        self.hash = state.hash;
+    }
+
+    fn set_en_passant(&mut self, square: Option<usize>) {
+        if let Some(old) = self.en_passant {
+            self.hash ^= zobrist::EN_PASSANT[bitboard::file_of(old)];
+        }
+        if let Some(new) = square {
+            self.hash ^= zobrist::EN_PASSANT[bitboard::file_of(new)];
+        }
+        self.en_passant = square;
+    }
+
+    // Has this position come up before since the last pawn move or capture?
+    pub fn is_repetition(&self) -> bool {
+        let reversible = (self.halfmove_clock as usize).min(self.history.len());
+        self.history
+            .iter()
+            .rev()
+            .take(reversible)
+            .skip(1)
+            .step_by(2)
+            .any(|state| state.hash == self.hash)
+    }
+
+    // How many times this position came up before, since the last pawn move
+    // or capture
+    pub fn repetitions(&self) -> usize {
+        let reversible = (self.halfmove_clock as usize).min(self.history.len());
+        self.history
+            .iter()
+            .rev()
+            .take(reversible)
+            .skip(1)
+            .step_by(2)
+            .filter(|state| state.hash == self.hash)
+            .count()
+    }
+
+    // Whether the game is over by the rules, and how. Draws by repetition
+    // need the position a third time here, unlike `is_draw`.
+    pub fn ending(&mut self) -> Option<Ending> {
+        if self.legal_moves().is_empty() {
+            return Some(if self.in_check() {
+                Ending::Checkmate(self.side_to_move.opposite())
+            } else {
+                Ending::Stalemate
+            });
+        }
+        if self.insufficient_material() {
+            Some(Ending::InsufficientMaterial)
+        } else if self.halfmove_clock >= 100 {
+            Some(Ending::FiftyMoves)
+        } else if self.repetitions() >= 2 {
+            Some(Ending::Repetition)
+        } else {
+            None
+        }
+    }
+
+    // Neither side has enough left to ever give mate. Anything in a pocket
+    // could still come back in crazyhouse.
+    pub fn insufficient_material(&self) -> bool {
+        if self.has_pockets() {
+            return false;
+        }
+        let heavy = [Types::Pawn, Types::Rook, Types::Queen];
+        if [Sides::White, Sides::Black]
+            .iter()
+            .any(|side| heavy.iter().any(|_type| self.pieces(*_type, *side) != 0))
+        {
+            return false;
+        }
+        let minors = [Sides::White, Sides::Black].iter().fold(0, |count, side| {
+            count
+                + (self.pieces(Types::Bishop, *side) | self.pieces(Types::Knight, *side))
+                    .count_ones()
+        });
+        minors <= 1
+    }
+
+    // Whether `side` could give mate by some series of legal moves, however
+    // unlikely, which is what decides whether running out of time against it
+    // loses. A lone minor piece needs something of the other side's to hem
+    // the king in, and bishops on one colour need it on the other colour.
+    pub fn can_mate(&self, side: Sides) -> bool {
+        const LIGHT_SQUARES: u64 = 0x55aa_55aa_55aa_55aa;
+        let them = side.opposite();
+        if self.pockets[side.index()].iter().any(|count| *count > 0)
+            || [Types::Pawn, Types::Rook, Types::Queen]
+                .iter()
+                .any(|_type| self.pieces(*_type, side) != 0)
+        {
+            return true;
+        }
+        let knights = self.pieces(Types::Knight, side);
+        let bishops = self.pieces(Types::Bishop, side);
+        let blockers = self.occupancy(them) & !self.pieces(Types::King, them);
+        match knights.count_ones() {
+            0 if bishops == 0 => false,
+            0 => {
+                let colour = if bishops & LIGHT_SQUARES != 0 {
+                    LIGHT_SQUARES
+                } else {
+                    !LIGHT_SQUARES
+                };
+                let their_bishops = self.pieces(Types::Bishop, them);
+                bishops & !colour != 0 || blockers & !(their_bishops & colour) != 0
+            }
+            1 if bishops == 0 => blockers != 0,
+            _ => true,
+        }
+    }
+
+    pub fn is_draw(&self) -> bool {
+        self.halfmove_clock >= 100 || self.is_repetition() || self.insufficient_material()
+    }
+
+    // Finds the legal move written in coordinate notation
+    pub fn parse_move(&mut self, text: &str) -> Option<Move> {
+        self.legal_moves()
+            .into_iter()
+            .find(|mv| mv.to_string().eq_ignore_ascii_case(text))
+    }
+
+    pub fn to_fen(&self) -> String {
+        let mut fen = String::new();
+        for rank in (0..8).rev() {
+            let mut empty = 0;
+            for file in 0..8 {
+                match self.squares[rank * 8 + file] {
+                    Some((_type, side)) => {
+                        if empty > 0 {
+                            fen.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        fen.push(piece_char(_type, side));
+                        if self.promoted & bitboard::bit(rank * 8 + file) != 0 {
+                            fen.push('~');
+                        }
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                fen.push_str(&empty.to_string());
+            }
+            if rank > 0 {
+                fen.push('/');
+            }
+        }
+        if self.crazyhouse {
+            fen.push_str(&self.pocket_field());
+        }
+
+        fen.push_str(match self.side_to_move {
+            Sides::White => " w ",
+            Sides::Black => " b ",
+        });
+
+        fen.push_str(&self.castling_field(false));
+
+        match self.en_passant {
+            Some(square) => fen.push_str(&format!(" {}", board::square_name(square))),
+            None => fen.push_str(" -"),
+        }
+
+        fen.push_str(&format!(
+            " {} {}",
+            self.halfmove_clock, self.fullmove_number
+        ));
+        fen
+    }
+
+    // FEN with the castling rooks always named by their files, the way
+    // Shredder writes Chess960 positions: "HAha" for the standard ones
+    pub fn to_shredder_fen(&self) -> String {
+        let fen = self.to_fen();
+        let fields: Vec<&str> = fen.split(' ').collect();
+        format!(
+            "{} {} {} {}",
+            fields[0],
+            fields[1],
+            self.castling_field(true),
+            fields[3..].join(" ")
+        )
+    }
+
+    // KQkq, except that in Chess960 a rook that isn't the outermost one on
+    // its side of the king goes by its file, as X-FEN has it. Shredder-FEN
+    // uses the files for all of them.
+    fn castling_field(&self, shredder: bool) -> String {
+        let mut field = String::new();
+        for (index, letter) in ['K', 'Q', 'k', 'q'].iter().enumerate() {
+            if self.castling & (1 << index) == 0 {
+                continue;
+            }
+            let side = if index < 2 {
+                Sides::White
+            } else {
+                Sides::Black
+            };
+            let rook = self.castling_rooks[index];
+            let outermost = outermost_rook(self, side, index % 2 == 0) == Some(rook);
+            let letter = if shredder || (self.chess960 && !outermost) {
+                let file = (b'A' + bitboard::file_of(rook) as u8) as char;
+                match side {
+                    Sides::White => file,
+                    Sides::Black => file.to_ascii_lowercase(),
+                }
+            } else {
+                *letter
+            };
+            field.push(letter);
+        }
+        if field.is_empty() {
+            field.push('-');
+        }
+        field
+    }
+}
+
+// The rook furthest out on one side of the king on its back rank
+fn outermost_rook(position: &Position, side: Sides, kingside: bool) -> Option<usize> {
+    let king = position.king_square(side);
+    let back = king - bitboard::file_of(king);
+    let mut files: Vec<usize> = if kingside {
+        (bitboard::file_of(king) + 1..8).rev().collect()
+    } else {
+        (0..bitboard::file_of(king)).collect()
+    };
+    files.retain(|file| position.squares[back + file] == Some((Types::Rook, side)));
+    files.first().map(|file| back + file)
+}
+
+pub fn castling_right(side: Sides, kingside: bool) -> u8 {
+    match (side, kingside) {
+        (Sides::White, true) => WHITE_KINGSIDE,
+        (Sides::White, false) => WHITE_QUEENSIDE,
+        (Sides::Black, true) => BLACK_KINGSIDE,
+        (Sides::Black, false) => BLACK_QUEENSIDE,
+    }
+}
+
+// Castling rights that go away when anything moves from or to the square, in
+// standard chess
+const fn castling_lost() -> [u8; 64] {
+    let mut lost = [0; 64];
+    lost[0] = WHITE_QUEENSIDE;
+    lost[4] = WHITE_KINGSIDE | WHITE_QUEENSIDE;
+    lost[7] = WHITE_KINGSIDE;
+    lost[56] = BLACK_QUEENSIDE;
+    lost[60] = BLACK_KINGSIDE | BLACK_QUEENSIDE;
+    lost[63] = BLACK_KINGSIDE;
+    lost
+}
+
+const CASTLING_LOST: [u8; 64] = castling_lost();
+
+// The rooks the rights are for in standard chess, in the order of the bits
+const CASTLING_ROOKS: [usize; 4] = [7, 0, 63, 56];
+
+pub fn piece_char(_type: Types, side: Sides) -> char {
+    let letter = match _type {
+        Types::Pawn => 'P',
+        Types::Rook => 'R',
+        Types::Bishop => 'B',
+        Types::Knight => 'N',
+        Types::Queen => 'Q',
+        Types::King => 'K',
+    };
+    match side {
+        Sides::White => letter,
+        Sides::Black => letter.to_ascii_lowercase(),
+    }
+}
+
+pub fn parse_piece_char(letter: char) -> Option<(Types, Sides)> {
+    let _type = match letter.to_ascii_uppercase() {
+        'P' => Types::Pawn,
+        'R' => Types::Rook,
+        'B' => Types::Bishop,
+        'N' => Types::Knight,
+        'Q' => Types::Queen,
+        'K' => Types::King,
+        _ => return None,
+    };
+    let side = if letter.is_ascii_uppercase() {
+        Sides::White
+    } else {
+        Sides::Black
+    };
+    Some((_type, side))
+}
+
+fn empty(side_to_move: Sides) -> Position {
+    Position {
+        squares: [None; 64],
+        bitboards: [[0; 6]; 2],
+        side_to_move,
+        castling: 0,
+        en_passant: None,
+        halfmove_clock: 0,
+        fullmove_number: 1,
+        hash: match side_to_move {
+            Sides::White => 0,
+            Sides::Black => zobrist::SIDE,
+        },
+        pawn_key: 0,
+        history: Vec::new(),
+        chess960: false,
+        castling_rooks: CASTLING_ROOKS,
+        castling_lost: CASTLING_LOST,
+        crazyhouse: false,
+        pockets: [[0; 6]; 2],
+        promoted: 0,
+        nnue: None,
+    }
+}
+
+// Castling rights are worked out from which kings and rooks haven't moved
+pub fn build(pieces: &[Piece], side_to_move: Sides) -> Position {
+    let mut position = empty(side_to_move);
+    for piece in pieces.iter() {
+        position.put(board::square(piece.location), piece._type, piece.side);
+    }
+
+    let unmoved = |_type: Types, side: Sides, location: (char, u32)| {
+        pieces.iter().any(|piece| {
+            piece._type == _type
+                && piece.side == side
+                && piece.location == location
+                && !piece.has_moved
+        })
+    };
+    let mut castling = 0;
+    for (side, row, kingside, queenside) in [
+        (Sides::White, 1, WHITE_KINGSIDE, WHITE_QUEENSIDE),
+        (Sides::Black, 8, BLACK_KINGSIDE, BLACK_QUEENSIDE),
+    ]
+    .iter()
+    {
+        if unmoved(Types::King, *side, ('E', *row)) {
+            if unmoved(Types::Rook, *side, ('H', *row)) {
+                castling |= kingside;
+            }
+            if unmoved(Types::Rook, *side, ('A', *row)) {
+                castling |= queenside;
+            }
+        }
+    }
+    position.castling = castling;
+    position.hash ^= zobrist::CASTLING[castling as usize];
+    position
+}
+
+pub fn start() -> Position {
+    build(&piece::generate_all(), Sides::White)
+}
+
+pub fn from_fen(fen: &str) -> Result<Position, String> {
+    let fields: Vec<&str> = fen.split_whitespace().collect();
+    if fields.len() < 4 {
+        return Err(format!("not enough fields in FEN: {}", fen));
+    }
+
+    let side_to_move = match fields[1] {
+        "w" => Sides::White,
+        "b" => Sides::Black,
+        other => return Err(format!("bad side to move: {}", other)),
+    };
+    let mut position = empty(side_to_move);
+
+    // Crazyhouse pockets come after the board in brackets: "[Qn]"
+    let (placement, pocket) = match fields[0].split_once('[') {
+        Some((placement, pocket)) => {
+            let pocket = pocket
+                .strip_suffix(']')
+                .ok_or(format!("bad pocket: [{}", pocket))?;
+            (placement, Some(pocket))
+        }
+        None => (fields[0], None),
+    };
+    let ranks: Vec<&str> = placement.split('/').collect();
+    if ranks.len() != 8 {
+        return Err(format!("expected 8 ranks, got {}", ranks.len()));
+    }
+    for (index, rank_text) in ranks.iter().enumerate() {
+        let rank = 7 - index;
+        let mut file = 0;
+        for letter in rank_text.chars() {
+            if let Some(skip) = letter.to_digit(10) {
+                file += skip as usize;
+            } else if letter == '~' {
+                // The piece before it was a pawn once
+                let square = (rank * 8 + file).checked_sub(1);
+                match square.filter(|square| file > 0 && position.squares[*square].is_some()) {
+                    Some(square) => position.promoted |= bitboard::bit(square),
+                    None => return Err(format!("nothing promoted on rank {}", rank + 1)),
+                }
+            } else {
+                let (_type, side) =
+                    parse_piece_char(letter).ok_or(format!("bad piece: {}", letter))?;
+                if file > 7 {
+                    return Err(format!("too many squares on rank {}", rank + 1));
+                }
+                position.put(rank * 8 + file, _type, side);
+                file += 1;
+            }
+        }
+        if file != 8 {
+            return Err(format!("wrong number of squares on rank {}", rank + 1));
+        }
+    }
+
+    if position.pieces(Types::King, Sides::White).count_ones() != 1
+        || position.pieces(Types::King, Sides::Black).count_ones() != 1
+    {
+        return Err("each side needs exactly one king".to_string());
+    }
+    let back_ranks = bitboard::RANK_1 | bitboard::rank_mask(7);
+    if (position.pieces(Types::Pawn, Sides::White) | position.pieces(Types::Pawn, Sides::Black))
+        & back_ranks
+        != 0
+    {
+        return Err("pawns can't be on the first or last rank".to_string());
+    }
+    if let Some(pocket) = pocket {
+        position.set_pockets(pocket)?;
+    }
+
+    // KQkq for the outermost rooks, or the rooks' files as in X-FEN and
+    // Shredder-FEN. Any rook that isn't where it starts in standard chess
+    // makes it a Chess960 position.
+    let mut rooks = CASTLING_ROOKS;
+    for letter in fields[2].chars().filter(|letter| *letter != '-') {
+        let side = if letter.is_ascii_uppercase() {
+            Sides::White
+        } else {
+            Sides::Black
+        };
+        let king = position.king_square(side);
+        let back = match side {
+            Sides::White => 0,
+            Sides::Black => 56,
+        };
+        let rook = match letter.to_ascii_uppercase() {
+            'K' => outermost_rook(&position, side, true),
+            'Q' => outermost_rook(&position, side, false),
+            file @ 'A'..='H' => Some(back + (file as u8 - b'A') as usize),
+            _ => return Err(format!("bad castling rights: {}", letter)),
+        };
+        let rook = rook
+            .filter(|_| bitboard::rank_of(king) == bitboard::rank_of(back))
+            .filter(|rook| position.squares[*rook] == Some((Types::Rook, side)))
+            .ok_or(format!("no rook to castle with for {}", letter))?;
+        let right = castling_right(side, rook > king);
+        position.castling |= right;
+        rooks[right.trailing_zeros() as usize] = rook;
+        if rook != CASTLING_ROOKS[right.trailing_zeros() as usize] || king != back + 4 {
+            position.chess960 = true;
+        }
+    }
+    position.set_castling_rooks(rooks);
+    position.hash ^= zobrist::CASTLING[position.castling as usize];
+
+    if fields[3] != "-" {
+        let square = board::parse_square(fields[3])
+            .ok_or(format!("bad en passant square: {}", fields[3]))?;
+        let capturers = bitboard::pawn_attacks(side_to_move.opposite(), square)
+            & position.pieces(Types::Pawn, side_to_move);
+        if capturers != 0 {
+            position.set_en_passant(Some(square));
+        }
+    }
+
+    if let Some(clock) = fields.get(4) {
+        position.halfmove_clock = clock
+            .parse()
+            .map_err(|_| format!("bad halfmove clock: {}", clock))?;
+    }
+    if let Some(number) = fields.get(5) {
+        position.fullmove_number = number
+            .parse()
+            .map_err(|_| format!("bad fullmove number: {}", number))?;
+    }
+
+    Ok(position)
+}
@@ -0,0 +1,198 @@
+use crate::bitboard;
+use crate::piece::{Sides, Types};
+use crate::position::{castling_right, Move, Position};
+
+const PROMOTIONS: [Types; 4] = [Types::Queen, Types::Rook, Types::Bishop, Types::Knight];
+
+impl Position {
+    // Every move that doesn't leave our own king in check
+    pub fn legal_moves(&mut self) -> Vec<Move> {
+        let moves = self.pseudo_legal_moves(false);
+        self.only_legal(moves)
+    }
+
+    // Captures and promotions only, for the quiescence search
+    pub fn legal_captures(&mut self) -> Vec<Move> {
+        let moves = self.pseudo_legal_moves(true);
+        self.only_legal(moves)
+    }
+
+    fn only_legal(&mut self, moves: Vec<Move>) -> Vec<Move> {
+        let us = self.side_to_move;
+        moves
+            .into_iter()
+            .filter(|mv| {
+                self.make_move(*mv);
+                let legal = !self.is_attacked(self.king_square(us), us.opposite());
+                self.unmake_move();
+                legal
+            })
+            .collect()
+    }
+
+    // Castling in Chess960 lands on our own rook, which isn't a capture
+    pub fn is_capture(&self, mv: Move) -> bool {
+        (self.squares[mv.to].is_some() && !self.is_castling(mv))
+            || (Some(mv.to) == self.en_passant
+                && self.squares[mv.from].map(|(_type, _)| _type) == Some(Types::Pawn))
+    }
+
+    pub fn gives_check(&mut self, mv: Move) -> bool {
+        self.make_move(mv);
+        let check = self.in_check();
+        self.unmake_move();
+        check
+    }
+
+    // Moves that follow the rules for how the pieces move, without checking
+    // whether they leave the king hanging
+    pub fn pseudo_legal_moves(&self, captures_only: bool) -> Vec<Move> {
+        let mut moves = Vec::with_capacity(64);
+        let us = self.side_to_move;
+        let them = us.opposite();
+        let occupied = self.all();
+        let enemies = self.occupancy(them);
+        let targets = if captures_only {
+            enemies
+        } else {
+            !self.occupancy(us)
+        };
+
+        self.pawn_moves(&mut moves, captures_only);
+
+        for _type in [
+            Types::Rook,
+            Types::Bishop,
+            Types::Knight,
+            Types::Queen,
+            Types::King,
+        ]
+        .iter()
+        {
+            for from in bitboard::squares(self.pieces(*_type, us)) {
+                let attacks = bitboard::attacks(*_type, us, from, occupied) & targets;
+                for to in bitboard::squares(attacks) {
+                    moves.push(Move::new(from, to));
+                }
+            }
+        }
+
+        if !captures_only {
+            self.castling_moves(&mut moves);
+            if self.crazyhouse {
+                self.drop_moves(&mut moves);
+            }
+        }
+
+        moves
+    }
+
+    fn pawn_moves(&self, moves: &mut Vec<Move>, captures_only: bool) {
+        let us = self.side_to_move;
+        let occupied = self.all();
+        let mut enemies = self.occupancy(us.opposite());
+        if let Some(square) = self.en_passant {
+            enemies |= bitboard::bit(square);
+        }
+        let (forward, start_rank, last_rank): (i32, usize, usize) = match us {
+            Sides::White => (8, 1, 7),
+            Sides::Black => (-8, 6, 0),
+        };
+
+        for from in bitboard::squares(self.pieces(Types::Pawn, us)) {
+            let mut targets = Vec::new();
+
+            let one = (from as i32 + forward) as usize;
+            if occupied & bitboard::bit(one) == 0 {
+                if !captures_only || bitboard::rank_of(one) == last_rank {
+                    targets.push(one);
+                }
+                let two = (one as i32 + forward) as usize;
+                if !captures_only
+                    && bitboard::rank_of(from) == start_rank
+                    && occupied & bitboard::bit(two) == 0
+                {
+                    targets.push(two);
+                }
+            }
+
+            for to in bitboard::squares(bitboard::pawn_attacks(us, from) & enemies) {
+                targets.push(to);
+            }
+
+            for to in targets {
+                if bitboard::rank_of(to) == last_rank {
+                    for promotion in PROMOTIONS.iter() {
+                        moves.push(Move {
+                            from,
+                            to,
+                            promotion: Some(*promotion),
+                            drop: None,
+                        });
+                    }
+                } else {
+                    moves.push(Move::new(from, to));
+                }
+            }
+        }
+    }
+
+    // The king and the rook can start anywhere along the back rank in
+    // Chess960. Everything between where either starts and ends up has to be
+    // empty apart from the two of them, and the king can't be in check or go
+    // through or to an attacked square.
+    fn castling_moves(&self, moves: &mut Vec<Move>) {
+        let us = self.side_to_move;
+        let them = us.opposite();
+        let king = self.king_square(us);
+        if self.pieces(Types::King, us) == 0 || self.is_attacked(king, them) {
+            return;
+        }
+
+        for kingside in [true, false].iter() {
+            let kingside = *kingside;
+            if self.castling & castling_right(us, kingside) == 0 {
+                continue;
+            }
+            let (rook, king_to, rook_to) = self.castling_squares(us, kingside);
+            if self.squares[rook] != Some((Types::Rook, us)) {
+                continue;
+            }
+            let others = self.all() & !bitboard::bit(king) & !bitboard::bit(rook);
+            if others & (span(king, king_to) | span(rook, rook_to)) != 0 {
+                continue;
+            }
+            if bitboard::squares(span(king, king_to))
+                .any(|square| square != king && self.is_attacked(square, them))
+            {
+                continue;
+            }
+            let to = if self.chess960 { rook } else { king_to };
+            moves.push(Move::new(king, to));
+        }
+    }
+}
+
+// Squares along a rank from one to the other, both included
+fn span(from: usize, to: usize) -> u64 {
+    (from.min(to)..=from.max(to)).fold(0, |span, square| span | bitboard::bit(square))
+}
+
+// Counts the leaf nodes of the move tree, the standard way to check a move
+// generator against known numbers
+pub fn perft(position: &mut Position, depth: u32) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    let moves = position.legal_moves();
+    if depth == 1 {
+        return moves.len() as u64;
+    }
+    let mut nodes = 0;
+    for mv in moves {
+        position.make_move(mv);
+        nodes += perft(position, depth - 1);
+        position.unmake_move();
+    }
+    nodes
+}
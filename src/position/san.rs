@@ -0,0 +1,175 @@
+use crate::bitboard;
+use crate::board;
+use crate::piece::{Sides, Types};
+use crate::position::{piece_char, Move, Position};
+
+// Standard algebraic notation, the way people and PGN files write moves:
+// "Nf3", "exd5", "O-O", "e8=Q+", and "N@f3" for a crazyhouse drop
+
+impl Position {
+    // The move written in SAN. It has to be legal in this position.
+    pub fn san(&mut self, mv: Move) -> String {
+        let mut text = if mv.drop.is_some() {
+            mv.to_string()
+        } else if self.is_castling(mv) {
+            if mv.to > mv.from {
+                "O-O".to_string()
+            } else {
+                "O-O-O".to_string()
+            }
+        } else {
+            let (_type, _) = match self.squares[mv.from] {
+                Some(piece) => piece,
+                None => return mv.to_string(),
+            };
+            let capture = self.is_capture(mv);
+            let mut text = String::new();
+            if _type == Types::Pawn {
+                if capture {
+                    text.push(board::location(mv.from).0.to_ascii_lowercase());
+                }
+            } else {
+                text.push(piece_char(_type, Sides::White));
+                text.push_str(&self.disambiguation(mv, _type));
+            }
+            if capture {
+                text.push('x');
+            }
+            text.push_str(&board::square_name(mv.to));
+            if let Some(promotion) = mv.promotion {
+                text.push('=');
+                text.push(piece_char(promotion, Sides::White));
+            }
+            text
+        };
+
+        self.make_move(mv);
+        if self.in_check() {
+            text.push(if self.legal_moves().is_empty() {
+                '#'
+            } else {
+                '+'
+            });
+        }
+        self.unmake_move();
+        text
+    }
+
+    // Just enough of the from square to tell `mv` apart from other moves of
+    // the same kind of piece to the same square: the file if that does it,
+    // otherwise the rank, otherwise both
+    fn disambiguation(&mut self, mv: Move, _type: Types) -> String {
+        let others: Vec<usize> = self
+            .legal_moves()
+            .into_iter()
+            .filter(|other| {
+                other.to == mv.to
+                    && other.from != mv.from
+                    && self.squares[other.from].map(|(other_type, _)| other_type) == Some(_type)
+            })
+            .map(|other| other.from)
+            .collect();
+        if others.is_empty() {
+            return String::new();
+        }
+
+        let name = board::square_name(mv.from);
+        let same_file = others
+            .iter()
+            .any(|from| bitboard::file_of(*from) == bitboard::file_of(mv.from));
+        let same_rank = others
+            .iter()
+            .any(|from| bitboard::rank_of(*from) == bitboard::rank_of(mv.from));
+        if !same_file {
+            name[..1].to_string()
+        } else if !same_rank {
+            name[1..].to_string()
+        } else {
+            name
+        }
+    }
+
+    // Finds the legal move written in SAN. Check and annotation marks are
+    // optional, and so is the "=" before a promotion.
+    pub fn parse_san(&mut self, text: &str) -> Option<Move> {
+        let text = text.trim_end_matches(|letter| "+#!?".contains(letter));
+        let text = text.replace('0', "O");
+        let moves = self.legal_moves();
+
+        if text == "O-O" || text == "O-O-O" {
+            let kingside = text == "O-O";
+            return moves
+                .into_iter()
+                .find(|mv| self.is_castling(*mv) && (mv.to > mv.from) == kingside);
+        }
+
+        // Drops, where a pawn might go without its letter: "@e4"
+        if let Some((piece, square)) = text.split_once('@') {
+            let _type = match piece {
+                "" => Types::Pawn,
+                piece => match crate::position::parse_piece_char(piece.chars().next()?) {
+                    Some((_type, _)) if piece.len() == 1 => _type,
+                    _ => return None,
+                },
+            };
+            let to = board::parse_square(square)?;
+            return moves
+                .into_iter()
+                .find(|mv| mv.drop == Some(_type) && mv.to == to);
+        }
+
+        let mut text: &str = &text;
+        let mut promotion = None;
+        if let Some(letter) = text
+            .chars()
+            .last()
+            .filter(|letter| letter.is_ascii_alphabetic())
+        {
+            if text.len() > 2 && "QRBN".contains(letter.to_ascii_uppercase()) {
+                promotion = crate::position::parse_piece_char(letter.to_ascii_uppercase())
+                    .map(|(_type, _)| _type);
+                text = text[..text.len() - 1].trim_end_matches('=');
+            }
+        }
+
+        let _type = match text.chars().next()? {
+            'K' => Types::King,
+            'Q' => Types::Queen,
+            'R' => Types::Rook,
+            'B' => Types::Bishop,
+            'N' => Types::Knight,
+            _ => Types::Pawn,
+        };
+        let rest = if _type == Types::Pawn {
+            text
+        } else {
+            &text[1..]
+        };
+        let rest: String = rest
+            .chars()
+            .filter(|letter| *letter != 'x' && *letter != '-')
+            .collect();
+        if rest.len() < 2 {
+            return None;
+        }
+        let to = board::parse_square(&rest[rest.len() - 2..])?;
+        let hint = &rest[..rest.len() - 2];
+        let file = hint.chars().find(|letter| ('a'..='h').contains(letter));
+        let rank = hint.chars().find(|letter| ('1'..='8').contains(letter));
+
+        let mut found = moves.into_iter().filter(|mv| {
+            let name = board::square_name(mv.from);
+            mv.to == to
+                && mv.promotion == promotion
+                && self.squares[mv.from].map(|(moving, _)| moving) == Some(_type)
+                && !self.is_castling(*mv)
+                && file.is_none_or(|file| name.starts_with(file))
+                && rank.is_none_or(|rank| name.ends_with(rank))
+        });
+        let mv = found.next()?;
+        if found.next().is_some() {
+            return None;
+        }
+        Some(mv)
+    }
+}
@@ -0,0 +1,65 @@
+// Random keys for hashing positions, generated at compile time from a fixed
+// seed so hashes are the same from run to run
+
+const SEED: u64 = 0x2545_f491_4f6c_dd1d;
+
+// splitmix64 of the key's index
+const fn key(index: u64) -> u64 {
+    let mut z = SEED.wrapping_add(index.wrapping_mul(0x9e37_79b9_7f4a_7c15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+const fn generate_pieces() -> [[[u64; 64]; 6]; 2] {
+    let mut keys = [[[0; 64]; 6]; 2];
+    let mut index = 0;
+    while index < 768 {
+        keys[index / 384][(index / 64) % 6][index % 64] = key(index as u64);
+        index += 1;
+    }
+    keys
+}
+
+const fn generate_castling() -> [u64; 16] {
+    let mut keys = [0; 16];
+    let mut rights = 1;
+    while rights < 16 {
+        keys[rights] = key(769 + rights as u64);
+        rights += 1;
+    }
+    keys
+}
+
+const fn generate_en_passant() -> [u64; 8] {
+    let mut keys = [0; 8];
+    let mut file = 0;
+    while file < 8 {
+        keys[file] = key(785 + file as u64);
+        file += 1;
+    }
+    keys
+}
+
+// Up to 16 of a kind in one pocket, with nothing for none
+const fn generate_pockets() -> [[[u64; 17]; 6]; 2] {
+    let mut keys = [[[0; 17]; 6]; 2];
+    let mut index = 0;
+    while index < 2 * 6 * 17 {
+        if index % 17 > 0 {
+            keys[index / 102][(index / 17) % 6][index % 17] = key(793 + index as u64);
+        }
+        index += 1;
+    }
+    keys
+}
+
+pub const PIECES: [[[u64; 64]; 6]; 2] = generate_pieces();
+// Xored in when it's Black to move
+pub const SIDE: u64 = key(768);
+// One key for every combination of castling rights, with no rights hashing to 0
+pub const CASTLING: [u64; 16] = generate_castling();
+pub const EN_PASSANT: [u64; 8] = generate_en_passant();
+// Crazyhouse pockets: the key for having n of a kind is xored in when the
+// n-th one comes and out again when it goes, so empty pockets hash to 0
+pub const POCKETS: [[[u64; 17]; 6]; 2] = generate_pockets();
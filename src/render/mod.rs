@@ -0,0 +1,189 @@
+use crate::piece::{Sides, Types};
+use crate::position::{piece_char, Position};
+use std::fmt;
+
+// Drawing positions as text: an 8x8 grid of letters or chess glyphs with the
+// ranks down the side and the files along the bottom, for debugging and for
+// playing in a terminal.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Glyphs {
+    // White's pieces in capitals, Black's in lower case
+    Ascii,
+    Unicode,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Style {
+    pub glyphs: Glyphs,
+    // ANSI colours for the squares and pieces, which need a terminal that
+    // understands them
+    pub colours: bool,
+    // Black at the bottom
+    pub flipped: bool,
+    pub labels: bool,
+    // Marks the squares of the last move and a king in check
+    pub highlights: bool,
+}
+
+impl Default for Style {
+    fn default() -> Style {
+        Style {
+            glyphs: Glyphs::Ascii,
+            colours: false,
+            flipped: false,
+            labels: true,
+            highlights: true,
+        }
+    }
+}
+
+// 256-colour backgrounds
+pub const LIGHT: u8 = 180;
+pub const DARK: u8 = 137;
+pub const LAST_LIGHT: u8 = 186;
+pub const LAST_DARK: u8 = 143;
+pub const CHECK: u8 = 160;
+
+pub const WHITE_PIECE: &str = "\x1b[1;97m";
+pub const BLACK_PIECE: &str = "\x1b[1;30m";
+pub const RESET: &str = "\x1b[0m";
+
+pub fn glyph(_type: Types, side: Sides) -> char {
+    let glyphs = match side {
+        Sides::White => ['♙', '♖', '♗', '♘', '♕', '♔'],
+        Sides::Black => ['♟', '♜', '♝', '♞', '♛', '♚'],
+    };
+    glyphs[_type.index()]
+}
+
+// A position drawn in a style, to go in format! and friends
+pub struct Board<'a> {
+    position: &'a Position,
+    style: Style,
+}
+
+impl<'a> Board<'a> {
+    pub fn new(position: &'a Position, style: Style) -> Board<'a> {
+        Board { position, style }
+    }
+
+    fn piece(&self, square: usize) -> char {
+        let (_type, side) = match self.position.squares[square] {
+            Some(piece) => piece,
+            None if self.style.colours => return ' ',
+            None if self.style.glyphs == Glyphs::Unicode => return '·',
+            None => return '.',
+        };
+        match self.style.glyphs {
+            Glyphs::Ascii => piece_char(_type, side),
+            // The solid glyphs read better on coloured squares, with the
+            // colour telling the sides apart
+            Glyphs::Unicode if self.style.colours => glyph(_type, Sides::Black),
+            Glyphs::Unicode => glyph(_type, side),
+        }
+    }
+
+    fn cell(&self, square: usize, last: &[usize], checked: Option<usize>) -> String {
+        let piece = self.piece(square);
+        let is_last = last.contains(&square);
+        let is_checked = checked == Some(square);
+        if !self.style.colours {
+            let (open, close) = if is_checked {
+                ('(', ')')
+            } else if is_last {
+                ('[', ']')
+            } else {
+                (' ', ' ')
+            };
+            return format!("{}{}{}", open, piece, close);
+        }
+
+        // a1 is a dark square
+        let light = (square / 8 + square % 8) % 2 == 1;
+        let background = match (is_checked, is_last, light) {
+            (true, _, _) => CHECK,
+            (false, true, true) => LAST_LIGHT,
+            (false, true, false) => LAST_DARK,
+            (false, false, true) => LIGHT,
+            (false, false, false) => DARK,
+        };
+        let foreground = match self.position.squares[square] {
+            Some((_, Sides::Black)) => BLACK_PIECE,
+            _ => WHITE_PIECE,
+        };
+        format!(
+            "\x1b[48;5;{}m{} {} {}",
+            background, foreground, piece, RESET
+        )
+    }
+}
+
+impl<'a> fmt::Display for Board<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let ranks: Vec<usize> = if self.style.flipped {
+            (0..8).collect()
+        } else {
+            (0..8).rev().collect()
+        };
+        let files: Vec<usize> = if self.style.flipped {
+            (0..8).rev().collect()
+        } else {
+            (0..8).collect()
+        };
+
+        let mut last = Vec::new();
+        let mut checked = None;
+        if self.style.highlights {
+            if let Some(mv) = self.position.history.last().and_then(|state| state.mv) {
+                last.push(mv.from);
+                last.push(mv.to);
+            }
+            if self.position.in_check() {
+                checked = Some(self.position.king_square(self.position.side_to_move));
+            }
+        }
+
+        for rank in ranks.iter() {
+            if self.style.labels {
+                write!(f, "{} ", rank + 1)?;
+            }
+            for file in files.iter() {
+                write!(f, "{}", self.cell(rank * 8 + file, &last, checked))?;
+            }
+            writeln!(f)?;
+        }
+        if self.style.labels {
+            write!(f, " ")?;
+            for file in files.iter() {
+                write!(f, "  {}", (b'a' + *file as u8) as char)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+// The board in the plain style, the right way up for `flipped`
+pub fn board(position: &Position, flipped: bool) -> String {
+    let style = Style {
+        flipped,
+        ..Default::default()
+    };
+    Board::new(position, style).to_string()
+}
+
+// `{}` draws the board in ASCII and `{:#}` with chess glyphs
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let style = Style {
+            glyphs: if f.alternate() {
+                Glyphs::Unicode
+            } else {
+                Glyphs::Ascii
+            },
+            ..Default::default()
+        };
+        write!(f, "{}", Board::new(self, style))
+    }
+}
@@ -0,0 +1,117 @@
+use crate::position::Position;
+use crate::search::{Limits, SearchHandle, SearchResult, Searcher, THREAD_STACK};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+// A search running on a thread of its own, for the protocols, which have to
+// keep reading commands while the engine thinks. The searcher goes with it
+// and comes back when it's stopped or cancelled.
+
+#[derive(Default)]
+struct State {
+    // The search itself is over, so stopping it again would only end the
+    // next one
+    finished: bool,
+    // A held search can give its answer now
+    released: bool,
+    // Nobody wants the answer any more
+    cancelled: bool,
+    // The answer has gone out
+    answered: bool,
+}
+
+pub struct Background {
+    thread: JoinHandle<(Searcher, SearchResult)>,
+    handle: SearchHandle,
+    // Everything that goes between this and the thread happens under the
+    // lock, so a stop can't land after the search is over and the answer is
+    // either given or cancelled, never both
+    state: Arc<Mutex<State>>,
+}
+
+impl Background {
+    // Searches `position` and passes the result to `answer` once it's done.
+    // A held search, like an infinite one, keeps its answer until it's
+    // stopped, even if it runs out of things to search first.
+    pub fn start<F>(
+        mut searcher: Searcher,
+        mut position: Position,
+        limits: Limits,
+        hold: bool,
+        answer: F,
+    ) -> Result<Background, String>
+    where
+        F: FnOnce(&SearchResult) + Send + 'static,
+    {
+        let state = Arc::new(Mutex::new(State::default()));
+        let handle = searcher.handle();
+        let shared = state.clone();
+        let thread = thread::Builder::new()
+            .stack_size(THREAD_STACK)
+            .spawn(move || {
+                let result = searcher.search(&mut position, &limits);
+                {
+                    let mut state = shared.lock().unwrap();
+                    state.finished = true;
+                    searcher.clear_signals();
+                }
+                while hold && !shared.lock().unwrap().released {
+                    thread::sleep(Duration::from_millis(1));
+                }
+                let mut state = shared.lock().unwrap();
+                if !state.cancelled {
+                    answer(&result);
+                    state.answered = true;
+                }
+                drop(state);
+                (searcher, result)
+            })
+            .map_err(|error| format!("can't start the search: {}", error))?;
+        Ok(Background {
+            thread,
+            handle,
+            state,
+        })
+    }
+
+    // Whether the answer is out and the searcher can be had back without
+    // waiting
+    pub fn is_done(&self) -> bool {
+        self.thread.is_finished()
+    }
+
+    pub fn ponder_hit(&self) {
+        let state = self.state.lock().unwrap();
+        if !state.finished {
+            self.handle.ponder_hit();
+        }
+    }
+
+    fn end(self, cancel: bool) -> (Searcher, SearchResult, bool) {
+        {
+            let mut state = self.state.lock().unwrap();
+            if !state.finished {
+                self.handle.stop();
+            }
+            state.released = true;
+            state.cancelled |= cancel;
+        }
+        let (searcher, result) = self.thread.join().expect("the search thread panicked");
+        let answered = self.state.lock().unwrap().answered;
+        (searcher, result, answered)
+    }
+
+    // Ends the search as soon as it can, with its answer
+    pub fn stop(self) -> (Searcher, SearchResult) {
+        let (searcher, result, _) = self.end(false);
+        (searcher, result)
+    }
+
+    // Ends the search without an answer, unless it had already given one,
+    // in which case that's the result that comes back
+    pub fn cancel(self) -> (Searcher, Option<SearchResult>) {
+        let (searcher, result, answered) = self.end(true);
+        (searcher, Some(result).filter(|_| answered))
+    }
+}
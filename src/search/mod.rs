@@ -0,0 +1,1103 @@
+pub mod background;
+pub mod score;
+pub mod skill;
+pub mod time;
+pub mod tt;
+
+use crate::book::Book;
+use crate::eval::{Evaluator, Params};
+use crate::position::{Move, Position};
+use crate::tablebase::{Tablebases, Wdl};
+pub use score::{Score, TB_CENTIPAWNS};
+use skill::Skill;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use tt::{Bound, TranspositionTable};
+
+pub const INFINITY: i32 = 32000;
+pub const MATE: i32 = 31000;
+pub const MAX_PLY: usize = 64;
+
+// Anything this close to MATE is a forced mate rather than an evaluation
+pub const MATE_BOUND: i32 = MATE - MAX_PLY as i32;
+
+// Wins the tablebases know of sit just below the mates and are counted down
+// by ply the same way, so the search heads for the nearest one. Any score
+// from TB_BOUND up depends on the ply it was found at.
+pub const TB_WIN: i32 = MATE_BOUND - 1;
+pub const TB_BOUND: i32 = TB_WIN - MAX_PLY as i32;
+
+// Searches from this depth on start with a narrow window around the last
+// iteration's score
+const ASPIRATION_DEPTH: i32 = 4;
+const ASPIRATION_WINDOW: i32 = 25;
+
+const RAZOR_MARGIN: [i32; 3] = [0, 300, 500];
+const FUTILITY_MARGIN: [i32; 3] = [0, 150, 350];
+
+// Rough piece values for putting captures in a sensible order, indexed like
+// `Types`
+const ORDER_VALUES: [i32; 6] = [100, 500, 330, 320, 950, 10000];
+
+// Where a weakened search starts its random numbers unless told otherwise
+const DEFAULT_SEED: u64 = 0x9e37_79b9_7f4a_7c15;
+
+// Threads only add up their node counts this often, to keep them off the
+// shared counter
+const NODE_BATCH: u64 = 1024;
+
+// Helper threads recurse as deep as the main one, so give them the same room.
+// So should any thread that runs a search of its own.
+pub const THREAD_STACK: usize = 16 << 20;
+
+// Each of the selective search tricks can be switched off on its own so we can
+// measure what it's worth
+#[derive(Debug, Clone, Copy)]
+pub struct Options {
+    pub null_move: bool,
+    pub late_move_reductions: bool,
+    pub futility_pruning: bool,
+    pub razoring: bool,
+    pub check_extensions: bool,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            null_move: true,
+            late_move_reductions: true,
+            futility_pruning: true,
+            razoring: true,
+            check_extensions: true,
+        }
+    }
+}
+
+impl Options {
+    // Plain alpha-beta, nothing pruned, reduced or extended
+    pub fn none() -> Options {
+        Options {
+            null_move: false,
+            late_move_reductions: false,
+            futility_pruning: false,
+            razoring: false,
+            check_extensions: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Limits {
+    pub depth: Option<u32>,
+    pub nodes: Option<u64>,
+    // Stop searching after this long
+    pub time: Option<Duration>,
+    // Search the position after the move we expect the opponent to play.
+    // No time limit applies until SearchHandle::ponder_hit says they played
+    // it, and then the clock starts from there.
+    pub ponder: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchResult {
+    pub best_move: Option<Move>,
+    // From the point of view of the side to move
+    pub score: i32,
+    pub depth: u32,
+    // Across every thread
+    pub nodes: u64,
+    // Positions the tablebases settled, across every thread
+    pub tb_hits: u64,
+    pub time: Duration,
+    // The line the search expects to be played, starting with best_move
+    pub pv: Vec<Move>,
+    // The best few moves, best first, when asked for more than one line.
+    // Always has the main line in it as well.
+    pub lines: Vec<Line>,
+}
+
+// One of the MultiPV lines: a root move, its score and how play continues
+#[derive(Debug, Clone, PartialEq)]
+pub struct Line {
+    pub mv: Move,
+    pub score: i32,
+    pub pv: Vec<Move>,
+}
+
+impl Line {
+    pub fn evaluation(&self) -> Score {
+        Score::from(self.score)
+    }
+}
+
+impl SearchResult {
+    // The score as centipawns or moves to mate, for showing to people
+    pub fn evaluation(&self) -> Score {
+        Score::from(self.score)
+    }
+
+    pub fn nps(&self) -> u64 {
+        let micros = self.time.as_micros().max(1);
+        (self.nodes as u128 * 1_000_000 / micros) as u64
+    }
+
+    // The reply the search expects, which is the move to ponder on
+    pub fn ponder_move(&self) -> Option<Move> {
+        self.pv.get(1).copied()
+    }
+}
+
+// What every thread of a search can see
+struct Shared {
+    tt: TranspositionTable,
+    nodes: AtomicU64,
+    tb_hits: AtomicU64,
+}
+
+impl Shared {
+    fn new(megabytes: usize) -> Shared {
+        Shared {
+            tt: TranspositionTable::new(megabytes),
+            nodes: AtomicU64::new(0),
+            tb_hits: AtomicU64::new(0),
+        }
+    }
+}
+
+// What other threads can tell a running search. Both flags are only cleared
+// once the search is over, so they can't be lost by arriving before it has
+// properly started.
+#[derive(Default)]
+struct Control {
+    stop: AtomicBool,
+    ponder_hit: AtomicBool,
+}
+
+// For stopping a search running on another thread, or letting a ponder
+// search know the move it was pondering on was played
+#[derive(Clone)]
+pub struct SearchHandle {
+    control: Arc<Control>,
+}
+
+impl SearchHandle {
+    // Ends the search as soon as possible with the best move found so far.
+    // On a ponder miss this is all there is to do before searching the
+    // position that was actually reached.
+    pub fn stop(&self) {
+        self.control.stop.store(true, Ordering::Relaxed);
+    }
+
+    // The opponent played the move we were pondering on, so from now on this
+    // is a normal search against the clock
+    pub fn ponder_hit(&self) {
+        self.control.ponder_hit.store(true, Ordering::Relaxed);
+    }
+}
+
+// Lazy SMP: every thread runs its own iterative deepening on the same
+// position and they only talk through the transposition table, where the
+// helpers leave behind the results the main thread then finds. Helpers search
+// odd threads one ply deeper to spread the work out. With one thread, the
+// default, the search is completely deterministic.
+pub struct Searcher {
+    pub evaluator: Evaluator,
+    pub options: Options,
+    shared: Arc<Shared>,
+    control: Arc<Control>,
+    helpers: Vec<Searcher>,
+    // 0 for the main thread
+    id: usize,
+    // How many root moves to find lines for
+    multi_pv: usize,
+    skill: Skill,
+    // State for the random choices weaker skill levels make
+    random: u64,
+    nodes: u64,
+    // How many of `nodes` have been added to the shared count
+    reported: u64,
+    node_limit: Option<u64>,
+    time_limit: Option<Duration>,
+    // When the time limit started counting, which for a ponder search is the
+    // ponder hit
+    clock: Instant,
+    pondering: bool,
+    stopped: bool,
+    killers: [[Option<Move>; 2]; MAX_PLY],
+    // Indexed by from and to square, bumped whenever a quiet move causes a
+    // cutoff
+    history: Vec<i32>,
+    // Triangular PV table: row `ply` holds the best line found from `ply`
+    // on, in columns ply..pv_length[ply]
+    pv_table: Vec<[Option<Move>; MAX_PLY]>,
+    pv_length: [usize; MAX_PLY],
+    tablebases: Option<Arc<Tablebases>>,
+    // What the root searches pick from, which the tablebases may have cut
+    // down to the moves that keep the best result
+    root_moves: Vec<Move>,
+    // Only the main thread keeps one
+    book: Option<Arc<Book>>,
+    // Hears about every iteration the main thread finishes
+    reporter: Option<Reporter>,
+}
+
+// Something to show a search's progress with
+pub type Reporter = Box<dyn FnMut(&SearchResult) + Send>;
+
+impl Searcher {
+    pub fn new(options: Options) -> Searcher {
+        let shared = Arc::new(Shared::new(tt::DEFAULT_MEGABYTES));
+        Searcher::thread(0, options, shared, Arc::default())
+    }
+
+    fn thread(id: usize, options: Options, shared: Arc<Shared>, control: Arc<Control>) -> Searcher {
+        Searcher {
+            evaluator: Evaluator::new(),
+            options,
+            shared,
+            control,
+            helpers: Vec::new(),
+            id,
+            multi_pv: 1,
+            skill: Skill::full(),
+            random: DEFAULT_SEED,
+            nodes: 0,
+            reported: 0,
+            node_limit: None,
+            time_limit: None,
+            clock: Instant::now(),
+            pondering: false,
+            stopped: false,
+            killers: [[None; 2]; MAX_PLY],
+            history: vec![0; 64 * 64],
+            pv_table: vec![[None; MAX_PLY]; MAX_PLY],
+            pv_length: [0; MAX_PLY],
+            tablebases: None,
+            root_moves: Vec::new(),
+            book: None,
+            reporter: None,
+        }
+    }
+
+    pub fn handle(&self) -> SearchHandle {
+        SearchHandle {
+            control: self.control.clone(),
+        }
+    }
+
+    pub fn threads(&self) -> usize {
+        self.helpers.len() + 1
+    }
+
+    pub fn set_threads(&mut self, threads: usize) {
+        let threads = threads.max(1);
+        self.helpers.truncate(threads - 1);
+        for id in self.helpers.len() + 1..threads {
+            let mut helper =
+                Searcher::thread(id, self.options, self.shared.clone(), self.control.clone());
+            helper.tablebases = self.tablebases.clone();
+            self.helpers.push(helper);
+        }
+    }
+
+    // Search for the best `lines` moves instead of just the best one. Only
+    // the main thread does this, the helpers keep to a single line.
+    pub fn set_multi_pv(&mut self, lines: usize) {
+        self.multi_pv = lines.max(1);
+    }
+
+    pub fn skill(&self) -> Skill {
+        self.skill
+    }
+
+    pub fn set_skill(&mut self, skill: Skill) {
+        self.skill = skill;
+    }
+
+    // Weaker skill levels choose their moves at random, the same way every
+    // time for the same seed
+    pub fn set_seed(&mut self, seed: u64) {
+        self.random = if seed == 0 { DEFAULT_SEED } else { seed };
+    }
+
+    // Endgame tablebases to choose root moves with and to end the search
+    // early once it reaches positions they cover
+    pub fn set_tablebases(&mut self, tablebases: Option<Arc<Tablebases>>) {
+        for helper in self.helpers.iter_mut() {
+            helper.tablebases = tablebases.clone();
+        }
+        self.tablebases = tablebases;
+    }
+
+    // An opening book to play from, without searching, for as long as it
+    // has moves for the position
+    pub fn set_book(&mut self, book: Option<Arc<Book>>) {
+        self.book = book;
+    }
+
+    // Called with the result so far after each depth the search finishes,
+    // with the nodes and time up to then
+    pub fn set_reporter(&mut self, reporter: Option<Reporter>) {
+        self.reporter = reporter;
+    }
+
+    // Throws away the transposition table for one of the given size
+    pub fn set_hash_size(&mut self, megabytes: usize) {
+        self.shared = Arc::new(Shared::new(megabytes));
+        for helper in self.helpers.iter_mut() {
+            helper.shared = self.shared.clone();
+        }
+    }
+
+    // Drops a stop or ponder hit that came in after the last search was
+    // already over, which would otherwise end the next one before it starts
+    pub fn clear_signals(&self) {
+        self.control.stop.store(false, Ordering::Relaxed);
+        self.control.ponder_hit.store(false, Ordering::Relaxed);
+    }
+
+    // Evaluates with these parameters from now on. The pawn table's scores
+    // were worked out with the old ones, so it starts again.
+    pub fn set_params(&mut self, params: Params) {
+        for helper in self.helpers.iter_mut() {
+            helper.set_params(params.clone());
+        }
+        self.evaluator.params = params;
+        self.evaluator.pawn_table.clear();
+    }
+
+    // Forget everything learnt from earlier searches, for a new game
+    pub fn clear(&mut self) {
+        self.shared.tt.clear();
+        self.history.iter_mut().for_each(|entry| *entry = 0);
+        self.evaluator.pawn_table.clear();
+        for helper in self.helpers.iter_mut() {
+            helper.clear();
+        }
+    }
+
+    // Iterative deepening down to the depth limit, or until the node limit
+    // runs out, in which case the last finished iteration is what counts.
+    // Helper threads run alongside the main one until it's done.
+    pub fn search(&mut self, position: &mut Position, limits: &Limits) -> SearchResult {
+        let start = Instant::now();
+        if let Some(result) = self.book_move(position, limits) {
+            return result;
+        }
+        let limits = &Limits {
+            depth: tightest(limits.depth, self.skill.depth_limit()),
+            nodes: tightest(limits.nodes, self.skill.node_limit()),
+            ..*limits
+        };
+        // With a network the position keeps its accumulators up to date as
+        // the search makes and takes back moves
+        let accumulators = position.nnue.take();
+        position.set_network(self.evaluator.network.clone());
+        self.shared.nodes.store(0, Ordering::Relaxed);
+        self.shared.tb_hits.store(0, Ordering::Relaxed);
+        self.shared.tt.new_search();
+
+        // Taken out for the duration so the main thread can borrow itself
+        let mut helpers = std::mem::take(&mut self.helpers);
+        let main = &mut *self;
+        let mut result = thread::scope(|scope| {
+            let handles: Vec<_> = helpers
+                .iter_mut()
+                .map(|helper| {
+                    helper.evaluator.params = main.evaluator.params.clone();
+                    helper.evaluator.network = main.evaluator.network.clone();
+                    helper.options = main.options;
+                    let mut position = position.clone();
+                    thread::Builder::new()
+                        .stack_size(THREAD_STACK)
+                        .spawn_scoped(scope, move || helper.iterate(&mut position, limits))
+                        .expect("couldn't start a search thread")
+                })
+                .collect();
+            let result = main.iterate(position, limits);
+            main.wait_while_pondering();
+            main.control.stop.store(true, Ordering::Relaxed);
+            for handle in handles {
+                handle.join().expect("a search thread panicked");
+            }
+            result
+        });
+        self.helpers = helpers;
+        position.nnue = accumulators;
+        self.control.stop.store(false, Ordering::Relaxed);
+        self.control.ponder_hit.store(false, Ordering::Relaxed);
+
+        // A weaker level plays one of the good moves rather than the best
+        if !self.skill.is_full() && result.lines.len() > 1 {
+            let line = result.lines[self.skill.pick(&result.lines, &mut self.random)].clone();
+            result.best_move = Some(line.mv);
+            result.score = line.score;
+            result.pv = line.pv;
+        }
+        result.lines.truncate(self.multi_pv);
+
+        result.nodes = self.shared.nodes.load(Ordering::Relaxed);
+        result.tb_hits = self.shared.tb_hits.load(Ordering::Relaxed);
+        result.time = start.elapsed();
+        result
+    }
+
+    // Book moves are played straight away, except when pondering, where
+    // there's nobody to play them against yet
+    fn book_move(&mut self, position: &mut Position, limits: &Limits) -> Option<SearchResult> {
+        if limits.ponder {
+            return None;
+        }
+        let mv = self.book.as_ref()?.choose(position, &mut self.random)?;
+        Some(SearchResult {
+            best_move: Some(mv),
+            score: 0,
+            depth: 0,
+            nodes: 0,
+            tb_hits: 0,
+            time: Duration::default(),
+            pv: vec![mv],
+            lines: vec![Line {
+                mv,
+                score: 0,
+                pv: vec![mv],
+            }],
+        })
+    }
+
+    // The main thread of a weakened search always wants some moves to choose
+    // from
+    fn lines_wanted(&self) -> usize {
+        if self.id == 0 && !self.skill.is_full() {
+            self.multi_pv.max(skill::CANDIDATES)
+        } else {
+            self.multi_pv
+        }
+    }
+
+    fn iterate(&mut self, position: &mut Position, limits: &Limits) -> SearchResult {
+        self.nodes = 0;
+        self.reported = 0;
+        self.node_limit = limits.nodes;
+        self.time_limit = limits.time;
+        self.clock = Instant::now();
+        self.pondering = limits.ponder;
+        self.stopped = false;
+        self.killers = [[None; 2]; MAX_PLY];
+        for entry in self.history.iter_mut() {
+            *entry /= 8;
+        }
+
+        self.root_moves = position.legal_moves();
+        if let Some(tablebases) = &self.tablebases {
+            match tablebases.root_moves(position) {
+                Some(moves) if !moves.is_empty() => self.root_moves = moves,
+                _ => {}
+            }
+        }
+        let mut result = SearchResult {
+            best_move: self.root_moves.first().copied(),
+            score: 0,
+            depth: 0,
+            nodes: 0,
+            tb_hits: 0,
+            time: Duration::default(),
+            pv: Vec::new(),
+            lines: Vec::new(),
+        };
+        if result.best_move.is_none() {
+            result.score = if position.in_check() { -MATE } else { 0 };
+            return result;
+        }
+
+        let max_depth = limits
+            .depth
+            .unwrap_or(MAX_PLY as u32 - 1)
+            .min(MAX_PLY as u32 - 1);
+        let wanted = self.lines_wanted().min(self.root_moves.len());
+        for depth in 1..=max_depth {
+            let depth = (depth + self.id as u32 % 2).min(max_depth);
+
+            // Each line is a search of the root moves the lines before it
+            // didn't already take. Lines only replace the last depth's once
+            // every one of them is done, so they always belong together.
+            let mut lines: Vec<Line> = Vec::with_capacity(wanted);
+            while lines.len() < wanted {
+                let excluded: Vec<Move> = lines.iter().map(|line| line.mv).collect();
+                let previous = result.lines.get(lines.len());
+                let score = self.aspiration(
+                    position,
+                    depth as i32,
+                    previous.map_or(result.score, |line| line.score),
+                    previous.map(|line| line.mv),
+                    &excluded,
+                );
+                if self.stopped {
+                    break;
+                }
+                let pv = self.principal_variation();
+                match pv.first() {
+                    Some(mv) => lines.push(Line { mv: *mv, score, pv }),
+                    None => break,
+                }
+            }
+            if self.stopped || lines.is_empty() {
+                break;
+            }
+
+            lines.sort_by_key(|line| -line.score);
+            result.best_move = Some(lines[0].mv);
+            result.score = lines[0].score;
+            result.pv = lines[0].pv.clone();
+            result.lines = lines;
+            result.depth = depth;
+            self.report(&result);
+        }
+
+        self.report_nodes();
+        result
+    }
+
+    fn report(&mut self, result: &SearchResult) {
+        if self.reporter.is_none() {
+            return;
+        }
+        let mut progress = result.clone();
+        progress.nodes = self.report_nodes();
+        progress.tb_hits = self.shared.tb_hits.load(Ordering::Relaxed);
+        progress.time = self.clock.elapsed();
+        if let Some(reporter) = self.reporter.as_mut() {
+            reporter(&progress);
+        }
+    }
+
+    // A ponder search that runs out of things to do still mustn't answer
+    // before the opponent has moved
+    fn wait_while_pondering(&self) {
+        while self.pondering
+            && !self.control.stop.load(Ordering::Relaxed)
+            && !self.control.ponder_hit.load(Ordering::Relaxed)
+        {
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    // Guess that the score will be close to last time and search with a
+    // narrow window around it, widening whichever side it falls out of
+    fn aspiration(
+        &mut self,
+        position: &mut Position,
+        depth: i32,
+        previous: i32,
+        first: Option<Move>,
+        excluded: &[Move],
+    ) -> i32 {
+        if depth < ASPIRATION_DEPTH {
+            return self.root(position, depth, -INFINITY, INFINITY, first, excluded);
+        }
+
+        let mut delta = ASPIRATION_WINDOW;
+        let mut alpha = (previous - delta).max(-INFINITY);
+        let mut beta = (previous + delta).min(INFINITY);
+        loop {
+            let score = self.root(position, depth, alpha, beta, first, excluded);
+            if self.stopped {
+                return score;
+            }
+            if score <= alpha {
+                alpha = (alpha - delta).max(-INFINITY);
+            } else if score >= beta {
+                beta = (beta + delta).min(INFINITY);
+            } else {
+                return score;
+            }
+            delta *= 2;
+            if delta > 500 {
+                alpha = -INFINITY;
+                beta = INFINITY;
+            }
+        }
+    }
+
+    fn root(
+        &mut self,
+        position: &mut Position,
+        depth: i32,
+        mut alpha: i32,
+        beta: i32,
+        first: Option<Move>,
+        excluded: &[Move],
+    ) -> i32 {
+        self.pv_length[0] = 0;
+        let mut moves = self.root_moves.clone();
+        moves.retain(|mv| !excluded.contains(mv));
+        self.order_moves(position, &mut moves, 0, first);
+
+        let mut best = -INFINITY;
+        for (index, mv) in moves.into_iter().enumerate() {
+            position.make_move(mv);
+            let score = if index == 0 {
+                -self.alpha_beta(position, depth - 1, -beta, -alpha, 1, true)
+            } else {
+                let score = -self.alpha_beta(position, depth - 1, -alpha - 1, -alpha, 1, true);
+                if score > alpha && score < beta {
+                    -self.alpha_beta(position, depth - 1, -beta, -alpha, 1, true)
+                } else {
+                    score
+                }
+            };
+            position.unmake_move();
+
+            if self.stopped {
+                break;
+            }
+            if score > best {
+                best = score;
+            }
+            if score > alpha {
+                alpha = score;
+                self.update_pv(0, mv);
+                if alpha >= beta {
+                    break;
+                }
+            }
+        }
+
+        best
+    }
+
+    fn update_pv(&mut self, ply: usize, mv: Move) {
+        self.pv_table[ply][ply] = Some(mv);
+        let child_length = self.pv_length[ply + 1].max(ply + 1);
+        for index in ply + 1..child_length {
+            self.pv_table[ply][index] = self.pv_table[ply + 1][index];
+        }
+        self.pv_length[ply] = child_length;
+    }
+
+    pub fn principal_variation(&self) -> Vec<Move> {
+        self.pv_table[0][..self.pv_length[0]]
+            .iter()
+            .filter_map(|mv| *mv)
+            .collect()
+    }
+
+    fn alpha_beta(
+        &mut self,
+        position: &mut Position,
+        mut depth: i32,
+        mut alpha: i32,
+        beta: i32,
+        ply: usize,
+        can_null: bool,
+    ) -> i32 {
+        self.pv_length[ply] = ply;
+        if position.is_draw() {
+            return 0;
+        }
+
+        let in_check = position.in_check();
+        if in_check && self.options.check_extensions {
+            depth += 1;
+        }
+        if depth <= 0 {
+            return self.quiesce(position, alpha, beta, ply);
+        }
+
+        if self.out_of_nodes() {
+            return 0;
+        }
+        if ply >= MAX_PLY - 1 {
+            return self.evaluator.evaluate(position);
+        }
+
+        // Mate distance pruning: if we've already found a mate closer to the
+        // root than anything we could find from here, there's nothing to look
+        // for
+        alpha = alpha.max(-MATE + ply as i32);
+        let beta = beta.min(MATE - ply as i32 - 1);
+        if alpha >= beta {
+            return alpha;
+        }
+
+        let pv_node = beta - alpha > 1;
+        let original_alpha = alpha;
+
+        // Another search of this position, by us or another thread, that went
+        // at least as deep settles it. PV nodes keep searching so the PV
+        // doesn't get cut short.
+        let entry = self.shared.tt.probe(position.hash, ply);
+        let hash_move = entry.and_then(|entry| entry.best_move);
+        if let Some(entry) = entry {
+            let cutoff = match entry.bound {
+                Bound::Exact => true,
+                Bound::Lower => entry.score >= beta,
+                Bound::Upper => entry.score <= alpha,
+            };
+            if !pv_node && entry.depth >= depth && cutoff {
+                return entry.score;
+            }
+        }
+
+        // The tablebases know the result for sure, but only right after a
+        // capture or pawn move, since they don't count the fifty move rule
+        // any further than that
+        if let Some(score) = self.probe_tablebases(position, depth, alpha, beta, ply) {
+            return score;
+        }
+
+        let us = position.side_to_move;
+        let static_eval = if in_check {
+            -INFINITY
+        } else {
+            self.evaluator.evaluate(position)
+        };
+
+        // Razoring: so far below alpha that only a capture could save us, so
+        // let the quiescence search decide
+        if self.options.razoring
+            && !pv_node
+            && !in_check
+            && depth <= 2
+            && static_eval + RAZOR_MARGIN[depth as usize] < alpha
+        {
+            let score = self.quiesce(position, alpha, beta, ply);
+            if score < alpha {
+                return score;
+            }
+        }
+
+        // Null move pruning: if we're still above beta after passing, the
+        // opponent isn't going to get back under it. Passing is never safe
+        // in zugzwang, which is why we need pieces and not just pawns, and
+        // why deep cutoffs are checked with a normal reduced search.
+        if self.options.null_move
+            && can_null
+            && !pv_node
+            && !in_check
+            && depth >= 3
+            && static_eval >= beta
+            && position.has_non_pawn_material(us)
+        {
+            let reduction = if depth > 6 { 3 } else { 2 };
+            position.make_null();
+            let score = -self.alpha_beta(
+                position,
+                depth - 1 - reduction,
+                -beta,
+                -beta + 1,
+                ply + 1,
+                false,
+            );
+            position.unmake_null();
+            if self.stopped {
+                return 0;
+            }
+            if score >= beta {
+                if depth <= 6 {
+                    return beta;
+                }
+                let verified =
+                    self.alpha_beta(position, depth - 1 - reduction, beta - 1, beta, ply, false);
+                if verified >= beta {
+                    return beta;
+                }
+            }
+        }
+
+        // Futility pruning: close to the horizon and far enough below alpha
+        // that a quiet move won't bring us back
+        let futile = self.options.futility_pruning
+            && !pv_node
+            && !in_check
+            && depth <= 2
+            && alpha.abs() < MATE_BOUND
+            && static_eval + FUTILITY_MARGIN[depth as usize] <= alpha;
+
+        let mut moves = position.legal_moves();
+        if moves.is_empty() {
+            return if in_check { -MATE + ply as i32 } else { 0 };
+        }
+        self.order_moves(position, &mut moves, ply, hash_move);
+
+        let mut best = -INFINITY;
+        let mut best_move = None;
+        let mut searched = 0;
+        for mv in moves {
+            let quiet = !position.is_capture(mv) && mv.promotion.is_none();
+            let killer = self.killers[ply].contains(&Some(mv));
+
+            position.make_move(mv);
+            let gives_check = position.in_check();
+
+            if futile && quiet && !gives_check && searched > 0 {
+                position.unmake_move();
+                continue;
+            }
+
+            // Late move reductions: moves this far down the list rarely turn
+            // out best, so look at them less deeply unless they surprise us
+            let reduction = if self.options.late_move_reductions
+                && depth >= 3
+                && searched >= 3
+                && quiet
+                && !killer
+                && !in_check
+                && !gives_check
+            {
+                late_move_reduction(depth, searched).min(depth - 2)
+            } else {
+                0
+            };
+
+            // Principal variation search: the first move gets the full
+            // window, everything after it only has to prove it's no better,
+            // and is searched properly if it turns out it is
+            let score = if searched == 0 {
+                -self.alpha_beta(position, depth - 1, -beta, -alpha, ply + 1, true)
+            } else {
+                let mut score = -self.alpha_beta(
+                    position,
+                    depth - 1 - reduction,
+                    -alpha - 1,
+                    -alpha,
+                    ply + 1,
+                    true,
+                );
+                if reduction > 0 && score > alpha {
+                    score =
+                        -self.alpha_beta(position, depth - 1, -alpha - 1, -alpha, ply + 1, true);
+                }
+                if score > alpha && score < beta {
+                    score = -self.alpha_beta(position, depth - 1, -beta, -alpha, ply + 1, true);
+                }
+                score
+            };
+            position.unmake_move();
+
+            if self.stopped {
+                return 0;
+            }
+            searched += 1;
+
+            if score > best {
+                best = score;
+                best_move = Some(mv);
+            }
+            if score > alpha {
+                alpha = score;
+                self.update_pv(ply, mv);
+                if alpha >= beta {
+                    if quiet {
+                        self.remember_cutoff(mv, depth, ply);
+                    }
+                    break;
+                }
+            }
+        }
+
+        let bound = if best >= beta {
+            Bound::Lower
+        } else if best > original_alpha {
+            Bound::Exact
+        } else {
+            Bound::Upper
+        };
+        // After a fail low every move was as bad as the next, so none of them
+        // is worth trying first
+        let best_move = if bound == Bound::Upper {
+            None
+        } else {
+            best_move
+        };
+        self.shared
+            .tt
+            .store(position.hash, ply, depth, best, bound, best_move);
+
+        best
+    }
+
+    // The tablebase score for a position they cover, right after a capture or
+    // pawn move, when it's enough for a cutoff. It goes in the table a few
+    // plies deeper than asked, since it's exact.
+    fn probe_tablebases(
+        &mut self,
+        position: &mut Position,
+        depth: i32,
+        alpha: i32,
+        beta: i32,
+        ply: usize,
+    ) -> Option<i32> {
+        let tablebases = self.tablebases.as_ref()?;
+        if position.halfmove_clock != 0 || !tablebases.covers(position) {
+            return None;
+        }
+        let wdl = tablebases.probe_wdl(position)?;
+        self.shared.tb_hits.fetch_add(1, Ordering::Relaxed);
+
+        let (score, bound) = match wdl {
+            Wdl::Win => (TB_WIN - ply as i32, Bound::Lower),
+            Wdl::Loss => (-TB_WIN + ply as i32, Bound::Upper),
+            _ => (0, Bound::Exact),
+        };
+        let cutoff = match bound {
+            Bound::Exact => true,
+            Bound::Lower => score >= beta,
+            Bound::Upper => score <= alpha,
+        };
+        if !cutoff {
+            return None;
+        }
+        self.shared.tt.store(
+            position.hash,
+            ply,
+            (depth + 6).min(MAX_PLY as i32),
+            score,
+            bound,
+            None,
+        );
+        Some(score)
+    }
+
+    // Only captures and promotions, so we never stop looking in the middle
+    // of an exchange
+    fn quiesce(&mut self, position: &mut Position, mut alpha: i32, beta: i32, ply: usize) -> i32 {
+        self.pv_length[ply] = ply;
+        if self.out_of_nodes() {
+            return 0;
+        }
+
+        let stand_pat = self.evaluator.evaluate(position);
+        if ply >= MAX_PLY - 1 || stand_pat >= beta {
+            return stand_pat;
+        }
+        if stand_pat > alpha {
+            alpha = stand_pat;
+        }
+
+        let mut moves = position.legal_captures();
+        self.order_moves(position, &mut moves, ply, None);
+
+        for mv in moves {
+            position.make_move(mv);
+            let score = -self.quiesce(position, -beta, -alpha, ply + 1);
+            position.unmake_move();
+
+            if self.stopped {
+                return 0;
+            }
+            if score >= beta {
+                return score;
+            }
+            if score > alpha {
+                alpha = score;
+            }
+        }
+
+        alpha
+    }
+
+    // With a node limit every node is counted straight away so the limit is
+    // exact, otherwise only every NODE_BATCH nodes. The main thread keeps an
+    // eye on the clock at the same time.
+    fn out_of_nodes(&mut self) -> bool {
+        self.nodes += 1;
+        let batch = self.nodes.is_multiple_of(NODE_BATCH);
+        if self.node_limit.is_some() || batch {
+            let total = self.report_nodes();
+            if self.node_limit.is_some_and(|limit| total > limit) {
+                self.control.stop.store(true, Ordering::Relaxed);
+            }
+        }
+        if batch && self.id == 0 && self.out_of_time() {
+            self.control.stop.store(true, Ordering::Relaxed);
+        }
+        if self.control.stop.load(Ordering::Relaxed) {
+            self.stopped = true;
+        }
+        self.stopped
+    }
+
+    fn out_of_time(&mut self) -> bool {
+        if self.pondering {
+            if !self.control.ponder_hit.load(Ordering::Relaxed) {
+                return false;
+            }
+            self.pondering = false;
+            self.clock = Instant::now();
+        }
+        self.time_limit
+            .is_some_and(|limit| self.clock.elapsed() >= limit)
+    }
+
+    // Adds our new nodes to the shared count and returns the total
+    fn report_nodes(&mut self) -> u64 {
+        let new = self.nodes - self.reported;
+        self.reported = self.nodes;
+        self.shared.nodes.fetch_add(new, Ordering::Relaxed) + new
+    }
+
+    fn remember_cutoff(&mut self, mv: Move, depth: i32, ply: usize) {
+        if self.killers[ply][0] != Some(mv) {
+            self.killers[ply][1] = self.killers[ply][0];
+            self.killers[ply][0] = Some(mv);
+        }
+        self.history[mv.from * 64 + mv.to] += depth * depth;
+    }
+
+    // Best guess first: the move that was best last time, then captures of
+    // big pieces by small ones, promotions, killers and finally whatever
+    // quiet moves have been causing cutoffs
+    fn order_moves(
+        &self,
+        position: &Position,
+        moves: &mut [Move],
+        ply: usize,
+        first: Option<Move>,
+    ) {
+        moves.sort_by_cached_key(|mv| {
+            let score = if Some(*mv) == first {
+                1_000_000
+            } else if position.is_capture(*mv) {
+                let victim =
+                    position.squares[mv.to].map_or(0, |(_type, _)| ORDER_VALUES[_type.index()]);
+                let attacker =
+                    position.squares[mv.from].map_or(0, |(_type, _)| ORDER_VALUES[_type.index()]);
+                100_000 + victim * 10 - attacker / 10
+            } else if mv.promotion.is_some() {
+                90_000
+            } else if self.killers[ply][0] == Some(*mv) {
+                80_000
+            } else if self.killers[ply][1] == Some(*mv) {
+                79_000
+            } else {
+                self.history[mv.from * 64 + mv.to].min(70_000)
+            };
+            -score
+        });
+    }
+}
+
+fn tightest<T: Ord>(limit: Option<T>, other: Option<T>) -> Option<T> {
+    match (limit, other) {
+        (Some(limit), Some(other)) => Some(limit.min(other)),
+        (limit, other) => limit.or(other),
+    }
+}
+
+fn late_move_reduction(depth: i32, searched: usize) -> i32 {
+    let reduction = (depth as f64).ln() * (searched as f64).ln() / 2.0;
+    (reduction as i32).max(1)
+}
+
+pub fn best_move(position: &mut Position, depth: u32) -> Option<Move> {
+    let limits = Limits {
+        depth: Some(depth),
+        ..Default::default()
+    };
+    Searcher::new(Options::default())
+        .search(position, &limits)
+        .best_move
+}
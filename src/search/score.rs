@@ -0,0 +1,53 @@
+use crate::search::{MATE, MATE_BOUND, TB_BOUND};
+use std::fmt;
+
+// A search score the way a person wants to see it: either an evaluation or
+// how many moves until mate. Mate(n) is mate in n moves for the side to move,
+// and Mate(-n) means they get mated in n. Mate(0) is being mated already.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Score {
+    Centipawns(i32),
+    Mate(i32),
+}
+
+// What a tablebase win comes out as, rather than the couple of hundred pawns
+// short of mate it is inside the search. Other engines show them the same way.
+pub const TB_CENTIPAWNS: i32 = 20000;
+
+impl From<i32> for Score {
+    // Inside the search mates are MATE minus the number of plies to get there
+    fn from(value: i32) -> Score {
+        if value >= MATE_BOUND {
+            Score::Mate((MATE - value + 1) / 2)
+        } else if value <= -MATE_BOUND {
+            Score::Mate(-(MATE + value) / 2)
+        } else if value >= TB_BOUND {
+            Score::Centipawns(TB_CENTIPAWNS)
+        } else if value <= -TB_BOUND {
+            Score::Centipawns(-TB_CENTIPAWNS)
+        } else {
+            Score::Centipawns(value)
+        }
+    }
+}
+
+impl Score {
+    pub fn is_mate(self) -> bool {
+        matches!(self, Score::Mate(_))
+    }
+}
+
+// "M5" and "-M3" for mates, pawns with two decimals otherwise
+impl fmt::Display for Score {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Score::Mate(moves) if moves < 0 => write!(f, "-M{}", -moves),
+            Score::Mate(moves) => write!(f, "M{}", moves),
+            Score::Centipawns(centipawns) => {
+                let sign = if centipawns < 0 { "-" } else { "+" };
+                let centipawns = centipawns.abs();
+                write!(f, "{}{}.{:02}", sign, centipawns / 100, centipawns % 100)
+            }
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use crate::search::{Line, MATE_BOUND};
+
+// Playing strength from 0, a beginner, to MAX_LEVEL, which is the engine at
+// full strength. Weaker levels search less and don't always play their best
+// move, but they only ever pick moves that looked nearly as good, so their
+// mistakes are the kind a person might make.
+pub const MAX_LEVEL: u32 = 20;
+
+// Weakened levels look at this many root moves to choose from
+pub const CANDIDATES: usize = 4;
+
+const LOWEST_ELO: u32 = 800;
+const ELO_PER_LEVEL: u32 = 100;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Skill {
+    level: u32,
+}
+
+impl Default for Skill {
+    fn default() -> Skill {
+        Skill::full()
+    }
+}
+
+impl Skill {
+    pub fn full() -> Skill {
+        Skill { level: MAX_LEVEL }
+    }
+
+    pub fn level(level: u32) -> Skill {
+        Skill {
+            level: level.min(MAX_LEVEL),
+        }
+    }
+
+    // A very rough match between levels and rating, 100 points a level from
+    // 800 up
+    pub fn from_elo(elo: u32) -> Skill {
+        Skill::level(elo.saturating_sub(LOWEST_ELO) / ELO_PER_LEVEL)
+    }
+
+    pub fn elo(self) -> u32 {
+        LOWEST_ELO + self.level * ELO_PER_LEVEL
+    }
+
+    pub fn get(self) -> u32 {
+        self.level
+    }
+
+    pub fn is_full(self) -> bool {
+        self.level == MAX_LEVEL
+    }
+
+    pub fn depth_limit(self) -> Option<u32> {
+        if self.is_full() {
+            None
+        } else {
+            Some(1 + self.level / 2)
+        }
+    }
+
+    pub fn node_limit(self) -> Option<u64> {
+        if self.is_full() {
+            None
+        } else {
+            Some(1000 << (self.level / 2))
+        }
+    }
+
+    // How far below the best move, in centipawns, a move can be and still get
+    // played
+    pub fn margin(self) -> i32 {
+        (MAX_LEVEL - self.level) as i32 * 25 / 2
+    }
+
+    // Chooses which of the ranked `lines` to play. Moves are only considered
+    // if they're within the margin of the best one and don't walk into a
+    // forced mate the best move avoids, and the closer a move is to the best
+    // the more likely it gets picked.
+    pub fn pick(self, lines: &[Line], random: &mut u64) -> usize {
+        let top = match lines.first() {
+            Some(line) => line.score,
+            None => return 0,
+        };
+        let margin = self.margin();
+        let weights: Vec<i64> = lines
+            .iter()
+            .map(|line| {
+                let gap = top - line.score;
+                let mated = line.score <= -MATE_BOUND && top > -MATE_BOUND;
+                if gap > margin || mated {
+                    0
+                } else {
+                    (margin - gap + 1) as i64
+                }
+            })
+            .collect();
+
+        let total: i64 = weights.iter().sum();
+        if total <= 0 {
+            return 0;
+        }
+        let mut roll = (next_random(random) % total as u64) as i64;
+        for (index, weight) in weights.iter().enumerate() {
+            if roll < *weight {
+                return index;
+            }
+            roll -= weight;
+        }
+        0
+    }
+}
+
+// xorshift64*, plenty for choosing between a few moves
+pub fn next_random(state: &mut u64) -> u64 {
+    *state ^= *state >> 12;
+    *state ^= *state << 25;
+    *state ^= *state >> 27;
+    state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+}
@@ -0,0 +1,20 @@
+use std::time::Duration;
+
+// How long to think about a move with `remaining` on the clock, `increment`
+// coming back after it, and `moves_to_go` moves left until the next time
+// control if there is one. Without that it plans for a game that goes on
+// another MOVES_LEFT moves, so early moves get a bit less than later ones.
+
+const MOVES_LEFT: u32 = 30;
+
+// Kept back for the time it takes the move to get to the clock
+const OVERHEAD: Duration = Duration::from_millis(50);
+
+pub fn allocate(remaining: Duration, increment: Duration, moves_to_go: Option<u32>) -> Duration {
+    let moves = moves_to_go.unwrap_or(MOVES_LEFT).clamp(1, MOVES_LEFT);
+    let usable = remaining.saturating_sub(OVERHEAD);
+    // Divided first, so clocks with absurd amounts of time can't overflow
+    let share = (usable / moves).saturating_add(increment / 4 * 3);
+    // Never more than most of what's left, whatever the increment
+    share.min(usable / 5 * 4).max(Duration::from_millis(1))
+}
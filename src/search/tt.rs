@@ -0,0 +1,201 @@
+use crate::piece::Types;
+use crate::position::Move;
+use crate::search::TB_BOUND;
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+// The transposition table is shared by every search thread without any
+// locking. Each slot is two words, the key stored xored with the data, so a
+// slot torn by two threads writing at once no longer matches its key and is
+// simply treated as a miss.
+
+pub const DEFAULT_MEGABYTES: usize = 16;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Bound {
+    // The score is exact
+    Exact,
+    // The search failed high, the real score is at least this
+    Lower,
+    // The search failed low, the real score is at most this
+    Upper,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Entry {
+    pub best_move: Option<Move>,
+    pub score: i32,
+    pub depth: i32,
+    pub bound: Bound,
+    generation: u8,
+}
+
+#[derive(Default)]
+struct Slot {
+    key: AtomicU64,
+    data: AtomicU64,
+}
+
+pub struct TranspositionTable {
+    slots: Vec<Slot>,
+    // Bumped once a search so entries from old searches get replaced first
+    generation: AtomicU8,
+}
+
+impl TranspositionTable {
+    pub fn new(megabytes: usize) -> TranspositionTable {
+        let wanted = (megabytes.max(1) << 20) / std::mem::size_of::<Slot>();
+        // Round down to a power of two so the index is just a mask
+        let size = 1 << (usize::BITS - 1 - wanted.leading_zeros());
+        TranspositionTable {
+            slots: (0..size).map(|_| Slot::default()).collect(),
+            generation: AtomicU8::new(0),
+        }
+    }
+
+    pub fn new_search(&self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn clear(&self) {
+        for slot in self.slots.iter() {
+            slot.key.store(0, Ordering::Relaxed);
+            slot.data.store(0, Ordering::Relaxed);
+        }
+    }
+
+    fn slot(&self, key: u64) -> &Slot {
+        &self.slots[key as usize & (self.slots.len() - 1)]
+    }
+
+    // Mate and tablebase scores are stored relative to the position rather than the root,
+    // so `ply` is needed to turn them back into distances from the root
+    pub fn probe(&self, key: u64, ply: usize) -> Option<Entry> {
+        let slot = self.slot(key);
+        let data = slot.data.load(Ordering::Relaxed);
+        if data == 0 || slot.key.load(Ordering::Relaxed) ^ data != key {
+            return None;
+        }
+        let mut entry = unpack(data);
+        entry.score = score_from_table(entry.score, ply);
+        Some(entry)
+    }
+
+    pub fn store(
+        &self,
+        key: u64,
+        ply: usize,
+        depth: i32,
+        score: i32,
+        bound: Bound,
+        best_move: Option<Move>,
+    ) {
+        let slot = self.slot(key);
+        let generation = self.generation.load(Ordering::Relaxed);
+        let old_data = slot.data.load(Ordering::Relaxed);
+        let same_key = old_data != 0 && slot.key.load(Ordering::Relaxed) ^ old_data == key;
+        let mut best_move = best_move;
+
+        if old_data != 0 {
+            let old = unpack(old_data);
+            if same_key {
+                if bound != Bound::Exact && depth + 2 < old.depth {
+                    return;
+                }
+                // A fail low doesn't know a best move, so keep the old one
+                if best_move.is_none() {
+                    best_move = old.best_move;
+                }
+            } else if old.generation == generation && depth < old.depth {
+                return;
+            }
+        }
+
+        let data = pack(Entry {
+            best_move,
+            score: score_to_table(score, ply),
+            depth,
+            bound,
+            generation,
+        });
+        slot.key.store(key ^ data, Ordering::Relaxed);
+        slot.data.store(data, Ordering::Relaxed);
+    }
+}
+
+fn score_to_table(score: i32, ply: usize) -> i32 {
+    if score >= TB_BOUND {
+        score + ply as i32
+    } else if score <= -TB_BOUND {
+        score - ply as i32
+    } else {
+        score
+    }
+}
+
+fn score_from_table(score: i32, ply: usize) -> i32 {
+    if score >= TB_BOUND {
+        score - ply as i32
+    } else if score <= -TB_BOUND {
+        score + ply as i32
+    } else {
+        score
+    }
+}
+
+// Bits 0-15 move, 16-31 score, 32-39 depth, 40-41 bound, 42-49 generation and
+// bit 50 always set so a used slot is never all zero
+fn pack(entry: Entry) -> u64 {
+    // A drop goes in like a promotion, which is never from the square it's
+    // to, of the piece dropped
+    let mv = entry.best_move.map_or(0, |mv| {
+        let promotion = mv
+            .promotion
+            .or(mv.drop)
+            .map_or(0, |_type| _type.index() as u64 + 1);
+        1 << 15 | promotion << 12 | (mv.from as u64) << 6 | mv.to as u64
+    });
+    let bound = match entry.bound {
+        Bound::Exact => 0,
+        Bound::Lower => 1,
+        Bound::Upper => 2,
+    };
+    mv | (entry.score as i16 as u16 as u64) << 16
+        | (entry.depth.clamp(0, 255) as u64) << 32
+        | bound << 40
+        | (entry.generation as u64) << 42
+        | 1 << 50
+}
+
+fn unpack(data: u64) -> Entry {
+    let mv = data & 0xffff;
+    let best_move = if mv & 1 << 15 == 0 {
+        None
+    } else {
+        let promotion = ((mv >> 12) & 7) as usize;
+        let from = ((mv >> 6) & 63) as usize;
+        let to = (mv & 63) as usize;
+        let piece = if promotion == 0 {
+            None
+        } else {
+            Some(Types::ALL[promotion - 1])
+        };
+        Some(Move {
+            from,
+            to,
+            promotion: piece.filter(|_| from != to),
+            drop: piece.filter(|_| from == to),
+        })
+    };
+    let bound = match (data >> 40) & 3 {
+        0 => Bound::Exact,
+        1 => Bound::Lower,
+        _ => Bound::Upper,
+    };
+    Entry {
+        best_move,
+        score: ((data >> 16) & 0xffff) as u16 as i16 as i32,
+        depth: ((data >> 32) & 0xff) as i32,
+        bound,
+        generation: ((data >> 42) & 0xff) as u8,
+    }
+}
@@ -0,0 +1,366 @@
+pub mod table;
+
+use crate::piece::{Sides, Types};
+use crate::position::{piece_char, Move, Position};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use table::{Kind, Lookup, Table};
+
+// Syzygy endgame tablebases read from a local directory. WDL tables (.rtbw)
+// say whether a position is won, drawn or lost, DTZ tables (.rtbz) how far it
+// is to the next capture or pawn move on the way there. Tables are only read
+// the first time they're needed, and anything missing or unreadable just
+// means the probe fails and the search carries on without it.
+
+// Results counting the fifty move rule: a cursed win is a win that takes too
+// long and so is only a draw, and a blessed loss the other way around
+#[derive(Debug, PartialEq, Eq, Clone, Copy, PartialOrd, Ord)]
+pub enum Wdl {
+    Loss,
+    BlessedLoss,
+    Draw,
+    CursedWin,
+    Win,
+}
+
+impl Wdl {
+    fn from_value(value: i32) -> Wdl {
+        match value {
+            i32::MIN..=-2 => Wdl::Loss,
+            -1 => Wdl::BlessedLoss,
+            0 => Wdl::Draw,
+            1 => Wdl::CursedWin,
+            _ => Wdl::Win,
+        }
+    }
+}
+
+type Cache = RwLock<HashMap<String, Option<Arc<Table>>>>;
+
+pub struct Tablebases {
+    directory: PathBuf,
+    // File names without extension, for each kind
+    wdl_files: HashSet<String>,
+    dtz_files: HashSet<String>,
+    largest: usize,
+    wdl: Cache,
+    dtz: Cache,
+}
+
+// The material of one side the way table names spell it: "KRP"
+fn material(position: &Position, side: Sides) -> String {
+    let mut name = String::new();
+    for _type in [
+        Types::King,
+        Types::Queen,
+        Types::Rook,
+        Types::Bishop,
+        Types::Knight,
+        Types::Pawn,
+    ] {
+        for _ in 0..position.pieces(_type, side).count_ones() {
+            name.push(piece_char(_type, Sides::White));
+        }
+    }
+    name
+}
+
+// The DTZ of the move that got us to a position where the rule 50 counter was
+// just reset, going by the position's result
+fn dtz_before_zeroing(wdl: i32) -> i32 {
+    match wdl {
+        2 => 1,
+        1 => 101,
+        -1 => -101,
+        -2 => -1,
+        _ => 0,
+    }
+}
+
+fn is_zeroing(position: &Position, mv: Move) -> bool {
+    position.is_capture(mv)
+        || position.piece_at(mv.from).map(|(_type, _)| _type) == Some(Types::Pawn)
+}
+
+impl Tablebases {
+    // Looks through `directory` for table files. Only fails when the
+    // directory itself can't be read.
+    pub fn open<P: AsRef<Path>>(directory: P) -> Result<Tablebases, String> {
+        let directory = directory.as_ref().to_path_buf();
+        let entries = fs::read_dir(&directory)
+            .map_err(|error| format!("can't read {}: {}", directory.display(), error))?;
+
+        let mut tablebases = Tablebases {
+            directory,
+            wdl_files: HashSet::new(),
+            dtz_files: HashSet::new(),
+            largest: 0,
+            wdl: RwLock::new(HashMap::new()),
+            dtz: RwLock::new(HashMap::new()),
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let (stem, extension) = match (path.file_stem(), path.extension()) {
+                (Some(stem), Some(extension)) => {
+                    (stem.to_string_lossy(), extension.to_string_lossy())
+                }
+                _ => continue,
+            };
+            let pieces = stem.chars().filter(|letter| *letter != 'v').count();
+            if !stem.contains('v') || stem.starts_with('v') {
+                continue;
+            }
+            if extension == Kind::Wdl.extension() {
+                tablebases.wdl_files.insert(stem.to_string());
+                tablebases.largest = tablebases.largest.max(pieces);
+            } else if extension == Kind::Dtz.extension() {
+                tablebases.dtz_files.insert(stem.to_string());
+            }
+        }
+        Ok(tablebases)
+    }
+
+    // The most pieces, kings included, that any of our WDL tables cover
+    pub fn largest(&self) -> usize {
+        self.largest
+    }
+
+    pub fn has_dtz(&self) -> bool {
+        !self.dtz_files.is_empty()
+    }
+
+    // Whether it's worth trying to probe this position at all. Castling
+    // rights put a position outside the tables, and so does crazyhouse, where
+    // taken pieces come back.
+    pub fn covers(&self, position: &Position) -> bool {
+        position.castling == 0
+            && !position.crazyhouse
+            && position.all().count_ones() as usize <= self.largest
+    }
+
+    fn table(&self, position: &Position, kind: Kind) -> Option<Arc<Table>> {
+        let (files, cache) = match kind {
+            Kind::Wdl => (&self.wdl_files, &self.wdl),
+            Kind::Dtz => (&self.dtz_files, &self.dtz),
+        };
+        let white = material(position, Sides::White);
+        let black = material(position, Sides::Black);
+        let name = vec![
+            format!("{}v{}", white, black),
+            format!("{}v{}", black, white),
+        ]
+        .into_iter()
+        .find(|name| files.contains(name))?;
+
+        if let Some(table) = cache.read().ok()?.get(&name) {
+            return table.clone();
+        }
+        let path = self
+            .directory
+            .join(format!("{}.{}", name, kind.extension()));
+        let table = fs::read(path)
+            .ok()
+            .and_then(|bytes| Table::new(bytes, &name, kind))
+            .map(Arc::new);
+        cache.write().ok()?.insert(name, table.clone());
+        table
+    }
+
+    fn probe_table(&self, position: &Position, kind: Kind, wdl: i32) -> Option<Lookup> {
+        if position.all().count_ones() == 2 {
+            return Some(Lookup::Value(0));
+        }
+        self.table(position, kind)?.probe(position, wdl)
+    }
+
+    // Win, draw or loss for the side to move
+    pub fn probe_wdl(&self, position: &mut Position) -> Option<Wdl> {
+        if !self.covers(position) {
+            return None;
+        }
+        self.resolve(position, false)
+            .map(|(wdl, _)| Wdl::from_value(wdl))
+    }
+
+    // The tables leave out positions where a capture wins, or where the best
+    // a capture does is as good as the stored value, so every capture has to
+    // be tried as well. With `zeroing` pawn moves are tried too, as DTZ
+    // tables don't store positions where they win. Also says whether the
+    // best result comes from one of those moves.
+    fn resolve(&self, position: &mut Position, zeroing: bool) -> Option<(i32, bool)> {
+        let moves = position.legal_moves();
+        let total = moves.len();
+        let mut tried = 0;
+        let mut best = -2;
+        for mv in moves {
+            let pawn = position.piece_at(mv.from).map(|(_type, _)| _type) == Some(Types::Pawn);
+            let wanted = position.is_capture(mv) || (zeroing && pawn);
+            if !wanted {
+                continue;
+            }
+            tried += 1;
+            position.make_move(mv);
+            let value = self.resolve(position, false);
+            position.unmake_move();
+            let value = -value?.0;
+            if value > best {
+                best = value;
+                if value >= 2 {
+                    return Some((value, true));
+                }
+            }
+        }
+
+        // When every move is a capture there's nothing left to look up, and
+        // the tables don't store en passant rights anyway
+        let nothing_else = tried > 0 && tried == total;
+        let value = if nothing_else {
+            best
+        } else {
+            match self.probe_table(position, Kind::Wdl, 0)? {
+                Lookup::Value(value) => value,
+                Lookup::OtherSideToMove => return None,
+            }
+        };
+        if best >= value {
+            return Some((best, best > 0 || nothing_else));
+        }
+        Some((value, false))
+    }
+
+    // Plies to the next capture or pawn move on the way to the result, with
+    // the sign of the result: positive when winning, negative when losing
+    // and 0 for a draw. Off by 100 for cursed wins and blessed losses.
+    pub fn probe_dtz(&self, position: &mut Position) -> Option<i32> {
+        if !self.covers(position) {
+            return None;
+        }
+        let (wdl, zeroing_best) = self.resolve(position, true)?;
+        if wdl == 0 {
+            return Some(0);
+        }
+        if zeroing_best {
+            return Some(dtz_before_zeroing(wdl));
+        }
+
+        match self.probe_table(position, Kind::Dtz, wdl)? {
+            Lookup::Value(dtz) => {
+                let cursed = if wdl.abs() == 1 { 100 } else { 0 };
+                Some((dtz + cursed) * wdl.signum())
+            }
+            // Only stored for the other side to move, so go one move deeper
+            // and take the best of the replies
+            Lookup::OtherSideToMove => {
+                let mut best = i32::MAX;
+                for mv in position.legal_moves() {
+                    let zeroing = is_zeroing(position, mv);
+                    position.make_move(mv);
+                    let dtz = if zeroing {
+                        self.resolve(position, false)
+                            .map(|(wdl, _)| -dtz_before_zeroing(wdl))
+                    } else {
+                        self.probe_dtz(position).map(|dtz| -dtz)
+                    };
+                    let mates = position.in_check() && position.legal_moves().is_empty();
+                    position.unmake_move();
+
+                    let mut dtz = dtz?;
+                    if dtz == 1 && mates {
+                        best = 1;
+                    }
+                    if !zeroing {
+                        dtz += dtz.signum();
+                    }
+                    if dtz < best && dtz.signum() == wdl.signum() {
+                        best = dtz;
+                    }
+                }
+                Some(if best == i32::MAX { -1 } else { best })
+            }
+        }
+    }
+
+    // The root moves that keep the best result the tables know of. With DTZ
+    // tables a won position only keeps the moves that get to the next
+    // capture or pawn move fastest, so the win can't be frittered away under
+    // the fifty move rule, and a lost one the moves that hold out longest.
+    // Without DTZ tables it's every move that keeps the WDL result.
+    pub fn root_moves(&self, position: &mut Position) -> Option<Vec<Move>> {
+        if !self.covers(position) {
+            return None;
+        }
+        let ranked = if self.has_dtz() {
+            self.rank_by_dtz(position)
+                .or_else(|| self.rank_by_wdl(position))?
+        } else {
+            self.rank_by_wdl(position)?
+        };
+        let best = ranked.iter().map(|(_, rank)| *rank).max()?;
+        Some(
+            ranked
+                .into_iter()
+                .filter(|(_, rank)| *rank == best)
+                .map(|(mv, _)| mv)
+                .collect(),
+        )
+    }
+
+    fn rank_by_wdl(&self, position: &mut Position) -> Option<Vec<(Move, i32)>> {
+        let mut ranked = Vec::new();
+        for mv in position.legal_moves() {
+            position.make_move(mv);
+            let wdl = self.resolve(position, false);
+            position.unmake_move();
+            ranked.push((mv, -wdl?.0));
+        }
+        Some(ranked)
+    }
+
+    fn rank_by_dtz(&self, position: &mut Position) -> Option<Vec<(Move, i32)>> {
+        let rule50 = position.halfmove_clock as i32;
+        let mut ranked = Vec::new();
+        for mv in position.legal_moves() {
+            position.make_move(mv);
+            let dtz = if position.halfmove_clock == 0 {
+                self.resolve(position, false)
+                    .map(|(wdl, _)| dtz_before_zeroing(-wdl))
+            } else if position.is_draw() {
+                Some(0)
+            } else {
+                self.probe_dtz(position).map(|dtz| match -dtz {
+                    dtz if dtz > 0 => dtz + 1,
+                    dtz if dtz < 0 => dtz - 1,
+                    dtz => dtz,
+                })
+            };
+            let mates = position.in_check() && position.legal_moves().is_empty();
+            position.unmake_move();
+
+            let mut dtz = dtz?;
+            if mates && dtz == 2 {
+                dtz = 1;
+            }
+            // Wins the fifty move rule lets us keep beat wins it doesn't,
+            // which beat draws, and the other way around for losses
+            let rank = if dtz > 0 {
+                if dtz + rule50 <= 99 {
+                    2000 - dtz
+                } else {
+                    1000 - dtz
+                }
+            } else if dtz < 0 {
+                if -dtz * 2 + rule50 < 100 {
+                    -2000 - dtz
+                } else {
+                    -1000 - dtz
+                }
+            } else {
+                0
+            };
+            ranked.push((mv, rank));
+        }
+        Some(ranked)
+    }
+}
@@ -0,0 +1,895 @@
+use crate::bitboard;
+use crate::piece::{Sides, Types};
+use crate::position::Position;
+use std::sync::OnceLock;
+
+// One Syzygy table file, read into memory. The format stores every position
+// of a material combination as an index into a sequence of values, cut into
+// blocks of Huffman coded, recursively paired symbols. Indexing follows the
+// generator exactly, down to the order pieces are sorted in, so most of what
+// is here is reproducing that order.
+
+const WDL_MAGIC: [u8; 4] = [0x71, 0xe8, 0x23, 0x5d];
+const DTZ_MAGIC: [u8; 4] = [0xd7, 0x66, 0x0c, 0xa5];
+
+// Flags stored with each table section
+const STM: u8 = 1;
+const MAPPED: u8 = 2;
+const WIN_PLIES: u8 = 4;
+const LOSS_PLIES: u8 = 8;
+const WIDE: u8 = 16;
+const SINGLE_VALUE: u8 = 128;
+
+const MAX_PIECES: usize = 7;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Kind {
+    Wdl,
+    Dtz,
+}
+
+impl Kind {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Kind::Wdl => "rtbw",
+            Kind::Dtz => "rtbz",
+        }
+    }
+}
+
+// What looking a position up gives back
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Lookup {
+    Value(i32),
+    // DTZ tables only store one side to move, and it isn't this one
+    OtherSideToMove,
+}
+
+// The square mappings the generator uses, worked out once
+pub struct Indices {
+    // a2-h7 to 0..47, highest for the pawn that leads: nearest the edge and
+    // then lowest
+    pub map_pawns: [usize; 64],
+    // Squares below the a1-h8 diagonal to 0..27
+    pub map_b1h1h7: [usize; 64],
+    // The a1-d1-d4 triangle to 0..9, diagonal squares last
+    pub map_a1d1d4: [usize; 64],
+    // Every legal placement of two kings with the first in the triangle,
+    // 462 of them
+    pub map_kk: [[usize; 64]; 10],
+    // binomial[k][n] ways to choose k squares out of n
+    pub binomial: [[u64; 64]; MAX_PIECES],
+    pub lead_pawn_idx: [[u64; 64]; 6],
+    pub lead_pawns_size: [[u64; 4]; 6],
+}
+
+fn off_diagonal(square: usize) -> i32 {
+    bitboard::rank_of(square) as i32 - bitboard::file_of(square) as i32
+}
+
+fn flip_diagonal(square: usize) -> usize {
+    ((square >> 3) | (square << 3)) & 63
+}
+
+impl Indices {
+    fn new() -> Indices {
+        let mut map_b1h1h7 = [0; 64];
+        let mut code = 0;
+        for (square, entry) in map_b1h1h7.iter_mut().enumerate() {
+            if off_diagonal(square) < 0 {
+                *entry = code;
+                code += 1;
+            }
+        }
+
+        let mut map_a1d1d4 = [0; 64];
+        let mut diagonal = Vec::new();
+        code = 0;
+        for (square, entry) in map_a1d1d4.iter_mut().enumerate().take(28) {
+            if off_diagonal(square) < 0 && bitboard::file_of(square) <= 3 {
+                *entry = code;
+                code += 1;
+            } else if off_diagonal(square) == 0 && bitboard::file_of(square) <= 3 {
+                diagonal.push(square);
+            }
+        }
+        for square in diagonal {
+            map_a1d1d4[square] = code;
+            code += 1;
+        }
+
+        // Both kings on the diagonal come last. The first king's square 0 is
+        // b1, every other square that maps to 0 isn't in the triangle.
+        let mut map_kk = [[0; 64]; 10];
+        let mut both_on_diagonal = Vec::new();
+        code = 0;
+        for (index, row) in map_kk.iter_mut().enumerate() {
+            for (first, mapped) in map_a1d1d4.iter().enumerate().take(28) {
+                if *mapped != index || (index == 0 && first != 1) {
+                    continue;
+                }
+                for (second, entry) in row.iter_mut().enumerate() {
+                    let touching = bitboard::king_attacks(first) | bitboard::bit(first);
+                    if touching & bitboard::bit(second) != 0 {
+                        continue;
+                    }
+                    if off_diagonal(first) == 0 && off_diagonal(second) > 0 {
+                        continue;
+                    }
+                    if off_diagonal(first) == 0 && off_diagonal(second) == 0 {
+                        both_on_diagonal.push((index, second));
+                    } else {
+                        *entry = code;
+                        code += 1;
+                    }
+                }
+            }
+        }
+        for (index, second) in both_on_diagonal {
+            map_kk[index][second] = code;
+            code += 1;
+        }
+
+        let mut binomial = [[0; 64]; MAX_PIECES];
+        binomial[0][0] = 1;
+        for n in 1..64 {
+            for k in 0..MAX_PIECES.min(n + 1) {
+                let with = if k > 0 { binomial[k - 1][n - 1] } else { 0 };
+                let without = if k < n { binomial[k][n - 1] } else { 0 };
+                binomial[k][n] = with + without;
+            }
+        }
+
+        let mut map_pawns = [0; 64];
+        let mut lead_pawn_idx = [[0; 64]; 6];
+        let mut lead_pawns_size = [[0; 4]; 6];
+        let mut available: usize = 47;
+        for lead_pawns in 1..=5 {
+            for (file, size) in lead_pawns_size[lead_pawns].iter_mut().enumerate() {
+                let mut index = 0;
+                for rank in 1..=6 {
+                    let square = rank * 8 + file;
+                    if lead_pawns == 1 {
+                        map_pawns[square] = available;
+                        map_pawns[square ^ 7] = available.saturating_sub(1);
+                        available = available.saturating_sub(2);
+                    }
+                    lead_pawn_idx[lead_pawns][square] = index;
+                    index += binomial[lead_pawns - 1][map_pawns[square]];
+                }
+                *size = index;
+            }
+        }
+
+        Indices {
+            map_pawns,
+            map_b1h1h7,
+            map_a1d1d4,
+            map_kk,
+            binomial,
+            lead_pawn_idx,
+            lead_pawns_size,
+        }
+    }
+}
+
+pub fn indices() -> &'static Indices {
+    static INDICES: OnceLock<Indices> = OnceLock::new();
+    INDICES.get_or_init(Indices::new)
+}
+
+// The generator's piece codes: 1 to 6 for White's pawn, knight, bishop, rook,
+// queen and king, plus 8 for Black's
+fn piece_code(_type: Types, side: Sides) -> u8 {
+    let code = match _type {
+        Types::Pawn => 1,
+        Types::Knight => 2,
+        Types::Bishop => 3,
+        Types::Rook => 4,
+        Types::Queen => 5,
+        Types::King => 6,
+    };
+    match side {
+        Sides::White => code,
+        Sides::Black => code | 8,
+    }
+}
+
+// Everything needed to decode one section of a table: one per side to move
+// and, with pawns, per file of the leading pawn
+#[derive(Debug, Clone, Default)]
+struct PairsData {
+    flags: u8,
+    max_sym_len: u8,
+    // For a single value section, the value itself
+    min_sym_len: u8,
+    num_blocks: usize,
+    block_size: usize,
+    span: usize,
+    lowest_sym: usize,
+    btree: usize,
+    block_length: usize,
+    block_length_size: usize,
+    sparse_index: usize,
+    sparse_index_size: usize,
+    data: usize,
+    base64: Vec<u64>,
+    // How many values, less one, each symbol stands for
+    symlen: Vec<u8>,
+    pieces: [u8; MAX_PIECES],
+    group_idx: [u64; MAX_PIECES + 1],
+    group_len: [usize; MAX_PIECES + 1],
+    // Where the DTZ value maps for win, loss, cursed win and blessed loss
+    // start in the file
+    map_start: [usize; 4],
+}
+
+pub struct Table {
+    bytes: Vec<u8>,
+    kind: Kind,
+    // The material the file name gives White, e.g. "KR" for KRvK
+    white: String,
+    symmetric: bool,
+    piece_count: usize,
+    has_pawns: bool,
+    has_unique_pieces: bool,
+    // Pawns of the side whose pawns lead, then the other side's
+    pawn_count: [usize; 2],
+    // [side to move][file]
+    items: Vec<Vec<PairsData>>,
+}
+
+fn parse_material(material: &str) -> Option<Vec<Types>> {
+    material
+        .chars()
+        .map(|letter| match letter {
+            'K' => Some(Types::King),
+            'Q' => Some(Types::Queen),
+            'R' => Some(Types::Rook),
+            'B' => Some(Types::Bishop),
+            'N' => Some(Types::Knight),
+            'P' => Some(Types::Pawn),
+            _ => None,
+        })
+        .collect()
+}
+
+impl Table {
+    // `name` is the file name without its extension, like "KRPvKR". Gives
+    // up on anything that doesn't look like a table.
+    pub fn new(bytes: Vec<u8>, name: &str, kind: Kind) -> Option<Table> {
+        let magic = match kind {
+            Kind::Wdl => WDL_MAGIC,
+            Kind::Dtz => DTZ_MAGIC,
+        };
+        if bytes.len() < 6 || bytes[..4] != magic {
+            return None;
+        }
+
+        let (white_name, black_name) = name.split_once('v')?;
+        let white = parse_material(white_name)?;
+        let black = parse_material(black_name)?;
+        let piece_count = white.len() + black.len();
+        if !(3..=MAX_PIECES).contains(&piece_count) {
+            return None;
+        }
+        let count =
+            |pieces: &[Types], _type: Types| pieces.iter().filter(|piece| **piece == _type).count();
+        let has_pawns = count(&white, Types::Pawn) + count(&black, Types::Pawn) > 0;
+        let has_unique_pieces = [&white, &black].iter().any(|pieces| {
+            Types::ALL
+                .iter()
+                .any(|_type| *_type != Types::King && count(pieces, *_type) == 1)
+        });
+        // The side with fewer pawns leads, as long as it has some
+        let (white_pawns, black_pawns) = (count(&white, Types::Pawn), count(&black, Types::Pawn));
+        let white_leads = black_pawns == 0 || (white_pawns > 0 && black_pawns >= white_pawns);
+        let pawn_count = if white_leads {
+            [white_pawns, black_pawns]
+        } else {
+            [black_pawns, white_pawns]
+        };
+
+        let mut table = Table {
+            bytes,
+            kind,
+            white: white_name.to_string(),
+            symmetric: white_name == black_name,
+            piece_count,
+            has_pawns,
+            has_unique_pieces,
+            pawn_count,
+            items: Vec::new(),
+        };
+        let flags = table.bytes[4];
+        if (flags & 2 != 0) != has_pawns || (flags & 1 != 0) == table.symmetric {
+            return None;
+        }
+        table.read_header()?;
+        Some(table)
+    }
+
+    fn byte(&self, offset: usize) -> u8 {
+        self.bytes.get(offset).copied().unwrap_or(0)
+    }
+
+    fn u16_le(&self, offset: usize) -> u16 {
+        u16::from_le_bytes([self.byte(offset), self.byte(offset + 1)])
+    }
+
+    fn u32_le(&self, offset: usize) -> u32 {
+        u32::from_le_bytes([
+            self.byte(offset),
+            self.byte(offset + 1),
+            self.byte(offset + 2),
+            self.byte(offset + 3),
+        ])
+    }
+
+    fn u32_be(&self, offset: usize) -> u32 {
+        self.u32_le(offset).swap_bytes()
+    }
+
+    fn u64_be(&self, offset: usize) -> u64 {
+        (self.u32_be(offset) as u64) << 32 | self.u32_be(offset + 4) as u64
+    }
+
+    fn get(&self, stm: usize, file: usize) -> &PairsData {
+        let file = if self.has_pawns { file } else { 0 };
+        &self.items[stm % self.items.len()][file]
+    }
+
+    fn read_header(&mut self) -> Option<()> {
+        let sides = if self.kind == Kind::Wdl && !self.symmetric {
+            2
+        } else {
+            1
+        };
+        let files = if self.has_pawns { 4 } else { 1 };
+        let both_have_pawns = self.has_pawns && self.pawn_count[1] > 0;
+        let mut items = vec![vec![PairsData::default(); files]; sides];
+
+        let mut offset = 5;
+        for file in 0..files {
+            let first = self.byte(offset);
+            let second = if both_have_pawns {
+                self.byte(offset + 1)
+            } else {
+                0xff
+            };
+            let order = [
+                [(first & 0xf) as usize, (second & 0xf) as usize],
+                [(first >> 4) as usize, (second >> 4) as usize],
+            ];
+            offset += 1 + both_have_pawns as usize;
+
+            for k in 0..self.piece_count {
+                let byte = self.byte(offset);
+                for (side, side_items) in items.iter_mut().enumerate() {
+                    side_items[file].pieces[k] = if side == 1 { byte >> 4 } else { byte & 0xf };
+                }
+                offset += 1;
+            }
+
+            for (side, side_items) in items.iter_mut().enumerate() {
+                self.set_groups(&mut side_items[file], order[side], file)?;
+            }
+        }
+        offset += offset & 1;
+
+        for file in 0..files {
+            for side_items in items.iter_mut() {
+                offset = self.set_sizes(&mut side_items[file], offset)?;
+            }
+        }
+
+        if self.kind == Kind::Dtz {
+            for d in items[0].iter_mut() {
+                if d.flags & MAPPED == 0 {
+                    continue;
+                }
+                if d.flags & WIDE != 0 {
+                    offset += offset & 1;
+                    for start in d.map_start.iter_mut() {
+                        *start = offset + 2;
+                        offset += 2 * self.u16_le(offset) as usize + 2;
+                    }
+                } else {
+                    for start in d.map_start.iter_mut() {
+                        *start = offset + 1;
+                        offset += self.byte(offset) as usize + 1;
+                    }
+                }
+            }
+            offset += offset & 1;
+        }
+
+        for file in 0..files {
+            for side_items in items.iter_mut() {
+                let d = &mut side_items[file];
+                d.sparse_index = offset;
+                offset += d.sparse_index_size * 6;
+            }
+        }
+        for file in 0..files {
+            for side_items in items.iter_mut() {
+                let d = &mut side_items[file];
+                d.block_length = offset;
+                offset += d.block_length_size * 2;
+            }
+        }
+        for file in 0..files {
+            for side_items in items.iter_mut() {
+                let d = &mut side_items[file];
+                offset = (offset + 0x3f) & !0x3f;
+                d.data = offset;
+                offset += d.num_blocks * d.block_size;
+            }
+        }
+        if offset > self.bytes.len() {
+            return None;
+        }
+
+        self.items = items;
+        Some(())
+    }
+
+    // Pieces of the same type and colour are encoded together as a group,
+    // apart from the leading group, which is the first three pieces without
+    // pawns (or just the kings when no piece is on its own), or the leading
+    // pawns. `order` says in which order the groups make up the index.
+    fn set_groups(&self, d: &mut PairsData, order: [usize; 2], file: usize) -> Option<()> {
+        let indices = indices();
+        let mut n = 0;
+        let mut first_len: i32 = if self.has_pawns {
+            0
+        } else if self.has_unique_pieces {
+            3
+        } else {
+            2
+        };
+        d.group_len[0] = 1;
+        for i in 1..self.piece_count {
+            first_len -= 1;
+            if first_len > 0 || d.pieces[i] == d.pieces[i - 1] {
+                d.group_len[n] += 1;
+            } else {
+                n += 1;
+                d.group_len[n] = 1;
+            }
+        }
+        n += 1;
+        d.group_len[n] = 0;
+
+        let both_have_pawns = self.has_pawns && self.pawn_count[1] > 0;
+        let mut next = if both_have_pawns { 2 } else { 1 };
+        let mut free_squares =
+            64 - d.group_len[0] - if both_have_pawns { d.group_len[1] } else { 0 };
+        let mut index: u64 = 1;
+        let mut k = 0;
+        while next < n || k == order[0] || k == order[1] {
+            if k == order[0] {
+                d.group_idx[0] = index;
+                index *= if self.has_pawns {
+                    *indices.lead_pawns_size.get(d.group_len[0])?.get(file)?
+                } else if self.has_unique_pieces {
+                    31332
+                } else {
+                    462
+                };
+            } else if k == order[1] {
+                d.group_idx[1] = index;
+                index *= indices.binomial.get(d.group_len[1])?[48 - d.group_len[0]];
+            } else {
+                d.group_idx[next] = index;
+                index *= indices.binomial.get(d.group_len[next])?[free_squares];
+                free_squares -= d.group_len[next];
+                next += 1;
+            }
+            k += 1;
+            if k > 16 {
+                return None;
+            }
+        }
+        d.group_idx[n] = index;
+        Some(())
+    }
+
+    fn set_sizes(&self, d: &mut PairsData, mut offset: usize) -> Option<usize> {
+        d.flags = self.byte(offset);
+        offset += 1;
+        if d.flags & SINGLE_VALUE != 0 {
+            d.min_sym_len = self.byte(offset);
+            return Some(offset + 1);
+        }
+
+        let groups = d.group_len.iter().position(|len| *len == 0)?;
+        let size = d.group_idx[groups] as usize;
+        d.block_size = 1 << self.byte(offset);
+        d.span = 1 << self.byte(offset + 1);
+        d.sparse_index_size = size.div_ceil(d.span);
+        let padding = self.byte(offset + 2) as usize;
+        d.num_blocks = self.u32_le(offset + 3) as usize;
+        d.block_length_size = d.num_blocks + padding;
+        d.max_sym_len = self.byte(offset + 7);
+        d.min_sym_len = self.byte(offset + 8);
+        offset += 9;
+        if d.min_sym_len == 0 || d.max_sym_len < d.min_sym_len || d.max_sym_len > 32 {
+            return None;
+        }
+        d.lowest_sym = offset;
+
+        // Canonical Huffman codes: longer codes have lower values, so the
+        // lowest code of each length, padded out to 64 bits, tells us the
+        // length of whatever code comes next in the stream
+        let lengths = (d.max_sym_len - d.min_sym_len + 1) as usize;
+        d.base64 = vec![0; lengths];
+        for i in (0..lengths - 1).rev() {
+            let lowest = self.u16_le(d.lowest_sym + 2 * i) as u64;
+            let next_lowest = self.u16_le(d.lowest_sym + 2 * (i + 1)) as u64;
+            d.base64[i] = (d.base64[i + 1] + lowest).wrapping_sub(next_lowest) / 2;
+        }
+        for (i, base) in d.base64.iter_mut().enumerate() {
+            let shift = 64 - i as u32 - d.min_sym_len as u32;
+            *base = base.checked_shl(shift).unwrap_or(0);
+        }
+        offset += lengths * 2;
+
+        let symbols = self.u16_le(offset) as usize;
+        offset += 2;
+        d.btree = offset;
+        d.symlen = vec![0; symbols];
+        let mut visited = vec![false; symbols];
+        for symbol in 0..symbols {
+            if !visited[symbol] {
+                d.symlen[symbol] = self.set_symlen(d, symbol, &mut visited)?;
+            }
+        }
+        Some(offset + symbols * 3 + (symbols & 1))
+    }
+
+    fn left(&self, d: &PairsData, symbol: usize) -> usize {
+        let at = d.btree + 3 * symbol;
+        ((self.byte(at + 1) as usize & 0xf) << 8) | self.byte(at) as usize
+    }
+
+    fn right(&self, d: &PairsData, symbol: usize) -> usize {
+        let at = d.btree + 3 * symbol;
+        ((self.byte(at + 2) as usize) << 4) | (self.byte(at + 1) as usize >> 4)
+    }
+
+    // Every symbol is either a value or a pair of other symbols, so how many
+    // values it stands for comes from expanding it all the way down
+    fn set_symlen(&self, d: &mut PairsData, symbol: usize, visited: &mut [bool]) -> Option<u8> {
+        visited[symbol] = true;
+        let right = self.right(d, symbol);
+        if right == 0xfff {
+            return Some(0);
+        }
+        let left = self.left(d, symbol);
+        if left >= visited.len() || right >= visited.len() {
+            return None;
+        }
+        if !visited[left] {
+            d.symlen[left] = self.set_symlen(d, left, visited)?;
+        }
+        if !visited[right] {
+            d.symlen[right] = self.set_symlen(d, right, visited)?;
+        }
+        Some(d.symlen[left].wrapping_add(d.symlen[right]).wrapping_add(1))
+    }
+
+    // The value stored at `index`
+    fn decompress(&self, d: &PairsData, index: u64) -> Option<i32> {
+        if d.flags & SINGLE_VALUE != 0 {
+            return Some(d.min_sym_len as i32);
+        }
+
+        // The sparse index says which block roughly every span'th value is
+        // in, and where in it. From there we walk to the right block.
+        let index = index as usize;
+        let k = index / d.span;
+        if k >= d.sparse_index_size {
+            return None;
+        }
+        let entry = d.sparse_index + 6 * k;
+        let mut block = self.u32_le(entry) as usize;
+        let mut offset = self.u16_le(entry + 4) as i64;
+        offset += (index % d.span) as i64 - (d.span / 2) as i64;
+
+        let block_length = |block: usize| self.u16_le(d.block_length + 2 * block) as i64;
+        while offset < 0 {
+            block = block.checked_sub(1)?;
+            offset += block_length(block) + 1;
+        }
+        while offset > block_length(block) {
+            offset -= block_length(block) + 1;
+            block += 1;
+            if block >= d.block_length_size {
+                return None;
+            }
+        }
+        if block >= d.num_blocks {
+            return None;
+        }
+
+        // Read symbols off the start of the block until we get to the one
+        // that covers our value
+        let mut at = d.data + block * d.block_size;
+        let mut buffer = self.u64_be(at);
+        at += 8;
+        let mut buffered = 64;
+        let mut symbol;
+        loop {
+            let mut len = 0;
+            while buffer < d.base64[len] {
+                len += 1;
+                if len == d.base64.len() {
+                    return None;
+                }
+            }
+            let shift = 64 - len as u32 - d.min_sym_len as u32;
+            symbol = ((buffer - d.base64[len]) >> shift) as usize;
+            symbol += self.u16_le(d.lowest_sym + 2 * len) as usize;
+            if symbol >= d.symlen.len() {
+                return None;
+            }
+            if offset < d.symlen[symbol] as i64 + 1 {
+                break;
+            }
+            offset -= d.symlen[symbol] as i64 + 1;
+            let bits = len as u32 + d.min_sym_len as u32;
+            buffer <<= bits;
+            buffered -= bits;
+            if buffered <= 32 {
+                buffered += 32;
+                buffer |= (self.u32_be(at) as u64) << (64 - buffered);
+                at += 4;
+            }
+        }
+
+        // Then expand it, going left or right depending on which half our
+        // value falls in
+        while d.symlen[symbol] != 0 {
+            let left = self.left(d, symbol);
+            if left >= d.symlen.len() {
+                return None;
+            }
+            if offset < d.symlen[left] as i64 + 1 {
+                symbol = left;
+            } else {
+                offset -= d.symlen[left] as i64 + 1;
+                symbol = self.right(d, symbol);
+                if symbol >= d.symlen.len() {
+                    return None;
+                }
+            }
+        }
+        Some(self.left(d, symbol) as i32)
+    }
+
+    // DTZ values are stored remapped by how common they are, and in moves
+    // rather than plies where that loses nothing. `wdl` says which map.
+    fn map_score(&self, file: usize, value: i32, wdl: i32) -> i32 {
+        if self.kind == Kind::Wdl {
+            return value - 2;
+        }
+
+        const WDL_MAP: [usize; 5] = [1, 3, 0, 2, 0];
+        let d = self.get(0, file);
+        let mut value = value;
+        if d.flags & MAPPED != 0 {
+            let start = d.map_start[WDL_MAP[(wdl + 2) as usize]];
+            value = if d.flags & WIDE != 0 {
+                self.u16_le(start + 2 * value as usize) as i32
+            } else {
+                self.byte(start + value as usize) as i32
+            };
+        }
+        if (wdl == 2 && d.flags & WIN_PLIES == 0)
+            || (wdl == -2 && d.flags & LOSS_PLIES == 0)
+            || wdl == 1
+            || wdl == -1
+        {
+            value *= 2;
+        }
+        value + 1
+    }
+
+    // Looks the position up. It has to have exactly this table's material,
+    // with either colour being the one the file name puts first. For DTZ
+    // tables `wdl` is the position's result, which picks the value map.
+    pub fn probe(&self, position: &Position, wdl: i32) -> Option<Lookup> {
+        let indices = indices();
+        let mut white = String::new();
+        for _type in [
+            Types::King,
+            Types::Queen,
+            Types::Rook,
+            Types::Bishop,
+            Types::Knight,
+            Types::Pawn,
+        ] {
+            for _ in 0..position.pieces(_type, Sides::White).count_ones() {
+                white.push(crate::position::piece_char(_type, Sides::White));
+            }
+        }
+
+        // Tables are stored with the first named side as White, and only
+        // with White to move when both sides have the same pieces. Anything
+        // else gets its colours swapped and the board flipped.
+        let symmetric_black_to_move = self.symmetric && position.side_to_move == Sides::Black;
+        let flip = symmetric_black_to_move || white != self.white;
+        let flip_color = if flip { 8 } else { 0 };
+        let flip_squares = if flip { 56 } else { 0 };
+        let stm = flip as usize ^ position.side_to_move.index();
+
+        let mut squares = [0; MAX_PIECES];
+        let mut pieces = [0; MAX_PIECES];
+        let mut size = 0;
+        let mut lead_pawns = 0;
+        let mut file = 0;
+        if self.has_pawns {
+            let lead = self.items[0][0].pieces[0] ^ flip_color;
+            let side = if lead & 8 != 0 {
+                Sides::Black
+            } else {
+                Sides::White
+            };
+            lead_pawns = position.pieces(Types::Pawn, side);
+            for square in bitboard::squares(lead_pawns) {
+                squares[size] = square ^ flip_squares;
+                size += 1;
+            }
+            if size == 0 {
+                return None;
+            }
+            let leader = (0..size).fold(0, |best, i| {
+                if indices.map_pawns[squares[i]] > indices.map_pawns[squares[best]] {
+                    i
+                } else {
+                    best
+                }
+            });
+            squares.swap(0, leader);
+            let leader_file = bitboard::file_of(squares[0]);
+            file = leader_file.min(7 - leader_file);
+        }
+        let lead_count = size;
+
+        if self.kind == Kind::Dtz {
+            let flags = self.get(stm, file).flags;
+            let both_sides_stored = self.symmetric && !self.has_pawns;
+            if (flags & STM) as usize != stm && !both_sides_stored {
+                return Some(Lookup::OtherSideToMove);
+            }
+        }
+
+        for square in bitboard::squares(position.all() ^ lead_pawns) {
+            if size == MAX_PIECES {
+                return None;
+            }
+            let (_type, side) = position.piece_at(square)?;
+            squares[size] = square ^ flip_squares;
+            pieces[size] = piece_code(_type, side) ^ flip_color;
+            size += 1;
+        }
+        if size != self.piece_count {
+            return None;
+        }
+
+        // Put the pieces in the order the table lists them in
+        let d = self.get(stm, file);
+        for i in lead_count..size.saturating_sub(1) {
+            for j in i + 1..size {
+                if d.pieces[i] == pieces[j] {
+                    pieces.swap(i, j);
+                    squares.swap(i, j);
+                    break;
+                }
+            }
+        }
+
+        // Mirror so the leading piece is on files a to d
+        if bitboard::file_of(squares[0]) > 3 {
+            for square in squares[..size].iter_mut() {
+                *square ^= 7;
+            }
+        }
+
+        let mut index;
+        if self.has_pawns {
+            index = indices.lead_pawn_idx[lead_count][squares[0]];
+            squares[1..lead_count].sort_by_key(|square| indices.map_pawns[*square]);
+            for (i, square) in squares.iter().enumerate().take(lead_count).skip(1) {
+                index += indices.binomial[i][indices.map_pawns[*square]];
+            }
+        } else {
+            // Without pawns the board can also be flipped top to bottom and
+            // along the diagonal, to get the leading piece into the a1-d1-d4
+            // triangle and the first one off the diagonal below it
+            if bitboard::rank_of(squares[0]) > 3 {
+                for square in squares[..size].iter_mut() {
+                    *square ^= 56;
+                }
+            }
+            for i in 0..d.group_len[0] {
+                let off = off_diagonal(squares[i]);
+                if off == 0 {
+                    continue;
+                }
+                if off > 0 {
+                    for square in squares[i..size].iter_mut() {
+                        *square = flip_diagonal(*square);
+                    }
+                }
+                break;
+            }
+
+            if self.has_unique_pieces {
+                index = self.unique_index(&squares);
+            } else {
+                index = indices.map_kk[indices.map_a1d1d4[squares[0]]][squares[1]] as u64;
+            }
+        }
+
+        // The other groups, each as a combination of the squares the groups
+        // before it left free
+        index *= d.group_idx[0];
+        let mut start = d.group_len[0];
+        let mut remaining_pawns = self.has_pawns && self.pawn_count[1] > 0;
+        let mut next = 1;
+        while d.group_len[next] != 0 {
+            let len = d.group_len[next];
+            if start + len > size {
+                return None;
+            }
+            squares[start..start + len].sort_unstable();
+            let mut n = 0;
+            for i in 0..len {
+                let square = squares[start + i];
+                let adjust = squares[..start]
+                    .iter()
+                    .filter(|other| square > **other)
+                    .count();
+                let free = square - adjust - if remaining_pawns { 8 } else { 0 };
+                n += indices.binomial[i + 1][free];
+            }
+            remaining_pawns = false;
+            index += n * d.group_idx[next];
+            start += len;
+            next += 1;
+        }
+
+        let value = self.decompress(d, index)?;
+        Some(Lookup::Value(self.map_score(file, value, wdl)))
+    }
+
+    // The first three pieces together when they're all different
+    fn unique_index(&self, squares: &[usize; MAX_PIECES]) -> u64 {
+        let indices = indices();
+        let [first, second, third] = [squares[0], squares[1], squares[2]];
+        let adjust1 = (second > first) as usize;
+        let adjust2 = (third > first) as usize + (third > second) as usize;
+        let rank = bitboard::rank_of;
+        let index = if off_diagonal(first) != 0 {
+            (indices.map_a1d1d4[first] * 63 + second - adjust1) * 62 + third - adjust2
+        } else if off_diagonal(second) != 0 {
+            (6 * 63 + rank(first) * 28 + indices.map_b1h1h7[second]) * 62 + third - adjust2
+        } else if off_diagonal(third) != 0 {
+            6 * 63 * 62
+                + 4 * 28 * 62
+                + rank(first) * 7 * 28
+                + (rank(second) - adjust1) * 28
+                + indices.map_b1h1h7[third]
+        } else {
+            6 * 63 * 62
+                + 4 * 28 * 62
+                + 4 * 7 * 28
+                + rank(first) * 7 * 6
+                + (rank(second) - adjust1) * 6
+                + (rank(third) - adjust2)
+        };
+        index as u64
+    }
+}
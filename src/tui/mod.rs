@@ -0,0 +1,580 @@
+pub mod terminal;
+
+use crate::clock::{self, Clock};
+use crate::eval::Params;
+use crate::piece::{Sides, Types};
+use crate::play;
+use crate::position::{self, Move, Position};
+use crate::render::{self, RESET};
+use crate::search::background::Background;
+use crate::search::skill::Skill;
+use crate::search::{self, time, Limits, Score, Searcher};
+use std::io::{self, Write};
+use std::sync::mpsc::RecvTimeoutError;
+use std::time::{Duration, Instant};
+use terminal::Event;
+
+// Playing the engine full screen: the board with the engine's evaluation
+// beside it, the clocks and the moves so far. Pieces get moved with the
+// arrow keys or the mouse, without any notation. Everything but the terminal
+// itself is here, so the game can be driven by events and drawn to a string.
+
+// Where things go on the screen, counting lines and columns from 0
+const BOARD_TOP: usize = 2;
+const BOARD_LEFT: usize = 2;
+const CELL_WIDTH: usize = 3;
+const STATUS_LINE: usize = BOARD_TOP + 10;
+// The promotion choices, after "Promote to: " on the status line
+const PICKER_LEFT: usize = 12;
+
+const PROMOTIONS: [Types; 4] = [Types::Queen, Types::Rook, Types::Bishop, Types::Knight];
+
+// Backgrounds for the cursor, the piece picked up, and pieces it can take
+const CURSOR: u8 = 75;
+const SELECTED: u8 = 71;
+const CAPTURE: u8 = 174;
+// A dot on the empty squares it can go to
+const TARGET: &str = "\x1b[38;5;238m";
+
+// The evaluation bar, filled with White's share from White's side
+const BAR_WHITE: u8 = 255;
+const BAR_BLACK: u8 = 236;
+
+// How often the screen gets redrawn when nothing happens, for the clocks
+const REFRESH: Duration = Duration::from_millis(100);
+
+const HELP: &str = "arrows/mouse: move  enter/space: pick up, put down  \
+                    u: undo  f: flip  n: new game  q: quit";
+
+pub struct Tui {
+    position: Position,
+    // Away with the search while the engine thinks
+    searcher: Option<Searcher>,
+    search: Option<Background>,
+    limits: Limits,
+    clock: Option<Clock>,
+    // The clock as it was at the start, for a new game
+    initial_clock: Option<Clock>,
+    human: Sides,
+    flipped: bool,
+    cursor: usize,
+    // The piece picked up, and the moves it has
+    selected: Option<usize>,
+    targets: Vec<Move>,
+    // A pawn move waiting to be told what it promotes to, with the choice
+    // the picker is on
+    promotion: Option<(Move, usize)>,
+    // Every move so far in SAN
+    moves: Vec<String>,
+    // From the engine's last search, for White
+    evaluation: Option<i32>,
+    result: Option<String>,
+}
+
+// White's share of the evaluation bar, from 0 to 1
+fn share(evaluation: i32) -> f64 {
+    match Score::from(evaluation) {
+        Score::Mate(moves) if moves > 0 => 1.0,
+        Score::Mate(_) => 0.0,
+        Score::Centipawns(centipawns) => 1.0 / (1.0 + 10f64.powf(-f64::from(centipawns) / 400.0)),
+    }
+}
+
+fn background(colour: u8) -> String {
+    format!("\x1b[48;5;{}m", colour)
+}
+
+impl Tui {
+    pub fn new(human: Sides, skill: Skill, limits: Limits, clock: Option<Clock>) -> Tui {
+        let mut searcher = Searcher::new(search::Options::default());
+        searcher.set_skill(skill);
+        let mut tui = Tui {
+            position: position::start(),
+            searcher: Some(searcher),
+            search: None,
+            limits,
+            clock: None,
+            initial_clock: clock,
+            human,
+            flipped: false,
+            cursor: 0,
+            selected: None,
+            targets: Vec::new(),
+            promotion: None,
+            moves: Vec::new(),
+            evaluation: None,
+            result: None,
+        };
+        tui.new_game(Instant::now());
+        tui
+    }
+
+    // Evaluation parameters to play with. Whatever the engine was thinking
+    // about is dropped, to start again with them.
+    pub fn set_params(&mut self, params: Params) {
+        self.cancel();
+        if let Some(searcher) = self.searcher.as_mut() {
+            searcher.set_params(params);
+        }
+    }
+
+    pub fn position(&self) -> &Position {
+        &self.position
+    }
+
+    pub fn moves(&self) -> &[String] {
+        &self.moves
+    }
+
+    pub fn result(&self) -> Option<&str> {
+        self.result.as_deref()
+    }
+
+    pub fn is_thinking(&self) -> bool {
+        self.search.is_some()
+    }
+
+    fn new_game(&mut self, now: Instant) {
+        self.cancel();
+        self.position = position::start();
+        if let Some(searcher) = self.searcher.as_mut() {
+            searcher.clear();
+        }
+        self.clock = self.initial_clock.clone();
+        if let Some(clock) = self.clock.as_mut() {
+            clock.start(Sides::White, now);
+        }
+        self.flipped = self.human == Sides::Black;
+        self.cursor = match self.human {
+            Sides::White => 12,
+            Sides::Black => 52,
+        };
+        self.drop_piece();
+        self.moves.clear();
+        self.evaluation = None;
+        self.result = None;
+    }
+
+    // Stops the engine thinking, without its move
+    fn cancel(&mut self) {
+        if let Some(search) = self.search.take() {
+            let (searcher, _) = search.cancel();
+            self.searcher = Some(searcher);
+        }
+    }
+
+    fn drop_piece(&mut self) {
+        self.selected = None;
+        self.targets.clear();
+        self.promotion = None;
+    }
+
+    fn humans_turn(&self) -> bool {
+        self.result.is_none() && self.position.side_to_move == self.human
+    }
+
+    // The square shown at a row and column of the board, counting from the
+    // top left
+    fn square_at(&self, row: usize, column: usize) -> usize {
+        if self.flipped {
+            row * 8 + 7 - column
+        } else {
+            (7 - row) * 8 + column
+        }
+    }
+
+    fn place(&self, square: usize) -> (usize, usize) {
+        if self.flipped {
+            (square / 8, 7 - square % 8)
+        } else {
+            (7 - square / 8, square % 8)
+        }
+    }
+
+    fn move_cursor(&mut self, rows: isize, columns: isize) {
+        let (row, column) = self.place(self.cursor);
+        let row = (row as isize + rows).clamp(0, 7) as usize;
+        let column = (column as isize + columns).clamp(0, 7) as usize;
+        self.cursor = self.square_at(row, column);
+    }
+
+    fn play(&mut self, mv: Move, now: Instant) {
+        self.moves.push(self.position.san(mv));
+        self.position.make_move(mv);
+        self.drop_piece();
+        if let Some(ending) = self.position.ending() {
+            self.result = Some(format!("{} {{{}}}", ending.result(), ending.reason()));
+            if let Some(clock) = self.clock.as_mut() {
+                clock.stop(now);
+            }
+        } else if let Some(clock) = self.clock.as_mut() {
+            clock.press(now);
+        }
+    }
+
+    // Enter or a click on the square under the cursor: picks up one of our
+    // pieces, or puts the one we have down there
+    fn choose(&mut self, now: Instant) {
+        if !self.humans_turn() {
+            return;
+        }
+        let moves: Vec<Move> = self
+            .targets
+            .iter()
+            .copied()
+            .filter(|mv| mv.to == self.cursor)
+            .collect();
+        match moves.first() {
+            Some(mv) if mv.promotion.is_some() => self.promotion = Some((*mv, 0)),
+            Some(mv) => self.play(*mv, now),
+            None if self.selected == Some(self.cursor) => self.drop_piece(),
+            None => {
+                self.drop_piece();
+                let cursor = self.cursor;
+                if matches!(self.position.squares[cursor], Some((_, side)) if side == self.human) {
+                    self.targets = self
+                        .position
+                        .legal_moves()
+                        .into_iter()
+                        .filter(|mv| mv.from == cursor)
+                        .collect();
+                    if !self.targets.is_empty() {
+                        self.selected = Some(cursor);
+                    }
+                }
+            }
+        }
+    }
+
+    fn promote(&mut self, choice: usize, now: Instant) {
+        if let Some((mv, _)) = self.promotion {
+            let mv = Move {
+                promotion: Some(PROMOTIONS[choice]),
+                ..mv
+            };
+            self.play(mv, now);
+        }
+    }
+
+    // Takes back moves until it's our turn again, at least one of ours
+    // included
+    fn undo(&mut self, now: Instant) {
+        self.cancel();
+        self.drop_piece();
+        let mut position = self.position.clone();
+        let mut taken = 0;
+        while !position.history.is_empty() && (taken == 0 || position.side_to_move != self.human) {
+            position.unmake_move();
+            taken += 1;
+        }
+        if taken == 0 || position.side_to_move != self.human {
+            return;
+        }
+        self.position = position;
+        self.moves.truncate(self.moves.len() - taken);
+        self.result = None;
+        if let Some(clock) = self.clock.as_mut() {
+            clock.start(self.human, now);
+        }
+    }
+
+    // Handles a key or a click, returning false once it's time to quit
+    pub fn event(&mut self, event: Event, now: Instant) -> bool {
+        if let Some((mv, choice)) = self.promotion {
+            match event {
+                Event::Left => self.promotion = Some((mv, (choice + 3) % 4)),
+                Event::Right => self.promotion = Some((mv, (choice + 1) % 4)),
+                Event::Enter => self.promote(choice, now),
+                Event::Escape => self.promotion = None,
+                Event::Interrupt => return false,
+                Event::Char(c) => {
+                    if let Some(choice) = "qrbn".find(c.to_ascii_lowercase()) {
+                        self.promote(choice, now);
+                    }
+                }
+                Event::Click(row, column) => {
+                    let offset = column.wrapping_sub(1).wrapping_sub(PICKER_LEFT) / CELL_WIDTH;
+                    if row == STATUS_LINE + 1 && offset < 4 {
+                        self.promote(offset, now);
+                    } else {
+                        self.promotion = None;
+                    }
+                }
+                Event::Up | Event::Down => {}
+            }
+            return true;
+        }
+
+        match event {
+            Event::Up => self.move_cursor(-1, 0),
+            Event::Down => self.move_cursor(1, 0),
+            Event::Left => self.move_cursor(0, -1),
+            Event::Right => self.move_cursor(0, 1),
+            Event::Enter => self.choose(now),
+            Event::Escape => self.drop_piece(),
+            Event::Click(row, column) => {
+                let row = row.wrapping_sub(1).wrapping_sub(BOARD_TOP);
+                let column = column.wrapping_sub(1).wrapping_sub(BOARD_LEFT) / CELL_WIDTH;
+                if row < 8 && column < 8 {
+                    self.cursor = self.square_at(row, column);
+                    self.choose(now);
+                }
+            }
+            Event::Char('u') => self.undo(now),
+            Event::Char('f') => self.flipped = !self.flipped,
+            Event::Char('n') => self.new_game(now),
+            Event::Char('q') | Event::Interrupt => return false,
+            Event::Char(_) => {}
+        }
+        true
+    }
+
+    fn limits(&self, now: Instant) -> Limits {
+        let mut limits = self.limits;
+        if let Some(clock) = &self.clock {
+            if limits.time.is_none() && limits.depth.is_none() && limits.nodes.is_none() {
+                let side = self.position.side_to_move;
+                limits.time = Some(time::allocate(
+                    clock.remaining(side, now),
+                    clock.increment(side),
+                    clock.moves_to_go(side),
+                ));
+            }
+        }
+        limits
+    }
+
+    // Keeps the game going between events: flags fall, and the engine
+    // thinks when it's its turn and moves when it's done
+    pub fn update(&mut self, now: Instant) -> Result<(), String> {
+        if self.result.is_none() {
+            if let Some(side) = self.clock.as_ref().and_then(|clock| clock.flagged(now)) {
+                self.cancel();
+                let ending = clock::flag_fall(&self.position, side);
+                self.result = Some(format!("{} {{{}}}", ending.result(), ending.reason()));
+                if let Some(clock) = self.clock.as_mut() {
+                    clock.stop(now);
+                }
+                self.drop_piece();
+            }
+        }
+
+        if self.search.as_ref().is_some_and(Background::is_done) {
+            let (searcher, result) = self.search.take().unwrap().stop();
+            self.searcher = Some(searcher);
+            if let Some(mv) = result.best_move {
+                self.evaluation = Some(match self.position.side_to_move {
+                    Sides::White => result.score,
+                    Sides::Black => -result.score,
+                });
+                self.play(mv, now);
+            }
+        }
+
+        if self.result.is_none() && self.search.is_none() && !self.humans_turn() {
+            let searcher = self
+                .searcher
+                .take()
+                .expect("the searcher comes back when a search ends");
+            let limits = self.limits(now);
+            self.search = Some(Background::start(
+                searcher,
+                self.position.clone(),
+                limits,
+                false,
+                |_| {},
+            )?);
+        }
+        Ok(())
+    }
+
+    fn cell(&self, square: usize, last: &[usize], checked: Option<usize>) -> String {
+        let piece = self.position.squares[square];
+        let target = self.targets.iter().any(|mv| mv.to == square);
+        let light = (square / 8 + square % 8) % 2 == 1;
+        let colour = if square == self.cursor {
+            CURSOR
+        } else if self.selected == Some(square) {
+            SELECTED
+        } else if target && piece.is_some() {
+            CAPTURE
+        } else if checked == Some(square) {
+            render::CHECK
+        } else if last.contains(&square) {
+            if light {
+                render::LAST_LIGHT
+            } else {
+                render::LAST_DARK
+            }
+        } else if light {
+            render::LIGHT
+        } else {
+            render::DARK
+        };
+        let (foreground, glyph) = match piece {
+            Some((_type, Sides::White)) => {
+                (render::WHITE_PIECE, render::glyph(_type, Sides::Black))
+            }
+            Some((_type, Sides::Black)) => {
+                (render::BLACK_PIECE, render::glyph(_type, Sides::Black))
+            }
+            None if target => (TARGET, '•'),
+            None => ("", ' '),
+        };
+        format!("{}{} {} {}", background(colour), foreground, glyph, RESET)
+    }
+
+    // One row of the evaluation bar
+    fn bar(&self, row: usize) -> String {
+        let white = (share(self.evaluation.unwrap_or(0)) * 8.0).round() as usize;
+        let is_white = if self.flipped {
+            row < white
+        } else {
+            row >= 8 - white
+        };
+        let colour = if is_white { BAR_WHITE } else { BAR_BLACK };
+        format!("{}  {}", background(colour), RESET)
+    }
+
+    fn player(&self, side: Sides, now: Instant) -> String {
+        let marker = if self.result.is_none() && self.position.side_to_move == side {
+            '>'
+        } else {
+            ' '
+        };
+        let name = if side == self.human { "You" } else { "Engine" };
+        let mut text = format!("{} {:?} ({})", marker, side, name);
+        if let Some(clock) = &self.clock {
+            text.push_str(&format!("  {}", clock::format(clock.remaining(side, now))));
+        }
+        text
+    }
+
+    // What goes to the right of the board, a line for each row
+    fn panel(&self, now: Instant) -> Vec<String> {
+        let (top, bottom) = if self.flipped {
+            (Sides::White, Sides::Black)
+        } else {
+            (Sides::Black, Sides::White)
+        };
+        let mut panel = vec![self.player(top, now)];
+        panel.push(match self.evaluation {
+            Some(evaluation) => format!("  Evaluation {}", Score::from(evaluation)),
+            None => String::new(),
+        });
+        let pairs: Vec<String> = self
+            .moves
+            .chunks(2)
+            .enumerate()
+            .map(|(number, pair)| format!("  {:>3}. {}", number + 1, pair.join(" ")))
+            .collect();
+        panel.extend(pairs.iter().skip(pairs.len().saturating_sub(5)).cloned());
+        panel.resize(7, String::new());
+        panel.push(self.player(bottom, now));
+        panel
+    }
+
+    fn status(&self) -> String {
+        if let Some((_, choice)) = self.promotion {
+            let mut text = String::from("Promote to: ");
+            for (index, _type) in PROMOTIONS.iter().enumerate() {
+                let colour = if index == choice {
+                    CURSOR
+                } else {
+                    render::LIGHT
+                };
+                text.push_str(&format!(
+                    "{}{} {} {}",
+                    background(colour),
+                    render::BLACK_PIECE,
+                    render::glyph(*_type, Sides::Black),
+                    RESET
+                ));
+            }
+            return text;
+        }
+        match &self.result {
+            Some(result) => format!("{}  (n for a new game, u to take back)", result),
+            None if self.is_thinking() => "Thinking...".to_string(),
+            None if self.position.in_check() => "Your move, you're in check".to_string(),
+            None => "Your move".to_string(),
+        }
+    }
+
+    // The whole screen, drawn over what was there before
+    pub fn draw(&self, now: Instant) -> String {
+        let mut last = Vec::new();
+        if let Some(mv) = self.position.history.last().and_then(|state| state.mv) {
+            last.push(mv.from);
+            last.push(mv.to);
+        }
+        let checked = if self.position.in_check() {
+            Some(self.position.king_square(self.position.side_to_move))
+        } else {
+            None
+        };
+        let panel = self.panel(now);
+
+        let mut lines = vec![
+            format!("rust-chess: you play {:?}", self.human),
+            String::new(),
+        ];
+        for (row, text) in panel.iter().enumerate() {
+            let rank = self.square_at(row, 0) / 8 + 1;
+            let mut line = format!("{} ", rank);
+            for column in 0..8 {
+                line.push_str(&self.cell(self.square_at(row, column), &last, checked));
+            }
+            line.push_str("  ");
+            line.push_str(&self.bar(row));
+            line.push(' ');
+            line.push_str(text);
+            lines.push(line);
+        }
+        let mut files = " ".repeat(BOARD_LEFT);
+        for column in 0..8 {
+            let file = self.square_at(0, column) % 8;
+            files.push_str(&format!(" {} ", (b'a' + file as u8) as char));
+        }
+        lines.push(files);
+        lines.push(String::new());
+        lines.push(self.status());
+        lines.push(HELP.to_string());
+
+        format!("\x1b[H{}\x1b[K\x1b[J", lines.join("\x1b[K\r\n"))
+    }
+}
+
+// A game full screen until the player quits, with `clock` for both sides if
+// it's played on one
+pub fn run(human: Sides, skill: Skill, clock: Option<Clock>, params: Params) -> Result<(), String> {
+    let limits = match clock {
+        Some(_) => Limits::default(),
+        None => Limits {
+            time: Some(play::THINKING_TIME),
+            ..Default::default()
+        },
+    };
+    let mut tui = Tui::new(human, skill, limits, clock);
+    tui.set_params(params);
+    let _raw = terminal::RawMode::enter()?;
+    let events = terminal::listen();
+    let mut stdout = io::stdout();
+    loop {
+        tui.update(Instant::now())?;
+        write!(stdout, "{}", tui.draw(Instant::now()))
+            .and_then(|_| stdout.flush())
+            .map_err(|error| error.to_string())?;
+        match events.recv_timeout(REFRESH) {
+            Ok(event) => {
+                if !tui.event(event, Instant::now()) {
+                    break;
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+    tui.cancel();
+    Ok(())
+}
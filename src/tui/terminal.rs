@@ -0,0 +1,138 @@
+use std::io::{self, Read, Write};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+// The terminal side of the TUI. Raw mode comes from stty, which keeps this
+// free of anything platform specific beyond a Unix terminal, and everything
+// else is ANSI escape sequences: the alternate screen, a hidden cursor and
+// mouse reporting in the SGR format, where clicks come as "ESC [ < b ; x ; y M".
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    Up,
+    Down,
+    Left,
+    Right,
+    Enter,
+    Escape,
+    Char(char),
+    // A left click, with the terminal's row and column counted from 1
+    Click(usize, usize),
+    // Ctrl-C, which raw mode hands over instead of sending a signal
+    Interrupt,
+}
+
+const ENTER_SCREEN: &str = "\x1b[?1049h\x1b[?25l\x1b[?1000h\x1b[?1006h";
+const LEAVE_SCREEN: &str = "\x1b[?1006l\x1b[?1000l\x1b[?25h\x1b[?1049l";
+
+fn stty(arguments: &[&str]) -> Result<String, String> {
+    let output = Command::new("stty")
+        .args(arguments)
+        .stdin(Stdio::inherit())
+        .output()
+        .map_err(|error| format!("can't run stty: {}", error))?;
+    if !output.status.success() {
+        return Err("stty failed; is standard input a terminal?".to_string());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+// The terminal in raw mode on the alternate screen, for as long as this is
+// around
+pub struct RawMode {
+    saved: String,
+}
+
+impl RawMode {
+    pub fn enter() -> Result<RawMode, String> {
+        let saved = stty(&["-g"])?;
+        stty(&["raw", "-echo"])?;
+        let mut stdout = io::stdout();
+        let _ = write!(stdout, "{}", ENTER_SCREEN).and_then(|_| stdout.flush());
+        Ok(RawMode { saved })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let mut stdout = io::stdout();
+        let _ = write!(stdout, "{}", LEAVE_SCREEN).and_then(|_| stdout.flush());
+        let _ = stty(&[&self.saved]);
+    }
+}
+
+// Mouse reports after the "ESC [ <": "0;12;5M" is a left click at column 12
+// of row 5
+fn mouse(report: &str) -> Option<Event> {
+    let (fields, action) = report.split_at(report.len().checked_sub(1)?);
+    let fields: Vec<usize> = fields
+        .split(';')
+        .map(|field| field.parse().ok())
+        .collect::<Option<_>>()?;
+    match (fields.as_slice(), action) {
+        ([0, column, row], "M") => Some(Event::Click(*row, *column)),
+        _ => None,
+    }
+}
+
+// The events in what one read from the terminal got. Keys come in one read
+// at a time, so an escape on its own is the key and not the start of a
+// sequence.
+pub fn events(input: &[u8]) -> Vec<Event> {
+    let text = String::from_utf8_lossy(input);
+    let mut events = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        let event = match c {
+            '\x1b' => match chars.peek() {
+                Some('[') | Some('O') => {
+                    chars.next();
+                    let mut sequence = String::new();
+                    for c in chars.by_ref() {
+                        sequence.push(c);
+                        if c.is_ascii_alphabetic() || c == '~' {
+                            break;
+                        }
+                    }
+                    match sequence.as_str() {
+                        "A" => Some(Event::Up),
+                        "B" => Some(Event::Down),
+                        "C" => Some(Event::Right),
+                        "D" => Some(Event::Left),
+                        _ => sequence.strip_prefix('<').and_then(mouse),
+                    }
+                }
+                _ => Some(Event::Escape),
+            },
+            '\r' | '\n' | ' ' => Some(Event::Enter),
+            '\x03' => Some(Event::Interrupt),
+            c if !c.is_control() => Some(Event::Char(c)),
+            _ => None,
+        };
+        events.extend(event);
+    }
+    events
+}
+
+// Events from standard input as they come, read on a thread of their own so
+// the screen can carry on updating in between
+pub fn listen() -> Receiver<Event> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let mut stdin = io::stdin();
+        let mut buffer = [0; 256];
+        loop {
+            let read = match stdin.read(&mut buffer) {
+                Ok(0) | Err(_) => return,
+                Ok(read) => read,
+            };
+            for event in events(&buffer[..read]) {
+                if sender.send(event).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+    receiver
+}
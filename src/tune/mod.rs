@@ -0,0 +1,198 @@
+use crate::eval::{Evaluator, Params};
+use crate::piece::Sides;
+use crate::position::{self, Position};
+use std::fs;
+use std::path::Path;
+use std::thread;
+
+// Texel's tuning method: turn the evaluation of each position into an
+// expected score with a logistic curve, compare that to how the game it came
+// from actually ended, and nudge the evaluation parameters one at a time for
+// as long as that makes the average squared difference smaller.
+//
+// The evaluation is used as it is, without any search, so the positions
+// should be quiet ones: nothing hanging and not in check.
+
+// Each thread takes at least this many positions, so small sets don't pay
+// for threads they don't need
+const SAMPLES_PER_THREAD: usize = 1024;
+
+#[derive(Debug, Clone)]
+pub struct Sample {
+    pub position: Position,
+    // How the game ended for White: 1 for a win, 0.5 for a draw, 0 for a loss
+    pub result: f64,
+}
+
+fn parse_result(token: &str) -> Option<f64> {
+    match token.trim_matches(|letter| "[]\"';|,".contains(letter)) {
+        "1-0" | "1.0" | "1" => Some(1.0),
+        "1/2-1/2" | "0.5" | "1/2" => Some(0.5),
+        "0-1" | "0.0" | "0" => Some(0.0),
+        _ => None,
+    }
+}
+
+// One position a line: a FEN, with or without its move counters, and then
+// the result, which can be written as "1-0" or "1.0", in brackets, in quotes
+// or after a semicolon, the ways the usual data sets have it
+pub fn parse_sample(line: &str) -> Result<Sample, String> {
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    let at = tokens
+        .iter()
+        .rposition(|token| parse_result(token).is_some())
+        .filter(|at| *at >= 4)
+        .ok_or(format!("no result in: {}", line))?;
+    let counters = tokens[4..at]
+        .iter()
+        .take(2)
+        .take_while(|token| token.parse::<u32>().is_ok())
+        .count();
+    let fen = tokens[..4 + counters].join(" ");
+    Ok(Sample {
+        position: position::from_fen(&fen)?,
+        result: parse_result(tokens[at]).unwrap_or(0.5),
+    })
+}
+
+// Every sample in `text`, skipping blank lines
+pub fn parse_samples(text: &str) -> Result<Vec<Sample>, String> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(number, line)| {
+            parse_sample(line).map_err(|error| format!("line {}: {}", number + 1, error))
+        })
+        .collect()
+}
+
+pub fn load_samples<P: AsRef<Path>>(path: P) -> Result<Vec<Sample>, String> {
+    let path = path.as_ref();
+    let text = fs::read_to_string(path)
+        .map_err(|error| format!("can't read {}: {}", path.display(), error))?;
+    parse_samples(&text)
+}
+
+// The expected score for White of a position White leads by `score`
+// centipawns in. `k` sets how quickly that goes to a sure win.
+pub fn sigmoid(score: f64, k: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-k * score / 400.0))
+}
+
+pub struct Tuner {
+    samples: Vec<Sample>,
+    pub k: f64,
+    threads: usize,
+}
+
+impl Tuner {
+    pub fn new(samples: Vec<Sample>) -> Tuner {
+        let available = thread::available_parallelism().map_or(1, |threads| threads.get());
+        let threads = samples
+            .len()
+            .div_ceil(SAMPLES_PER_THREAD)
+            .clamp(1, available);
+        Tuner {
+            samples,
+            k: 1.0,
+            threads,
+        }
+    }
+
+    pub fn samples(&self) -> usize {
+        self.samples.len()
+    }
+
+    // Mean squared difference between the predicted and actual results
+    pub fn error(&self, params: &Params) -> f64 {
+        if self.samples.is_empty() {
+            return 0.0;
+        }
+        let chunk = self.samples.len().div_ceil(self.threads);
+        let total: f64 = thread::scope(|scope| {
+            let handles: Vec<_> = self
+                .samples
+                .chunks(chunk)
+                .map(|samples| scope.spawn(move || self.chunk_error(params, samples)))
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().expect("a tuning thread panicked"))
+                .sum()
+        });
+        total / self.samples.len() as f64
+    }
+
+    fn chunk_error(&self, params: &Params, samples: &[Sample]) -> f64 {
+        let mut evaluator = Evaluator::with_params(params.clone());
+        samples
+            .iter()
+            .map(|sample| {
+                let score = evaluator.evaluate(&sample.position);
+                let white = match sample.position.side_to_move {
+                    Sides::White => score,
+                    Sides::Black => -score,
+                };
+                (sample.result - sigmoid(white as f64, self.k)).powi(2)
+            })
+            .sum()
+    }
+
+    // Finds the k that fits these parameters best, before tuning them, so
+    // the tuner doesn't just scale every value up or down to fit the curve
+    pub fn fit_k(&mut self, params: &Params) -> f64 {
+        let (mut low, mut high) = (0.0, 5.0);
+        for _ in 0..40 {
+            let third = (high - low) / 3.0;
+            self.k = low + third;
+            let lower = self.error(params);
+            self.k = high - third;
+            let upper = self.error(params);
+            if lower < upper {
+                high -= third;
+            } else {
+                low += third;
+            }
+        }
+        self.k = (low + high) / 2.0;
+        self.k
+    }
+
+    // Local search: try moving each value up and then down by one, keep
+    // whichever makes the error smaller, and go round again until a whole
+    // pass finds nothing or `passes` run out. `progress` hears about the
+    // error after every pass.
+    pub fn tune<F: FnMut(usize, f64)>(
+        &self,
+        params: &Params,
+        passes: usize,
+        mut progress: F,
+    ) -> Params {
+        let mut params = params.clone();
+        let mut values = params.values();
+        let mut best = self.error(&params);
+
+        for pass in 1..=passes {
+            let mut improved = false;
+            for index in 0..values.len() {
+                for step in [1, -1].iter() {
+                    values[index] += step;
+                    params.set_values(&values);
+                    let error = self.error(&params);
+                    if error < best {
+                        best = error;
+                        improved = true;
+                        break;
+                    }
+                    values[index] -= step;
+                }
+            }
+            params.set_values(&values);
+            progress(pass, best);
+            if !improved {
+                break;
+            }
+        }
+        params
+    }
+}
@@ -0,0 +1,423 @@
+use crate::book::Book;
+use crate::eval::nnue::Network;
+use crate::eval::personality::Personality;
+use crate::eval::Params;
+use crate::piece::Sides;
+use crate::position::{self, Position};
+use crate::search::background::Background;
+use crate::search::skill::{self, Skill};
+use crate::search::{self, time, Limits, Score, SearchResult, Searcher};
+use crate::tablebase::Tablebases;
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::time::Duration;
+
+// The Universal Chess Interface: commands come in a line at a time and
+// everything the engine has to say goes out as lines on `output`. Searches
+// run on a thread of their own so the engine can still take `stop` and
+// `isready` while it thinks, and print their `info` lines as each depth is
+// done and `bestmove` at the end.
+
+const NAME: &str = "rust-chess";
+const AUTHOR: &str = "Justin Werner";
+
+const MAX_HASH: usize = 4096;
+const MAX_THREADS: usize = 256;
+const MAX_LINES: usize = 256;
+
+pub struct Uci {
+    output: Sender<String>,
+    position: Position,
+    // Away with the search while there is one
+    searcher: Option<Searcher>,
+    search: Option<Background>,
+    skill_level: u32,
+    limit_strength: bool,
+    elo: u32,
+    // EvalParams, or the built in values, for the personality to lean on
+    params: Params,
+    personality: Personality,
+    // Castling is the king taking its rook, in Chess960 positions or not
+    chess960: bool,
+    // UCI_Variant, which is either chess or crazyhouse
+    crazyhouse: bool,
+}
+
+fn score(score: i32) -> String {
+    match Score::from(score) {
+        Score::Centipawns(centipawns) => format!("cp {}", centipawns),
+        Score::Mate(moves) => format!("mate {}", moves),
+    }
+}
+
+fn moves(moves: &[position::Move]) -> String {
+    let moves: Vec<String> = moves.iter().map(|mv| mv.to_string()).collect();
+    moves.join(" ")
+}
+
+// One info line for each line the search has
+pub fn info(result: &SearchResult) -> Vec<String> {
+    result
+        .lines
+        .iter()
+        .enumerate()
+        .map(|(index, line)| {
+            format!(
+                "info depth {} multipv {} score {} nodes {} nps {} tbhits {} time {} pv {}",
+                result.depth,
+                index + 1,
+                score(line.score),
+                result.nodes,
+                result.nps(),
+                result.tb_hits,
+                result.time.as_millis(),
+                moves(&line.pv)
+            )
+        })
+        .collect()
+}
+
+pub fn best_move(result: &SearchResult) -> String {
+    match (result.best_move, result.ponder_move()) {
+        (Some(mv), Some(ponder)) => format!("bestmove {} ponder {}", mv, ponder),
+        (Some(mv), None) => format!("bestmove {}", mv),
+        (None, _) => "bestmove 0000".to_string(),
+    }
+}
+
+// The words after `key`, up to the next of `keys`
+fn words_after<'a>(words: &[&'a str], key: &str, keys: &[&str]) -> Option<Vec<&'a str>> {
+    let start = words
+        .iter()
+        .position(|word| word.eq_ignore_ascii_case(key))?
+        + 1;
+    Some(
+        words[start..]
+            .iter()
+            .take_while(|word| !keys.iter().any(|key| word.eq_ignore_ascii_case(key)))
+            .copied()
+            .collect(),
+    )
+}
+
+impl Uci {
+    pub fn new(output: Sender<String>) -> Uci {
+        Uci {
+            output,
+            position: position::start(),
+            searcher: Some(Searcher::new(search::Options::default())),
+            search: None,
+            skill_level: skill::MAX_LEVEL,
+            limit_strength: false,
+            elo: Skill::full().elo(),
+            params: Params::default(),
+            personality: Personality::Balanced,
+            chess960: false,
+            crazyhouse: false,
+        }
+    }
+
+    fn send<S: Into<String>>(&self, line: S) {
+        // Nobody to tell if the other end has gone
+        let _ = self.output.send(line.into());
+    }
+
+    // Carries out one command, returning false once it's time to quit
+    pub fn command(&mut self, line: &str) -> bool {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let (command, arguments) = match words.split_first() {
+            Some((command, arguments)) => (*command, arguments),
+            None => return true,
+        };
+        let outcome = match command {
+            "uci" => {
+                self.identify();
+                Ok(())
+            }
+            "isready" => {
+                self.send("readyok");
+                Ok(())
+            }
+            "ucinewgame" => {
+                self.stop();
+                self.searcher().clear();
+                Ok(())
+            }
+            "position" => self.set_position(arguments),
+            "go" => self.go(arguments),
+            "stop" => {
+                self.stop();
+                Ok(())
+            }
+            "ponderhit" => {
+                if let Some(search) = &self.search {
+                    search.ponder_hit();
+                }
+                Ok(())
+            }
+            "setoption" => self.set_option(arguments),
+            "debug" | "register" => Ok(()),
+            "quit" => {
+                self.stop();
+                return false;
+            }
+            _ => Err(format!("unknown command: {}", line.trim())),
+        };
+        if let Err(error) = outcome {
+            self.send(format!("info string {}", error));
+        }
+        true
+    }
+
+    fn identify(&self) {
+        self.send(format!("id name {} {}", NAME, env!("CARGO_PKG_VERSION")));
+        self.send(format!("id author {}", AUTHOR));
+        self.send(format!(
+            "option name Hash type spin default {} min 1 max {}",
+            search::tt::DEFAULT_MEGABYTES,
+            MAX_HASH
+        ));
+        self.send(format!(
+            "option name Threads type spin default 1 min 1 max {}",
+            MAX_THREADS
+        ));
+        self.send(format!(
+            "option name MultiPV type spin default 1 min 1 max {}",
+            MAX_LINES
+        ));
+        self.send("option name Ponder type check default false");
+        self.send(format!(
+            "option name Skill Level type spin default {} min 0 max {}",
+            skill::MAX_LEVEL,
+            skill::MAX_LEVEL
+        ));
+        self.send("option name UCI_LimitStrength type check default false");
+        self.send(format!(
+            "option name UCI_Elo type spin default {} min {} max {}",
+            Skill::full().elo(),
+            Skill::level(0).elo(),
+            Skill::full().elo()
+        ));
+        self.send("option name SyzygyPath type string default <empty>");
+        self.send("option name BookFile type string default <empty>");
+        self.send("option name EvalFile type string default <empty>");
+        self.send("option name EvalParams type string default <empty>");
+        let personalities: Vec<String> = Personality::ALL
+            .iter()
+            .map(|personality| format!("var {}", personality.name()))
+            .collect();
+        self.send(format!(
+            "option name Personality type combo default {} {}",
+            Personality::Balanced.name(),
+            personalities.join(" ")
+        ));
+        self.send("option name UCI_Chess960 type check default false");
+        self.send("option name UCI_Variant type combo default chess var chess var crazyhouse");
+        self.send("option name Clear Hash type button");
+        self.send("uciok");
+    }
+
+    // The searcher, once any search that has it is over
+    fn searcher(&mut self) -> &mut Searcher {
+        self.stop();
+        self.searcher
+            .as_mut()
+            .expect("the searcher comes back when a search ends")
+    }
+
+    // Ends any search, which then gives its answer, and takes the searcher
+    // back
+    fn stop(&mut self) {
+        if let Some(search) = self.search.take() {
+            self.searcher = Some(search.stop().0);
+        }
+    }
+
+    // position startpos|fen <fen> [moves <move>...]
+    fn set_position(&mut self, arguments: &[&str]) -> Result<(), String> {
+        let mut position = match arguments.first() {
+            Some(&"startpos") => position::start(),
+            Some(&"fen") => {
+                let fen = words_after(arguments, "fen", &["moves"]).unwrap_or_default();
+                position::from_fen(&fen.join(" "))?
+            }
+            _ => return Err("expected startpos or fen".to_string()),
+        };
+        position.chess960 |= self.chess960;
+        position.crazyhouse |= self.crazyhouse;
+        for text in words_after(arguments, "moves", &[]).unwrap_or_default() {
+            let mv = position
+                .parse_move(text)
+                .ok_or(format!("illegal move: {}", text))?;
+            position.make_move(mv);
+        }
+        self.position = position;
+        Ok(())
+    }
+
+    fn go(&mut self, arguments: &[&str]) -> Result<(), String> {
+        let number = |key: &str| -> Result<Option<u64>, String> {
+            match arguments.iter().position(|word| *word == key) {
+                Some(index) => arguments
+                    .get(index + 1)
+                    .and_then(|value| value.parse::<i64>().ok())
+                    .map(|value| Some(value.max(0) as u64))
+                    .ok_or(format!("expected a number after {}", key)),
+                None => Ok(None),
+            }
+        };
+        let millis = |key: &str| number(key).map(|value| value.map(Duration::from_millis));
+        let infinite = arguments.contains(&"infinite");
+        let ponder = arguments.contains(&"ponder");
+
+        let (clock, increment) = match self.position.side_to_move {
+            Sides::White => (millis("wtime")?, millis("winc")?),
+            Sides::Black => (millis("btime")?, millis("binc")?),
+        };
+        let mut limits = Limits {
+            depth: number("depth")?.map(|depth| depth as u32),
+            nodes: number("nodes")?,
+            time: millis("movetime")?,
+            ponder,
+        };
+        if limits.time.is_none() && !infinite {
+            if let Some(clock) = clock {
+                let moves_to_go = number("movestogo")?.map(|moves| moves as u32);
+                limits.time = Some(time::allocate(
+                    clock,
+                    increment.unwrap_or_default(),
+                    moves_to_go,
+                ));
+            }
+        }
+
+        self.stop();
+        let mut searcher = self
+            .searcher
+            .take()
+            .expect("the searcher comes back when a search ends");
+        let output = self.output.clone();
+        searcher.set_reporter(Some(Box::new(move |result: &SearchResult| {
+            for line in info(result) {
+                let _ = output.send(line);
+            }
+        })));
+
+        let output = self.output.clone();
+        self.search = Some(Background::start(
+            searcher,
+            self.position.clone(),
+            limits,
+            infinite,
+            move |result| {
+                let _ = output.send(best_move(result));
+            },
+        )?);
+        Ok(())
+    }
+
+    // setoption name <name> [value <value>]
+    fn set_option(&mut self, arguments: &[&str]) -> Result<(), String> {
+        let name = words_after(arguments, "name", &["value"])
+            .ok_or("expected an option name")?
+            .join(" ")
+            .to_ascii_lowercase();
+        let value = words_after(arguments, "value", &[])
+            .unwrap_or_default()
+            .join(" ");
+        let number = || {
+            value
+                .parse::<usize>()
+                .map_err(|_| format!("expected a number for {}", name))
+        };
+        // Paths can be left empty to switch the feature off
+        let path = || match value.as_str() {
+            "" | "<empty>" => None,
+            path => Some(path.to_string()),
+        };
+
+        match name.as_str() {
+            "hash" => {
+                let megabytes = number()?.clamp(1, MAX_HASH);
+                self.searcher().set_hash_size(megabytes);
+            }
+            "threads" => {
+                let threads = number()?.clamp(1, MAX_THREADS);
+                self.searcher().set_threads(threads);
+            }
+            "multipv" => {
+                let lines = number()?.clamp(1, MAX_LINES);
+                self.searcher().set_multi_pv(lines);
+            }
+            "ponder" => {}
+            "skill level" => {
+                self.skill_level = number()? as u32;
+                self.update_skill();
+            }
+            "uci_limitstrength" => {
+                self.limit_strength = value.eq_ignore_ascii_case("true");
+                self.update_skill();
+            }
+            "uci_elo" => {
+                self.elo = number()? as u32;
+                self.update_skill();
+            }
+            "syzygypath" => {
+                let tablebases = match path() {
+                    Some(path) => Some(Arc::new(Tablebases::open(path)?)),
+                    None => None,
+                };
+                self.searcher().set_tablebases(tablebases);
+            }
+            "bookfile" => {
+                let book = match path() {
+                    Some(path) => Some(Arc::new(Book::open(path)?)),
+                    None => None,
+                };
+                self.searcher().set_book(book);
+            }
+            "evalfile" => {
+                let network = match path() {
+                    Some(path) => Some(Arc::new(Network::load(path)?)),
+                    None => None,
+                };
+                self.searcher().evaluator.network = network;
+            }
+            "evalparams" => {
+                self.params = match path() {
+                    Some(path) => Params::load(path)?,
+                    None => Params::default(),
+                };
+                self.update_params();
+            }
+            "personality" => {
+                self.personality = Personality::from_name(&value)
+                    .ok_or(format!("no personality called {}", value))?;
+                self.update_params();
+            }
+            "uci_chess960" => self.chess960 = value.eq_ignore_ascii_case("true"),
+            "uci_variant" => match value.to_ascii_lowercase().as_str() {
+                "chess" => self.crazyhouse = false,
+                "crazyhouse" => self.crazyhouse = true,
+                _ => return Err(format!("no variant called {}", value)),
+            },
+            "clear hash" => self.searcher().clear(),
+            _ => return Err(format!("no option called {}", name)),
+        }
+        Ok(())
+    }
+
+    fn update_skill(&mut self) {
+        let skill = if self.limit_strength {
+            Skill::from_elo(self.elo)
+        } else {
+            Skill::level(self.skill_level)
+        };
+        self.searcher().set_skill(skill);
+    }
+
+    fn update_params(&mut self) {
+        let params = self.params.with_personality(self.personality);
+        self.searcher().set_params(params);
+    }
+}